@@ -0,0 +1,441 @@
+//! Asynchronous variants of the module and the network session.
+
+use core::{
+    convert::TryFrom,
+    future::{poll_fn, Future},
+    pin::pin,
+    task::Poll,
+};
+
+use embedded_hal_async::delay::DelayNs;
+use embedded_io_async::{Read, Write};
+use heapless::{String, Vec};
+
+use crate::{
+    buffered::SEND_MAX_LEN,
+    error::ensure,
+    ipv4::{format_ipv4, IPV4_MAX_LEN},
+    module::{
+        find_subsequence, skip_unsolicited_frames, AtCommand, CarretCondition, Condition, Escaped,
+        ExpectOk, OkCondition, RawResponse, DEFAULT_BUFFER_LEN,
+    },
+    net::{IpAddr, SocketAddr},
+    network_session::{link_mask, MAX_LINKS},
+    parser::CommandResponse,
+    reader_part::{truncate_buf, PendingData, ReadData},
+    Error, JoinApConfig, NetworkEvent,
+};
+
+/// Maximum length of the AT command sent by the asynchronous module.
+const COMMAND_MAX_LEN: usize = 256;
+/// Maximum number of bytes read from the serial port at once.
+const READ_CHUNK_LEN: usize = 64;
+
+/// Asynchronous communication interface with the esp8266 module.
+///
+/// Unlike the [`Module`](crate::Module), it awaits the serial port and the timer instead
+/// of polling them in a busy loop, so the executor can run the other tasks or put the core
+/// into a sleep mode while the module is processing a command.
+///
+/// The serial port is any [`embedded_io_async`] implementation, e.g. the buffered UART
+/// of the embassy HALs, and the timer is any [`DelayNs`] implementation. Only the ESP-AT
+/// 2.x firmware command set is supported.
+#[derive(Debug)]
+pub struct AsyncModule<S, D, const N: usize = DEFAULT_BUFFER_LEN>
+where
+    S: Read + Write,
+    D: DelayNs,
+{
+    serial: S,
+    delay: D,
+    buf: Vec<u8, N>,
+    timeout: Option<u64>,
+}
+
+impl<S, D, const N: usize> AsyncModule<S, D, N>
+where
+    S: Read + Write,
+    D: DelayNs,
+{
+    /// Establishes serial communication with the esp8266 module.
+    pub async fn new(serial: S, delay: D) -> crate::Result<Self> {
+        let mut module = Self {
+            serial,
+            delay,
+            buf: Vec::new(),
+            timeout: None,
+        };
+        module.send_at_command("ATE0").await?.expect_ok()?;
+        Ok(module)
+    }
+
+    /// Sets the operation timeout in microseconds.
+    ///
+    /// If the specified value is `None`, the operations will wait infinitely.
+    pub fn set_timeout(&mut self, us: Option<u64>) {
+        self.timeout = us;
+    }
+
+    /// Returns the operation timeout in microseconds.
+    pub fn timeout(&self) -> Option<u64> {
+        self.timeout
+    }
+
+    /// Sends an AT command and gets the response for it.
+    pub async fn send_at_command<T: AtCommand>(
+        &mut self,
+        cmd: T,
+    ) -> crate::Result<RawResponse<'_, N>> {
+        self.write_command(cmd).await?;
+        self.read_until(OkCondition).await
+    }
+
+    async fn write_command<T: AtCommand>(&mut self, cmd: T) -> crate::Result<()> {
+        let mut raw: String<COMMAND_MAX_LEN> = String::new();
+        cmd.write_to(&mut raw).map_err(|_| Error::BufferFull)?;
+
+        self.write_all(raw.as_bytes()).await?;
+        self.write_all(b"\r\n").await?;
+        // Make sure the command doesn't sit in the TX buffer while the deadline ticks.
+        self.serial.flush().await.map_err(|_| Error::WriteBuffer)
+    }
+
+    async fn write_all(&mut self, bytes: &[u8]) -> crate::Result<()> {
+        self.serial
+            .write_all(bytes)
+            .await
+            .map_err(|_| Error::WriteBuffer)
+    }
+
+    async fn read_until<'b, T>(&'b mut self, condition: T) -> crate::Result<T::Output>
+    where
+        T: Condition<'b, N>,
+    {
+        // The unsolicited frames received while waiting for the response are left before
+        // the response, so the network session will handle them later.
+        let mut kept = self.buf.len();
+        let timeout = self.timeout;
+        self.read_while(timeout, |buf| {
+            kept = skip_unsolicited_frames(buf, kept);
+            !condition.is_performed(&buf[kept..])
+        })
+        .await?;
+
+        Ok(condition.output(ReadData::new(&mut self.buf, kept)))
+    }
+
+    /// Reads bytes into the buffer while the given predicate is true.
+    ///
+    /// Returns the [`Error::Timeout`] error if the predicate is still true after the given
+    /// time in microseconds.
+    async fn read_while<F>(&mut self, timeout: Option<u64>, mut predicate: F) -> crate::Result<()>
+    where
+        F: FnMut(&[u8]) -> bool,
+    {
+        let Self {
+            serial, delay, buf, ..
+        } = self;
+
+        let mut deadline = pin!(async move {
+            match timeout {
+                Some(us) => delay_us(delay, us).await,
+                None => core::future::pending().await,
+            }
+        });
+
+        while predicate(buf) {
+            if buf.is_full() {
+                return Err(Error::BufferFull);
+            }
+
+            let mut chunk = [0_u8; READ_CHUNK_LEN];
+            let len = core::cmp::min(chunk.len(), buf.capacity() - buf.len());
+            let res = {
+                let mut read = pin!(serial.read(&mut chunk[..len]));
+                poll_fn(|cx| {
+                    if let Poll::Ready(res) = read.as_mut().poll(cx) {
+                        return Poll::Ready(res.map_err(|_| Error::ReadBuffer));
+                    }
+                    deadline.as_mut().poll(cx).map(|()| Err(Error::Timeout))
+                })
+                .await
+            };
+
+            match res? {
+                // The serial port has been closed.
+                0 => return Err(Error::ReadBuffer),
+                // The length is limited by the free space of the buffer.
+                len => buf.extend_from_slice(&chunk[..len]).ok(),
+            };
+        }
+        Ok(())
+    }
+}
+
+impl<'a> JoinApConfig<'a> {
+    /// Joins to the existing access point and establishing a new asynchronous WiFi session.
+    ///
+    /// The extra [options](JoinApConfig::options) are not supported, the
+    /// [`Error::Unsupported`] error is returned if they are set.
+    pub async fn join_async<S, D, const N: usize>(
+        self,
+        mut module: AsyncModule<S, D, N>,
+    ) -> crate::Result<AsyncNetworkSession<S, D, N>>
+    where
+        S: Read + Write,
+        D: DelayNs,
+    {
+        self.validate()?;
+        if self.options.is_some() {
+            return Err(Error::Unsupported);
+        }
+
+        // Enable Station mode.
+        module.send_at_command("AT+CWMODE=1").await?.expect_ok()?;
+        // Enable multiple connections.
+        module.send_at_command("AT+CIPMUX=1").await?.expect_ok()?;
+        module.send_at_command("AT+CIPDINFO=1").await?.ok();
+
+        module
+            .send_at_command(format_args!(
+                "AT+CWJAP=\"{}\",\"{}\"",
+                Escaped(self.ssid),
+                Escaped(self.password.unwrap_or_default()),
+            ))
+            .await?
+            .map_err(|_| Error::JoinApError)?;
+
+        Ok(AsyncNetworkSession {
+            module,
+            closed_locally: 0,
+            pending_data: None,
+        })
+    }
+}
+
+/// Asynchronous session with the typical network operations.
+///
+/// The session is created by the [`JoinApConfig::join_async`] method.
+#[derive(Debug)]
+pub struct AsyncNetworkSession<S, D, const N: usize = DEFAULT_BUFFER_LEN>
+where
+    S: Read + Write,
+    D: DelayNs,
+{
+    module: AsyncModule<S, D, N>,
+    /// Bitmask of the links that have been closed by the driver.
+    closed_locally: u8,
+    /// Partially consumed data that remains in the reader buffer.
+    pending_data: Option<PendingData>,
+}
+
+impl<S, D, const N: usize> AsyncNetworkSession<S, D, N>
+where
+    S: Read + Write,
+    D: DelayNs,
+{
+    /// Begins to listen to the incoming TCP connections on the specified port.
+    pub async fn listen(&mut self, port: u16) -> crate::Result<()> {
+        self.module
+            .send_at_command(format_args!("AT+CIPSERVER=1,{}", port))
+            .await?
+            .expect_ok()?;
+
+        Ok(())
+    }
+
+    /// Establishes a TCP connection with the specified IP address, link identifier will
+    /// be associated with the given IP address.
+    pub async fn connect(&mut self, link_id: usize, address: SocketAddr) -> crate::Result<()> {
+        let mut ip_buf = [0; IPV4_MAX_LEN];
+        let ip = address.ip();
+        let ip: &dyn core::fmt::Display = match &ip {
+            IpAddr::V4(ip) => &format_ipv4(*ip, &mut ip_buf),
+            IpAddr::V6(ip) => ip,
+        };
+
+        self.module
+            .send_at_command(format_args!(
+                "AT+CIPSTART={},\"TCP\",\"{}\",{}",
+                link_id,
+                ip,
+                address.port(),
+            ))
+            .await?
+            .map_err(|_| Error::CommandFailed)?;
+
+        Ok(())
+    }
+
+    /// Closes the TCP connection with the given link identifier.
+    ///
+    /// The corresponding [`NetworkEvent::Closed`] event will be marked as closed locally.
+    pub async fn close(&mut self, link_id: usize) -> crate::Result<()> {
+        ensure!(
+            link_id < MAX_LINKS,
+            "Link identifier should be less than the maximum number of connections"
+        );
+
+        self.module
+            .send_at_command(format_args!("AT+CIPCLOSE={}", link_id))
+            .await?
+            .map_err(|_| Error::CommandFailed)?;
+
+        self.closed_locally |= link_mask(link_id as u16);
+        Ok(())
+    }
+
+    /// Sends data packet via the TCP socket with the link given identifier.
+    ///
+    /// # Notes
+    ///
+    /// No more than 2048 bytes can be sent at a time.
+    pub async fn send(&mut self, link_id: usize, bytes: &[u8]) -> crate::Result<()> {
+        ensure!(
+            bytes.len() <= SEND_MAX_LEN,
+            "Total packet size should not be greater than the 2048 bytes"
+        );
+
+        self.module
+            .write_command(format_args!("AT+CIPSEND={},{}", link_id, bytes.len()))
+            .await?;
+        self.module.read_until(CarretCondition).await?;
+
+        self.module.write_all(bytes).await?;
+        self.module
+            .serial
+            .flush()
+            .await
+            .map_err(|_| Error::WriteBuffer)?;
+
+        self.module
+            .read_until(OkCondition)
+            .await?
+            .map_err(|_| Error::CommandFailed)?;
+        Ok(())
+    }
+
+    /// Waits for a new network event.
+    ///
+    /// The waiting is not limited by the operation timeout, use the timer of the executor,
+    /// e.g. the `embassy_time::with_timeout`, to limit it.
+    pub async fn next_event(&mut self) -> crate::Result<NetworkEvent<'_, N>> {
+        if let Some(data) = self.pending_data.take() {
+            return Ok(self.data_event(data.link_id, data.len, data.remote));
+        }
+
+        loop {
+            let buf = &self.module.buf;
+            let parsed = CommandResponse::parse(buf).map(|(rest, response)| (rest.len(), response));
+            if let Some((remaining_bytes, response)) = parsed {
+                let pos = buf.len() - remaining_bytes;
+                truncate_buf(&mut self.module.buf, pos);
+                return self.event(response).await;
+            }
+
+            match find_subsequence(buf, b"\r\n") {
+                Some(0) => {
+                    truncate_buf(&mut self.module.buf, 2);
+                    continue;
+                }
+                // The module reboot wipes out all the links.
+                Some(len) if &buf[..len] == b"ready" => {
+                    truncate_buf(&mut self.module.buf, len + 2);
+                    self.closed_locally = 0;
+                    return Ok(NetworkEvent::ModuleRebooted);
+                }
+                Some(len) => {
+                    let mut line = ReadData::new(&mut self.module.buf, 0);
+                    // Remove the line together with its terminator.
+                    line.cut(len, 2);
+                    return Ok(NetworkEvent::Unknown(line));
+                }
+                None => {}
+            }
+
+            let len = buf.len();
+            self.module.read_while(None, |buf| buf.len() == len).await?;
+        }
+    }
+
+    async fn event(&mut self, response: CommandResponse) -> crate::Result<NetworkEvent<'_, N>> {
+        let event = match response {
+            CommandResponse::Connected { link_id } => {
+                self.closed_locally &= !link_mask(link_id);
+                NetworkEvent::Connected { link_id }
+            }
+            // The flag will be reset by the next connection with the same link identifier.
+            CommandResponse::Closed { link_id } => NetworkEvent::Closed {
+                link_id,
+                closed_locally: self.closed_locally & link_mask(link_id) != 0,
+            },
+            CommandResponse::DataAvailable {
+                link_id,
+                size,
+                remote,
+            } => {
+                let size = usize::try_from(size).map_err(|_| Error::BufferFull)?;
+                if size > N {
+                    return Err(Error::BufferFull);
+                }
+                // The rest of the data is received by the module timeout.
+                let timeout = self.module.timeout;
+                self.module
+                    .read_while(timeout, |buf| buf.len() < size)
+                    .await?;
+                self.data_event(link_id, size, remote)
+            }
+            CommandResponse::DataBuffered { link_id, size } => NetworkEvent::DataBuffered {
+                link_id,
+                len: size as usize,
+            },
+            CommandResponse::WifiDisconnect => NetworkEvent::WifiDisconnected,
+            CommandResponse::WifiConnected => NetworkEvent::WifiConnected,
+            CommandResponse::WifiGotIp => NetworkEvent::WifiGotIp,
+        };
+        Ok(event)
+    }
+
+    fn data_event(
+        &mut self,
+        link_id: u16,
+        len: usize,
+        remote: Option<SocketAddr>,
+    ) -> NetworkEvent<'_, N> {
+        let data = ReadData::received(
+            &mut self.module.buf,
+            len,
+            link_id,
+            remote,
+            &mut self.pending_data,
+            false,
+        );
+        NetworkEvent::DataAvailable {
+            link_id,
+            remote,
+            len: data.len(),
+            data,
+        }
+    }
+
+    /// Sends an AT command and gets the response for it.
+    pub async fn send_at_command<T: AtCommand>(
+        &mut self,
+        cmd: T,
+    ) -> crate::Result<RawResponse<'_, N>> {
+        self.module.send_at_command(cmd).await
+    }
+}
+
+/// Waits for the given number of microseconds, the too long delays are waited
+/// in milliseconds.
+async fn delay_us<D: DelayNs>(delay: &mut D, us: u64) {
+    match u32::try_from(us) {
+        Ok(us) => delay.delay_us(us).await,
+        Err(_) => {
+            delay
+                .delay_ms(u32::try_from(us / 1_000).unwrap_or(u32::MAX))
+                .await
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Exponential backoff of the repeated attempts.
+
+use simple_clock::SimpleClock;
+
+/// Bounded exponential backoff with jitter.
+///
+/// Each next delay is twice as long as the previous one until it reaches the maximum delay.
+/// The actual delay is randomly chosen from the upper half of the current backoff interval,
+/// so the devices which lost the connection at the same time don't retry in lockstep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    initial_us: u64,
+    max_us: u64,
+    max_attempts: Option<u32>,
+    attempt: u32,
+    seed: u64,
+}
+
+impl Backoff {
+    /// Creates a new backoff with the given initial and maximum delays in microseconds.
+    ///
+    /// By default, the number of attempts is not limited.
+    pub const fn new(initial_us: u64, max_us: u64) -> Self {
+        Self {
+            initial_us,
+            max_us,
+            max_attempts: None,
+            attempt: 0,
+            seed: 0,
+        }
+    }
+
+    /// Limits the number of the attempts.
+    pub const fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    /// Returns the number of the delays returned since the last reset.
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Starts the backoff from the initial delay.
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    /// Returns the delay in microseconds before the next attempt, or `None` if the
+    /// number of the attempts is exhausted.
+    ///
+    /// The clock is used as a source of the jitter.
+    pub fn next_delay<C: SimpleClock>(&mut self, clock: &C) -> Option<u64> {
+        if matches!(self.max_attempts, Some(max_attempts) if self.attempt >= max_attempts) {
+            return None;
+        }
+
+        let interval = self
+            .initial_us
+            .checked_shl(self.attempt)
+            .filter(|&interval| interval >> self.attempt == self.initial_us)
+            .map_or(self.max_us, |interval| interval.min(self.max_us));
+        self.attempt = self.attempt.saturating_add(1);
+
+        let half = interval / 2;
+        Some(half + self.random(clock) % (interval - half + 1))
+    }
+
+    /// Generates a pseudo-random number by the xorshift algorithm mixed with the clock.
+    fn random<C: SimpleClock>(&mut self, clock: &C) -> u64 {
+        let mut x = self.seed ^ clock.now_us() ^ 0x9e37_79b9_7f4a_7c15;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.seed = x;
+        x
+    }
+}
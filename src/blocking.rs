@@ -0,0 +1,103 @@
+//! Blocking facade over the network session.
+
+use embedded_hal::serial;
+use simple_clock::SimpleClock;
+
+use crate::{
+    net::SocketAddr, NetworkEvent, NetworkSession, OwnedNetworkEvent, Result, SessionInfo,
+    DEFAULT_BUFFER_LEN,
+};
+
+/// Network session with the blocking methods that don't use the [`nb`] crate.
+///
+/// This facade is convenient for the simple super-loop firmwares, all the methods block
+/// until the operation completes or the operation [timeout](NetworkSession::timeout) is
+/// reached.
+#[derive(Debug)]
+pub struct BlockingSession<Rx, Tx, C, const N: usize = DEFAULT_BUFFER_LEN>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    session: NetworkSession<Rx, Tx, C, N>,
+}
+
+impl<Rx, Tx, C, const N: usize> BlockingSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Creates a blocking facade over the given network session.
+    pub fn new(session: NetworkSession<Rx, Tx, C, N>) -> Self {
+        Self { session }
+    }
+
+    /// Returns the underlying network session.
+    pub fn into_inner(self) -> NetworkSession<Rx, Tx, C, N> {
+        self.session
+    }
+
+    /// Returns a reference to the underlying network session.
+    pub fn session(&self) -> &NetworkSession<Rx, Tx, C, N> {
+        &self.session
+    }
+
+    /// Returns a mutable reference to the underlying network session.
+    pub fn session_mut(&mut self) -> &mut NetworkSession<Rx, Tx, C, N> {
+        &mut self.session
+    }
+
+    /// Begins to listen to the incoming TCP connections on the specified port.
+    pub fn listen(&mut self, port: u16) -> Result<()> {
+        self.session.listen(port)
+    }
+
+    /// Establishes a TCP connection with the specified IP address.
+    ///
+    /// See [`NetworkSession::connect`] for details.
+    pub fn connect(&mut self, link_id: usize, address: SocketAddr) -> Result<()> {
+        self.session.connect(link_id, address)
+    }
+
+    /// Closes the TCP connection with the given link identifier.
+    pub fn close(&mut self, link_id: usize) -> Result<()> {
+        self.session.close(link_id)
+    }
+
+    /// Sends bytes via the TCP connection with the given link identifier.
+    ///
+    /// See [`NetworkSession::send`] for details.
+    pub fn send(&mut self, link_id: usize, bytes: &[u8]) -> Result<()> {
+        self.session.send(link_id, bytes.iter().copied())
+    }
+
+    /// Waits for a new network event.
+    pub fn next_event(&mut self) -> Result<NetworkEvent<'_, N>> {
+        self.session.wait_for(|_| true)
+    }
+
+    /// Waits for a new network event that doesn't borrow the session.
+    ///
+    /// The received data larger than `M` bytes is split into the several events.
+    pub fn next_event_owned<const M: usize>(&mut self) -> Result<OwnedNetworkEvent<M>> {
+        self.next_event().map(NetworkEvent::into_owned)
+    }
+
+    /// Gets network session information.
+    pub fn get_info(&mut self) -> Result<SessionInfo> {
+        self.session.get_info()
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> From<NetworkSession<Rx, Tx, C, N>> for BlockingSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    fn from(session: NetworkSession<Rx, Tx, C, N>) -> Self {
+        Self::new(session)
+    }
+}
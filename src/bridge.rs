@@ -0,0 +1,109 @@
+//! Ready-made TCP servers built on top of the network session.
+
+use core::convert::Infallible;
+
+use embedded_hal::serial;
+use simple_clock::SimpleClock;
+
+use crate::{Error, NetworkEvent, NetworkSession};
+
+/// Maximum size of the data forwarded at a time.
+const CHUNK_LEN: usize = 256;
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Runs the TCP server on the given port that sends all the received data back to
+    /// the peer.
+    ///
+    /// It's useful as a smoke test during the hardware bring-up. This method returns only
+    /// if an error occurs.
+    pub fn run_echo_server(&mut self, port: u16) -> crate::Result<Infallible> {
+        self.listen(port)?;
+        loop {
+            match self.echo_once() {
+                Ok(()) | Err(nb::Error::WouldBlock) => {}
+                Err(nb::Error::Other(err)) => return Err(err),
+            }
+        }
+    }
+
+    /// Handles a next network event of the echo server.
+    pub(crate) fn echo_once(&mut self) -> nb::Result<(), Error> {
+        let mut buf = [0_u8; CHUNK_LEN];
+        let (link_id, len) = match self.poll_network_event()? {
+            NetworkEvent::DataAvailable {
+                link_id, mut data, ..
+            } => (link_id, data.read(&mut buf)),
+            _ => return Ok(()),
+        };
+        self.send(usize::from(link_id), buf[..len].iter().copied())?;
+        Ok(())
+    }
+
+    /// Runs the TCP server on the given port that bridges the peer with a local byte stream,
+    /// e.g. a serial port.
+    ///
+    /// The `read` function should copy the available local bytes into the given buffer
+    /// without blocking and return their number, these bytes are sent to the last connected
+    /// peer. The data received from the peer is passed to the `write` function.
+    ///
+    /// This method returns only if an error occurs.
+    pub fn run_bridge<R, W>(
+        &mut self,
+        port: u16,
+        mut read: R,
+        mut write: W,
+    ) -> crate::Result<Infallible>
+    where
+        R: FnMut(&mut [u8]) -> usize,
+        W: FnMut(&[u8]),
+    {
+        self.listen(port)?;
+        let mut peer = None;
+        loop {
+            self.bridge_once(&mut peer, &mut read, &mut write)?;
+        }
+    }
+
+    /// Forwards a next chunk of data in the both directions.
+    pub(crate) fn bridge_once<R, W>(
+        &mut self,
+        peer: &mut Option<usize>,
+        read: &mut R,
+        write: &mut W,
+    ) -> crate::Result<()>
+    where
+        R: FnMut(&mut [u8]) -> usize,
+        W: FnMut(&[u8]),
+    {
+        match self.poll_network_event() {
+            Ok(NetworkEvent::Connected { link_id }) => *peer = Some(usize::from(link_id)),
+            Ok(NetworkEvent::Closed { link_id, .. }) => {
+                if *peer == Some(usize::from(link_id)) {
+                    *peer = None;
+                }
+            }
+            Ok(NetworkEvent::DataAvailable { data, .. }) => write(&data),
+            Ok(NetworkEvent::ModuleRebooted { .. }) => *peer = None,
+            Ok(NetworkEvent::DataBuffered { .. })
+            | Ok(NetworkEvent::WifiConnected)
+            | Ok(NetworkEvent::WifiGotIp)
+            | Ok(NetworkEvent::WifiDisconnected)
+            | Ok(NetworkEvent::Unknown(_))
+            | Err(nb::Error::WouldBlock) => {}
+            Err(nb::Error::Other(err)) => return Err(err),
+        }
+
+        let mut buf = [0_u8; CHUNK_LEN];
+        let len = read(&mut buf);
+        // The local data is dropped if there is no connected peer.
+        if let (Some(link_id), true) = (*peer, len > 0) {
+            self.send(link_id, buf[..len].iter().copied())?;
+        }
+        Ok(())
+    }
+}
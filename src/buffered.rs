@@ -0,0 +1,381 @@
+//! Buffered adapters over the network session links.
+
+use core::fmt;
+
+use embedded_hal::serial;
+use heapless::Vec;
+use simple_clock::SimpleClock;
+
+use crate::{Error, NetworkEvent, NetworkSession};
+
+/// Maximum size of the data that can be sent by a single command.
+pub(crate) const SEND_MAX_LEN: usize = 2048;
+
+/// Writer that accumulates small writes to the link and sends them by a single command.
+///
+/// Every [`send`](NetworkSession::send) call costs a full `AT+CIPSEND` handshake, so sending
+/// a lot of small pieces of data kills the throughput. The buffered data is sent once the
+/// buffer of `B` bytes is full, on the explicit [`flush`](Self::flush) call or when the writer
+/// is dropped. Errors that happen during sending on drop are ignored.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut writer = session.buf_writer::<256>(link_id);
+/// for value in values {
+///     write!(writer, "{};", value)?;
+/// }
+/// writer.flush()?;
+/// ```
+pub struct BufWriter<'a, Rx, Tx, C, const N: usize, const B: usize>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    session: &'a mut NetworkSession<Rx, Tx, C, N>,
+    link_id: usize,
+    buf: Vec<u8, B>,
+}
+
+impl<'a, Rx, Tx, C, const N: usize, const B: usize> BufWriter<'a, Rx, Tx, C, N, B>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// The buffer size is checked at compile time, the whole buffer should fit into a single
+    /// send command.
+    const BUFFER_SIZE_CHECK: () = assert!(
+        B > 0 && B <= SEND_MAX_LEN,
+        "Buffer size should be in range from 1 to 2048 bytes"
+    );
+
+    /// Creates a new buffered writer to the link with the given identifier.
+    pub fn new(session: &'a mut NetworkSession<Rx, Tx, C, N>, link_id: usize) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::BUFFER_SIZE_CHECK;
+
+        Self {
+            session,
+            link_id,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Returns the link identifier.
+    pub fn link_id(&self) -> usize {
+        self.link_id
+    }
+
+    /// Returns the buffered data that hasn't been sent yet.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Writes the bytes into the buffer, sending the buffered data each time the buffer
+    /// is full.
+    pub fn write(&mut self, mut bytes: &[u8]) -> crate::Result<()> {
+        while !bytes.is_empty() {
+            if self.buf.is_full() {
+                self.flush()?;
+            }
+
+            let len = core::cmp::min(B - self.buf.len(), bytes.len());
+            self.buf
+                .extend_from_slice(&bytes[..len])
+                .map_err(|_| Error::BufferFull)?;
+            bytes = &bytes[len..];
+        }
+        Ok(())
+    }
+
+    /// Sends the buffered data.
+    pub fn flush(&mut self) -> crate::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+
+        // The data is discarded if sending fails, so the next data won't be sent after
+        // the lost one.
+        let result = self.session.send(self.link_id, self.buf.iter().copied());
+        self.buf.clear();
+        result
+    }
+}
+
+impl<'a, Rx, Tx, C, const N: usize, const B: usize> fmt::Write for BufWriter<'a, Rx, Tx, C, N, B>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+impl<'a, Rx, Tx, C, const N: usize, const B: usize> Drop for BufWriter<'a, Rx, Tx, C, N, B>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    fn drop(&mut self) {
+        self.flush().ok();
+    }
+}
+
+/// Reader that buffers the data received from the link.
+///
+/// The `+IPD` segment boundaries don't match the application message boundaries, so this
+/// reader allows to read the data regardless of how it has been split into segments. The
+/// segments longer than the buffer of `B` bytes are read by parts.
+///
+/// The reader polls the network events by itself and discards the events of the other
+/// links, so it's intended for the sessions with a single active link.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut reader = session.buf_reader::<64>(link_id);
+/// let mut header = [0_u8; 4];
+/// reader.read_exact(&mut header)?;
+///
+/// let mut line = [0_u8; 82];
+/// while let Some(sentence) = reader.read_line(&mut line)? {
+///     handle_nmea(sentence);
+/// }
+/// ```
+pub struct BufReader<'a, Rx, Tx, C, const N: usize, const B: usize>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    session: &'a mut NetworkSession<Rx, Tx, C, N>,
+    link_id: usize,
+    buf: [u8; B],
+    pos: usize,
+    len: usize,
+    /// The link has been closed.
+    eof: bool,
+    /// Timeout of the blocking read in microseconds.
+    read_timeout: Option<u64>,
+}
+
+impl<'a, Rx, Tx, C, const N: usize, const B: usize> BufReader<'a, Rx, Tx, C, N, B>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// The buffer size is checked at compile time.
+    const BUFFER_SIZE_CHECK: () = assert!(B > 0, "Buffer size should not be zero");
+
+    /// Creates a new buffered reader from the link with the given identifier.
+    pub fn new(session: &'a mut NetworkSession<Rx, Tx, C, N>, link_id: usize) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::BUFFER_SIZE_CHECK;
+
+        Self {
+            session,
+            link_id,
+            buf: [0; B],
+            pos: 0,
+            len: 0,
+            eof: false,
+            read_timeout: None,
+        }
+    }
+
+    /// Returns the link identifier.
+    pub fn link_id(&self) -> usize {
+        self.link_id
+    }
+
+    /// Returns the buffered data that hasn't been consumed yet.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.pos..self.len]
+    }
+
+    /// Sets the timeout of the [`read_blocking`](Self::read_blocking) method in microseconds,
+    /// `None` means that the method waits forever.
+    pub fn set_read_timeout(&mut self, us: Option<u64>) {
+        self.read_timeout = us;
+    }
+
+    /// Returns the timeout of the [`read_blocking`](Self::read_blocking) method.
+    pub fn read_timeout(&self) -> Option<u64> {
+        self.read_timeout
+    }
+
+    /// Receives a next segment into the buffer if the buffer is empty.
+    fn fill(&mut self) -> nb::Result<(), Error> {
+        if self.pos < self.len || self.eof {
+            return Ok(());
+        }
+
+        match self.session.poll_network_event()? {
+            NetworkEvent::DataAvailable {
+                link_id, mut data, ..
+            } if usize::from(link_id) == self.link_id => {
+                self.pos = 0;
+                self.len = data.read(&mut self.buf);
+                Ok(())
+            }
+            NetworkEvent::Closed { link_id, .. } if usize::from(link_id) == self.link_id => {
+                self.eof = true;
+                Ok(())
+            }
+            NetworkEvent::ModuleRebooted { .. } => {
+                self.eof = true;
+                Ok(())
+            }
+            // The data of the other links is discarded, otherwise the retained data would
+            // block the link forever.
+            NetworkEvent::DataAvailable { data, .. } => {
+                data.discard();
+                Err(nb::Error::WouldBlock)
+            }
+            _ => Err(nb::Error::WouldBlock),
+        }
+    }
+
+    /// Returns the buffered data, receiving a next segment if the buffer is empty.
+    ///
+    /// The returned bytes should be marked as consumed by the [`consume`](Self::consume)
+    /// method. An empty slice means that the link has been closed.
+    pub fn fill_buf(&mut self) -> nb::Result<&[u8], Error> {
+        self.fill()?;
+        Ok(self.buffer())
+    }
+
+    /// Marks the given number of the buffered bytes as consumed.
+    pub fn consume(&mut self, amt: usize) {
+        self.pos = core::cmp::min(self.pos + amt, self.len);
+    }
+
+    /// Reads the data into the given buffer.
+    ///
+    /// Returns the number of bytes read, zero means that the link has been closed.
+    pub fn read(&mut self, buf: &mut [u8]) -> nb::Result<usize, Error> {
+        self.fill()?;
+
+        let bytes = self.buffer();
+        let len = core::cmp::min(bytes.len(), buf.len());
+        buf[..len].copy_from_slice(&bytes[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+
+    /// Blocks until the given buffer is filled, the link is closed or the
+    /// [read timeout](Self::set_read_timeout) is reached.
+    ///
+    /// Returns the number of bytes read, the bytes that have been received before the timeout
+    /// are returned as well. The `Timeout` error is returned only if no bytes have been
+    /// received at all, zero means that the link has been closed.
+    pub fn read_blocking(&mut self, buf: &mut [u8]) -> crate::Result<usize> {
+        let started_at = self.session.clock().now_us();
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.read(&mut buf[filled..]) {
+                Ok(0) => break,
+                Ok(len) => filled += len,
+                Err(nb::Error::WouldBlock) => {
+                    let elapsed = self.session.clock().now_us().saturating_sub(started_at);
+                    if matches!(self.read_timeout, Some(timeout) if elapsed >= timeout) {
+                        if filled == 0 {
+                            return Err(Error::Timeout);
+                        }
+                        break;
+                    }
+                }
+                Err(nb::Error::Other(err)) => return Err(err),
+            }
+        }
+        Ok(filled)
+    }
+
+    /// Blocks until the given buffer is completely filled.
+    ///
+    /// Returns the `ConnectionClosed` error if the link has been closed earlier.
+    pub fn read_exact(&mut self, buf: &mut [u8]) -> crate::Result<()> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match nb::block!(self.read(&mut buf[filled..]))? {
+                0 => return Err(Error::ConnectionClosed),
+                len => filled += len,
+            }
+        }
+        Ok(())
+    }
+
+    /// Blocks until a complete line is received and copies it into the given buffer.
+    ///
+    /// Returns the line without the `\n` or `\r\n` terminator, the last line may have no
+    /// terminator at all. `None` means that the link has been closed and there is no more
+    /// data. If the line doesn't fit into the buffer, it is skipped and the `BufferFull`
+    /// error is returned, so the next call reads the next line.
+    pub fn read_line<'b>(&mut self, buf: &'b mut [u8]) -> crate::Result<Option<&'b [u8]>> {
+        let mut len = 0;
+        let mut overflow = false;
+        loop {
+            let bytes = nb::block!(self.fill_buf())?;
+            if bytes.is_empty() {
+                return match (overflow, len) {
+                    (true, _) => Err(Error::BufferFull),
+                    (false, 0) => Ok(None),
+                    (false, _) => Ok(Some(trim_cr(&buf[..len]))),
+                };
+            }
+
+            let end = bytes.iter().position(|byte| *byte == b'\n');
+            let chunk = &bytes[..end.unwrap_or(bytes.len())];
+            if len + chunk.len() > buf.len() {
+                overflow = true;
+            }
+            if !overflow {
+                buf[len..len + chunk.len()].copy_from_slice(chunk);
+                len += chunk.len();
+            }
+
+            let chunk_len = chunk.len();
+            match end {
+                Some(_) => {
+                    self.consume(chunk_len + 1);
+                    return if overflow {
+                        Err(Error::BufferFull)
+                    } else {
+                        Ok(Some(trim_cr(&buf[..len])))
+                    };
+                }
+                None => self.consume(chunk_len),
+            }
+        }
+    }
+}
+
+/// Removes the trailing carriage return of the line.
+fn trim_cr(line: &[u8]) -> &[u8] {
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Creates a buffered writer with the buffer of `B` bytes to the link with the given
+    /// identifier.
+    pub fn buf_writer<const B: usize>(&mut self, link_id: usize) -> BufWriter<'_, Rx, Tx, C, N, B> {
+        BufWriter::new(self, link_id)
+    }
+
+    /// Creates a buffered reader with the buffer of `B` bytes from the link with the given
+    /// identifier.
+    pub fn buf_reader<const B: usize>(&mut self, link_id: usize) -> BufReader<'_, Rx, Tx, C, N, B> {
+        BufReader::new(self, link_id)
+    }
+}
@@ -0,0 +1,193 @@
+//! Optional features supported by the module firmware.
+
+use core::ops::{BitOr, BitOrAssign};
+
+use embedded_hal::serial;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use simple_clock::SimpleClock;
+
+use crate::{parser::find_line_value, Chip, Dialect, Module};
+
+/// Version of the AT firmware reported by the `AT+GMR` command.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct AtVersion {
+    /// Major version number.
+    pub major: u8,
+    /// Minor version number.
+    pub minor: u8,
+    /// Patch version number.
+    pub patch: u8,
+}
+
+impl AtVersion {
+    /// Parses the version from the `AT+GMR` response, e.g. `AT version:1.7.4.0(May 11 2020)`.
+    pub(crate) fn parse(resp: &[u8]) -> Option<Self> {
+        let value = find_line_value(resp, "AT version:")?;
+        let end = value
+            .iter()
+            .position(|byte| !(byte.is_ascii_digit() || *byte == b'.'))
+            .unwrap_or(value.len());
+
+        let mut parts = core::str::from_utf8(&value[..end]).ok()?.split('.');
+        Some(Self {
+            major: parts.next()?.parse().ok()?,
+            minor: parts.next()?.parse().ok()?,
+            patch: parts.next().map_or(Some(0), |part| part.parse().ok())?,
+        })
+    }
+}
+
+/// Set of the optional commands supported by the module firmware.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Capabilities(u16);
+
+impl Capabilities {
+    /// Resolving domain names by the `AT+CIPDOMAIN` command.
+    pub const DOMAIN_RESOLVE: Self = Self(1 << 0);
+    /// Passive receiving mode set by the `AT+CIPRECVMODE` command.
+    pub const PASSIVE_RECEIVE: Self = Self(1 << 1);
+    /// SNTP time synchronization by the `AT+CIPSNTPCFG` command.
+    pub const SNTP: Self = Self(1 << 2);
+    /// Light-sleep wakeup sources configured by the `AT+SLEEPWKCFG` command.
+    pub const SLEEP_WAKEUP: Self = Self(1 << 3);
+    /// Free heap size reported by the `AT+SYSRAM` command.
+    pub const SYSTEM_RAM: Self = Self(1 << 4);
+    /// WiFi station state reported by the `AT+CWSTATE` command.
+    pub const WIFI_STATE: Self = Self(1 << 5);
+    /// RF TX power reported by the `AT+RFPOWER` command.
+    pub const RF_POWER: Self = Self(1 << 6);
+    /// Authentication of the SSL links configured by the `AT+CIPSSLCCONF` command.
+    pub const SSL_CONFIG: Self = Self(1 << 7);
+
+    /// Commands that are used to probe the capabilities.
+    const PROBES: [(Self, &'static str); 8] = [
+        (Self::DOMAIN_RESOLVE, "AT+CIPDOMAIN=?"),
+        (Self::PASSIVE_RECEIVE, "AT+CIPRECVMODE?"),
+        (Self::SNTP, "AT+CIPSNTPCFG?"),
+        (Self::SLEEP_WAKEUP, "AT+SLEEPWKCFG=?"),
+        (Self::SYSTEM_RAM, "AT+SYSRAM?"),
+        (Self::WIFI_STATE, "AT+CWSTATE?"),
+        (Self::RF_POWER, "AT+RFPOWER?"),
+        (Self::SSL_CONFIG, "AT+CIPSSLCCONF?"),
+    ];
+
+    /// Returns an empty set of the capabilities.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns the set of all the known capabilities.
+    pub const fn all() -> Self {
+        Self((1 << Self::PROBES.len()) - 1)
+    }
+
+    /// Returns `true` if all of the given capabilities are supported.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the raw value of the bitset.
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Capabilities {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> Module<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Returns the version of the module AT firmware if it has been recognized.
+    pub fn at_version(&self) -> Option<AtVersion> {
+        self.at_version
+    }
+
+    /// Returns the set of the optional commands that are known to be supported by the module
+    /// firmware.
+    ///
+    /// The commands are probed lazily before their first use, call the
+    /// [`probe_capabilities`](Self::probe_capabilities) method to probe all of them at once.
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    /// Probes all the optional commands that haven't been probed yet and returns the set
+    /// of the supported ones.
+    pub fn probe_capabilities(&mut self) -> crate::Result<Capabilities> {
+        self.probe(Capabilities::all())?;
+        Ok(self.capabilities)
+    }
+
+    /// Checks that the optional command is supported by the module firmware.
+    pub(crate) fn require(&mut self, capabilities: Capabilities) -> crate::Result<()> {
+        self.probe(capabilities)?;
+        if self.capabilities.contains(capabilities) {
+            Ok(())
+        } else {
+            Err(crate::Error::Unsupported)
+        }
+    }
+
+    /// Sends the query command like the [`query`](Self::query) method if it is supported
+    /// by the module firmware, otherwise returns `None`.
+    pub(crate) fn query_supported<F, R>(
+        &mut self,
+        capabilities: Capabilities,
+        cmd: &str,
+        parse: F,
+    ) -> crate::Result<Option<R>>
+    where
+        F: FnOnce(&[u8]) -> Option<R>,
+    {
+        if self.require(capabilities).is_err() {
+            return Ok(None);
+        }
+        self.query(cmd, parse)
+    }
+
+    /// Gets the firmware version and the chip model.
+    pub(crate) fn identify(&mut self) -> crate::Result<()> {
+        let (at_version, chip) = self
+            .query("AT+GMR", |resp| {
+                Some((AtVersion::parse(resp), Chip::parse(resp)))
+            })?
+            .unwrap_or_default();
+        self.at_version = at_version;
+        self.dialect = at_version.map(Dialect::from_version).unwrap_or_default();
+        self.chip = chip.unwrap_or_default();
+        Ok(())
+    }
+
+    /// Probes the given optional commands unless they have been probed already.
+    fn probe(&mut self, capabilities: Capabilities) -> crate::Result<()> {
+        for (capability, cmd) in Capabilities::PROBES.iter() {
+            if !capabilities.contains(*capability) || self.probed.contains(*capability) {
+                continue;
+            }
+
+            if self.send_at_command(*cmd)?.is_ok() {
+                self.capabilities |= *capability;
+            }
+            self.probed |= *capability;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+//! Captive portal for the device onboarding in the SoftAP mode.
+
+use core::fmt::Write;
+
+use embedded_hal::serial;
+use heapless::String;
+use simple_clock::SimpleClock;
+
+use crate::{net::Ipv4Addr, network_session::link_mask, Error, NetworkEvent, NetworkSession};
+
+/// Link identifier of the DNS responder socket, the rest of the links are used by
+/// the HTTP server.
+pub const CAPTIVE_PORTAL_DNS_LINK: usize = 4;
+
+const DNS_PORT: u16 = 53;
+const HTTP_PORT: u16 = 80;
+/// Maximum size of the DNS message over UDP.
+pub(crate) const DNS_MESSAGE_MAX_LEN: usize = 512;
+/// Size of the DNS message header.
+const DNS_HEADER_LEN: usize = 12;
+/// Time to live of the DNS answers in seconds, it's kept short so the clients forget
+/// the hijacked addresses soon after the onboarding.
+const DNS_ANSWER_TTL: u32 = 60;
+/// Maximum size of the data sent by a single command.
+const SEND_CHUNK_LEN: usize = 1024;
+
+/// Captive portal that answers all DNS queries with the access point address and serves
+/// the landing page to any HTTP request.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut session = SoftApConfig { .. }.start(module)?;
+/// let address = match session.get_info()?.softap_address {
+///     Some(IpAddr::V4(address)) => address,
+///     _ => unreachable!(),
+/// };
+///
+/// let mut portal = CaptivePortal::new(address, LANDING_PAGE);
+/// portal.start(&mut session)?;
+/// loop {
+///     nb::block!(portal.poll(&mut session))?;
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct CaptivePortal<'a> {
+    address: Ipv4Addr,
+    page: &'a str,
+    /// Bitmask of the links that have been answered, the rest of their requests
+    /// is ignored.
+    answered: u8,
+}
+
+impl<'a> CaptivePortal<'a> {
+    /// Creates a new captive portal with the given access point address and the landing
+    /// page HTML.
+    pub fn new(address: Ipv4Addr, page: &'a str) -> Self {
+        Self {
+            address,
+            page,
+            answered: 0,
+        }
+    }
+
+    /// Starts the DNS responder and the HTTP server.
+    ///
+    /// This method should be called before any other [listen](NetworkSession::listen) call.
+    pub fn start<Rx, Tx, C, const N: usize>(
+        &self,
+        session: &mut NetworkSession<Rx, Tx, C, N>,
+    ) -> crate::Result<()>
+    where
+        Rx: serial::Read<u8> + 'static,
+        Tx: serial::Write<u8> + 'static,
+        C: SimpleClock,
+    {
+        session.set_max_connections(CAPTIVE_PORTAL_DNS_LINK as u8)?;
+        session.listen(HTTP_PORT)?;
+        session.listen_udp(CAPTIVE_PORTAL_DNS_LINK, DNS_PORT)
+    }
+
+    /// Handles a next network event.
+    ///
+    /// Each connection is answered once, the rest of the request is ignored.
+    ///
+    /// Returns `WouldBlock` if there are no new events.
+    pub fn poll<Rx, Tx, C, const N: usize>(
+        &mut self,
+        session: &mut NetworkSession<Rx, Tx, C, N>,
+    ) -> nb::Result<(), crate::Error>
+    where
+        Rx: serial::Read<u8> + 'static,
+        Tx: serial::Write<u8> + 'static,
+        C: SimpleClock,
+    {
+        let mut buf = [0_u8; DNS_MESSAGE_MAX_LEN];
+        let (link_id, len) = match session.poll_network_event()? {
+            NetworkEvent::DataAvailable {
+                link_id, mut data, ..
+            } => {
+                let len = data.read(&mut buf);
+                // The tail of the oversized message is not needed to answer it.
+                data.discard();
+                (link_id, len)
+            }
+            NetworkEvent::Connected { link_id } => {
+                self.answered &= !link_mask(link_id);
+                return Ok(());
+            }
+            NetworkEvent::ModuleRebooted => {
+                self.answered = 0;
+                return Ok(());
+            }
+            _ => return Ok(()),
+        };
+
+        if usize::from(link_id) == CAPTIVE_PORTAL_DNS_LINK {
+            self.answer_dns(session, &mut buf, len)?;
+        } else if self.answered & link_mask(link_id) == 0 {
+            self.answered |= link_mask(link_id);
+            send_html(session, usize::from(link_id), self.page)?;
+        }
+        Ok(())
+    }
+
+    /// Answers the DNS query of the given length in the buffer with the portal address.
+    pub(crate) fn answer_dns<Rx, Tx, C, const N: usize>(
+        &self,
+        session: &mut NetworkSession<Rx, Tx, C, N>,
+        buf: &mut [u8],
+        len: usize,
+    ) -> crate::Result<()>
+    where
+        Rx: serial::Read<u8> + 'static,
+        Tx: serial::Write<u8> + 'static,
+        C: SimpleClock,
+    {
+        match dns_answer(buf, len, self.address) {
+            Some(len) => session.send(CAPTIVE_PORTAL_DNS_LINK, buf[..len].iter().copied()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Answers the HTTP request with the given HTML page and closes the connection.
+pub(crate) fn send_html<Rx, Tx, C, const N: usize>(
+    session: &mut NetworkSession<Rx, Tx, C, N>,
+    link_id: usize,
+    page: &str,
+) -> crate::Result<()>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    let mut header = String::<128>::new();
+    write!(
+        header,
+        "HTTP/1.1 200 OK\r\n\
+        Content-Type: text/html; charset=utf-8\r\n\
+        Content-Length: {}\r\n\
+        Connection: close\r\n\r\n",
+        page.len()
+    )
+    .map_err(|_| Error::BufferFull)?;
+
+    session.send(link_id, header.bytes())?;
+    for chunk in page.as_bytes().chunks(SEND_CHUNK_LEN) {
+        session.send(link_id, chunk.iter().copied())?;
+    }
+    session.close(link_id)
+}
+
+/// Turns the DNS query of the given length in the buffer into the answer with the given
+/// address, returns the answer length.
+///
+/// Only the first question is answered, the `A` record is returned for the `A` and `ANY`
+/// queries, the rest of the queries get an empty answer.
+pub(crate) fn dns_answer(buf: &mut [u8], len: usize, address: Ipv4Addr) -> Option<usize> {
+    const TYPE_A: u16 = 1;
+    const TYPE_ANY: u16 = 255;
+
+    let query = buf.get(..len)?;
+    // Skip the responses and the messages without questions.
+    if len < DNS_HEADER_LEN || query[2] & 0x80 != 0 || query[4..6] == [0, 0] {
+        return None;
+    }
+
+    // Find the end of the queried name.
+    let mut pos = DNS_HEADER_LEN;
+    loop {
+        match *query.get(pos)? {
+            0 => break,
+            // The compression is not used in the question.
+            label_len if label_len & 0xC0 == 0 => pos += usize::from(label_len) + 1,
+            _ => return None,
+        }
+    }
+    let qtype = u16::from_be_bytes([*query.get(pos + 1)?, *query.get(pos + 2)?]);
+    // Skip the terminating zero, type and class of the question.
+    let question_end = pos + 5;
+    if question_end > len {
+        return None;
+    }
+
+    let has_answer = qtype == TYPE_A || qtype == TYPE_ANY;
+    let mut answer_end = question_end;
+    if has_answer {
+        // The name is a pointer to the queried one, type A, class IN.
+        let parts: [&[u8]; 4] = [
+            &[0xC0, 0x0C, 0x00, 0x01, 0x00, 0x01],
+            &DNS_ANSWER_TTL.to_be_bytes(),
+            &[0x00, 0x04],
+            &address.octets(),
+        ];
+        for part in parts.iter() {
+            buf.get_mut(answer_end..answer_end + part.len())?
+                .copy_from_slice(part);
+            answer_end += part.len();
+        }
+    }
+
+    // Response, authoritative, recursion is kept as desired and available.
+    buf[2] = 0x84 | (buf[2] & 0x01);
+    buf[3] = 0x80;
+    // One question, the answer if any, no authority and additional records.
+    buf[4..12].copy_from_slice(&[0, 1, 0, has_answer as u8, 0, 0, 0, 0]);
+    Some(answer_end)
+}
@@ -0,0 +1,87 @@
+//! Chips supported by the AT firmware.
+
+use embedded_hal::serial;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use simple_clock::SimpleClock;
+
+use crate::{parser::find_line_value, Module};
+
+/// Chip of the module that runs the AT firmware.
+///
+/// The ESP32-series modules use nearly the same AT command set as the esp8266 module,
+/// so the same driver can talk to them. The command set itself is selected by the
+/// [`Dialect`](crate::Dialect), whereas the chip defines the hardware limits, e.g. the wakeup
+/// GPIO numbers. The chip-specific response lines, like the IPv6 addresses in the
+/// `+CIPSTA:` response of the ESP32 chips, are skipped by the response parsers.
+#[derive(Debug, Default, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Chip {
+    /// The esp8266 chip.
+    #[default]
+    Esp8266,
+    /// The ESP32 chip.
+    Esp32,
+    /// The ESP32-C3 chip.
+    Esp32C3,
+}
+
+impl Chip {
+    /// Recognizes the chip by the `AT+GMR` response, e.g.
+    /// `AT version:2.2.0.0(s-b097cdf - ESP32C3 - Jun 17 2021)`.
+    pub(crate) fn parse(resp: &[u8]) -> Option<Self> {
+        let version = find_line_value(resp, "AT version:")?;
+        let contains = |pattern: &[u8]| {
+            version
+                .windows(pattern.len())
+                .any(|window| window == pattern)
+        };
+
+        if contains(b"ESP32C3") || contains(b"ESP32-C3") {
+            Some(Self::Esp32C3)
+        } else if contains(b"ESP32") {
+            Some(Self::Esp32)
+        } else if contains(b"ESP8266") {
+            Some(Self::Esp8266)
+        } else {
+            None
+        }
+    }
+
+    /// Maximum number of the GPIO pin that can be used as a wakeup source.
+    pub(crate) fn max_wakeup_gpio(self) -> u8 {
+        match self {
+            Self::Esp8266 => 15,
+            Self::Esp32 => 39,
+            Self::Esp32C3 => 21,
+        }
+    }
+
+    /// Maximum RF TX power in 0.25 dBm units.
+    pub(crate) fn max_rf_power(self) -> u8 {
+        match self {
+            Self::Esp8266 => 82,
+            Self::Esp32 | Self::Esp32C3 => 84,
+        }
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> Module<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Returns the chip of the module.
+    ///
+    /// The chip is recognized by the firmware version during the initialization,
+    /// the esp8266 chip is assumed if the firmware doesn't report it.
+    pub fn chip(&self) -> Chip {
+        self.chip
+    }
+
+    /// Overrides the chip of the module.
+    pub fn set_chip(&mut self, chip: Chip) {
+        self.chip = chip;
+    }
+}
@@ -0,0 +1,139 @@
+//! DHCP configuration of the network interfaces.
+
+use core::ops::RangeInclusive;
+
+use embedded_hal::serial;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use simple_clock::SimpleClock;
+
+use crate::{
+    error::ensure,
+    ipv4::{format_ipv4, IPV4_MAX_LEN},
+    net::Ipv4Addr,
+    Dialect, Error, Module, NetworkSession,
+};
+
+/// Allowed range of the DHCP lease time in minutes.
+pub const DHCP_LEASE_TIME_RANGE: RangeInclusive<u16> = 1..=2880;
+
+/// Network interface of the module.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum WifiInterface {
+    /// Station interface connected to the joined access point, uses the DHCP client.
+    Station,
+    /// Software access point interface, uses the DHCP server.
+    SoftAp,
+}
+
+/// Range of the addresses leased by the DHCP server of the software access point.
+///
+/// The addresses should belong to the access point network.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DhcpLeaseRange {
+    /// First leased address.
+    #[cfg_attr(feature = "serde", serde(with = "crate::ipv4::serde_ipv4"))]
+    pub start: Ipv4Addr,
+    /// Last leased address.
+    #[cfg_attr(feature = "serde", serde(with = "crate::ipv4::serde_ipv4"))]
+    pub end: Ipv4Addr,
+    /// Lease time in minutes, should be in the [`DHCP_LEASE_TIME_RANGE`].
+    pub lease_time: u16,
+}
+
+impl<Rx, Tx, C, const N: usize> Module<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Enables or disables DHCP on the given interface.
+    ///
+    /// # Notes
+    ///
+    /// The station without the DHCP client should be configured with the static address,
+    /// and the clients of the access point without the DHCP server should configure their
+    /// addresses by themselves.
+    pub fn set_dhcp(&mut self, interface: WifiInterface, enable: bool) -> crate::Result<()> {
+        // The NonOS firmware swaps the parameters and numbers the interfaces differently.
+        match self.dialect() {
+            Dialect::NonOs => {
+                let mode = match interface {
+                    WifiInterface::SoftAp => 0,
+                    WifiInterface::Station => 1,
+                };
+                self.send_at_command(format_args!("AT+CWDHCP_CUR={},{}", mode, enable as u8))?
+            }
+            Dialect::EspAt => {
+                let mask = match interface {
+                    WifiInterface::Station => 1,
+                    WifiInterface::SoftAp => 2,
+                };
+                self.send_at_command(format_args!("AT+CWDHCP={},{}", enable as u8, mask))?
+            }
+        }
+        .map_err(|_| Error::CommandFailed)?;
+
+        Ok(())
+    }
+
+    /// Sets the range of the addresses leased by the DHCP server of the software access
+    /// point, or restores the module default range if it is `None`.
+    ///
+    /// # Notes
+    ///
+    /// The module rejects the range outside the access point network, so the range should
+    /// be set after the access point [address](crate::SoftApConfig::address).
+    pub fn set_dhcp_lease_range(&mut self, range: Option<DhcpLeaseRange>) -> crate::Result<()> {
+        let suffix = self.dialect().current_suffix();
+        match range {
+            Some(range) => {
+                ensure!(
+                    DHCP_LEASE_TIME_RANGE.contains(&range.lease_time),
+                    "DHCP lease time should be in the allowed range"
+                );
+                ensure!(
+                    range.start <= range.end,
+                    "The first leased address should not be greater than the last one"
+                );
+
+                let mut start_buf = [0_u8; IPV4_MAX_LEN];
+                let mut end_buf = [0_u8; IPV4_MAX_LEN];
+                self.send_at_command(format_args!(
+                    "AT+CWDHCPS{}=1,{},\"{}\",\"{}\"",
+                    suffix,
+                    range.lease_time,
+                    format_ipv4(range.start, &mut start_buf),
+                    format_ipv4(range.end, &mut end_buf),
+                ))?
+            }
+            None => self.send_at_command(format_args!("AT+CWDHCPS{}=0", suffix))?,
+        }
+        .map_err(|_| Error::CommandFailed)?;
+
+        Ok(())
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Enables or disables DHCP on the given interface.
+    ///
+    /// See [`Module::set_dhcp`] for details.
+    pub fn set_dhcp(&mut self, interface: WifiInterface, enable: bool) -> crate::Result<()> {
+        self.module.set_dhcp(interface, enable)
+    }
+
+    /// Sets the range of the addresses leased by the DHCP server of the software access point.
+    ///
+    /// See [`Module::set_dhcp_lease_range`] for details.
+    pub fn set_dhcp_lease_range(&mut self, range: Option<DhcpLeaseRange>) -> crate::Result<()> {
+        self.module.set_dhcp_lease_range(range)
+    }
+}
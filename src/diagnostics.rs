@@ -0,0 +1,162 @@
+//! Module health diagnostics.
+
+use embedded_hal::serial;
+use heapless::String;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use simple_clock::SimpleClock;
+
+use crate::{
+    parser::{find_line_value, parse_numeric},
+    AtVersion, Capabilities, Error, Module, NetworkSession,
+};
+
+/// Maximum length of the firmware version string.
+const VERSION_MAX_LEN: usize = 64;
+
+/// WiFi station state reported by the module.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum WifiState {
+    /// The station has not started any connection.
+    NotStarted,
+    /// The station has joined an access point, but has not obtained an IP address yet.
+    Connected,
+    /// The station has joined an access point and obtained an IP address.
+    GotIp,
+    /// The station is connecting or reconnecting to an access point.
+    Connecting,
+    /// The station is disconnected.
+    Disconnected,
+    /// State code that is unknown to this crate.
+    Unknown(u8),
+}
+
+impl From<u8> for WifiState {
+    fn from(code: u8) -> Self {
+        match code {
+            0 => Self::NotStarted,
+            1 => Self::Connected,
+            2 => Self::GotIp,
+            3 => Self::Connecting,
+            4 => Self::Disconnected,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+/// Snapshot of the module health information.
+///
+/// Fields are `None` if the corresponding query is not supported by the module firmware.
+#[derive(Debug, PartialEq, Clone, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Diagnostics {
+    /// AT firmware version.
+    pub firmware_version: Option<String<VERSION_MAX_LEN>>,
+    /// Free heap size in bytes.
+    pub free_ram: Option<u32>,
+    /// WiFi RF TX power in 0.25 dBm units.
+    pub rf_power: Option<u8>,
+    /// WiFi station state.
+    pub wifi_state: Option<WifiState>,
+    /// Time elapsed since the module has been reset in microseconds.
+    pub uptime: u64,
+}
+
+/// Firmware information reported by the `AT+GMR` command.
+///
+/// Fields are `None` if the corresponding line is missing from the response, e.g. the old
+/// firmwares don't report the compile time.
+#[derive(Debug, PartialEq, Clone, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct FirmwareInfo {
+    /// AT firmware version.
+    pub at_version: Option<AtVersion>,
+    /// SDK version, e.g. `3.0.4(9532ceb)`.
+    pub sdk_version: Option<String<VERSION_MAX_LEN>>,
+    /// Firmware compile time, e.g. `May 27 2020 10:12:17`.
+    pub compile_time: Option<String<VERSION_MAX_LEN>>,
+}
+
+impl FirmwareInfo {
+    /// Parses the `AT+GMR` response.
+    ///
+    /// The compile time line of the ESP-AT firmware contains the commit hash, e.g.
+    /// `compile time(3a696ba):Jul  2 2021 11:54:43`, which is skipped.
+    pub(crate) fn parse(resp: &[u8]) -> Self {
+        let compile_time = find_line_value(resp, "compile time").and_then(|value| {
+            let start = value.iter().position(|&byte| byte == b':')?;
+            to_version_string(&value[start + 1..])
+        });
+
+        Self {
+            at_version: AtVersion::parse(resp),
+            sdk_version: find_line_value(resp, "SDK version:").and_then(to_version_string),
+            compile_time,
+        }
+    }
+}
+
+/// Copies the version string, the too long strings are `None`.
+fn to_version_string(raw: &[u8]) -> Option<String<VERSION_MAX_LEN>> {
+    let mut s = String::new();
+    s.push_str(core::str::from_utf8(raw).ok()?).ok()?;
+    Some(s)
+}
+
+impl<Rx, Tx, C, const N: usize> Module<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Collects the module health information.
+    pub fn diagnostics(&mut self) -> crate::Result<Diagnostics> {
+        let firmware_version = self.query("AT+GMR", |resp| {
+            find_line_value(resp, "AT version:").and_then(to_version_string)
+        })?;
+        let free_ram = self.query_supported(Capabilities::SYSTEM_RAM, "AT+SYSRAM?", |resp| {
+            parse_numeric(resp, "+SYSRAM:")
+        })?;
+        let rf_power = self.query_supported(Capabilities::RF_POWER, "AT+RFPOWER?", |resp| {
+            parse_numeric(resp, "+RFPOWER:")
+        })?;
+        let wifi_state = self.query_supported(Capabilities::WIFI_STATE, "AT+CWSTATE?", |resp| {
+            parse_numeric::<u8>(resp, "+CWSTATE:").map(WifiState::from)
+        })?;
+
+        Ok(Diagnostics {
+            firmware_version,
+            free_ram,
+            rf_power,
+            wifi_state,
+            uptime: self.uptime(),
+        })
+    }
+
+    /// Queries the firmware information, e.g. to enable the workarounds of the old firmware
+    /// versions.
+    pub fn firmware_info(&mut self) -> crate::Result<FirmwareInfo> {
+        self.query("AT+GMR", |resp| Some(FirmwareInfo::parse(resp)))?
+            .ok_or(Error::CommandFailed)
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Collects the module health information.
+    pub fn diagnostics(&mut self) -> crate::Result<Diagnostics> {
+        self.module.diagnostics()
+    }
+
+    /// Queries the firmware information.
+    ///
+    /// See [`Module::firmware_info`] for details.
+    pub fn firmware_info(&mut self) -> crate::Result<FirmwareInfo> {
+        self.module.firmware_info()
+    }
+}
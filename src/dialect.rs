@@ -0,0 +1,59 @@
+//! Dialects of the AT command set.
+
+use embedded_hal::serial;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use simple_clock::SimpleClock;
+
+use crate::{AtVersion, Module};
+
+/// Generation of the AT firmware that defines the exact command set.
+#[derive(Debug, Default, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Dialect {
+    /// The NonOS AT 1.x firmware, which uses the `_CUR` suffixed commands like `AT+CWJAP_CUR`
+    /// to change the current configuration without saving it to the flash.
+    NonOs,
+    /// The ESP-AT 2.x firmware.
+    #[default]
+    EspAt,
+}
+
+impl Dialect {
+    /// Recognizes the dialect by the AT firmware version.
+    pub(crate) fn from_version(version: AtVersion) -> Self {
+        if version.major < 2 {
+            Self::NonOs
+        } else {
+            Self::EspAt
+        }
+    }
+
+    /// Returns the suffix of the commands that change or query the current configuration.
+    pub(crate) fn current_suffix(self) -> &'static str {
+        match self {
+            Self::NonOs => "_CUR",
+            Self::EspAt => "",
+        }
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> Module<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Returns the dialect of the AT command set used by the module.
+    ///
+    /// The dialect is recognized by the firmware version during the initialization,
+    /// the ESP-AT dialect is assumed if the version is unknown.
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    /// Overrides the dialect of the AT command set.
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.dialect = dialect;
+    }
+}
@@ -0,0 +1,128 @@
+//! Fan-out of the network events to the several consumers.
+
+use embedded_hal::serial;
+use heapless::Vec;
+use simple_clock::SimpleClock;
+
+use crate::{
+    error::ensure,
+    network_session::{link_mask, MAX_LINKS},
+    Error, NetworkEvent, NetworkSession,
+};
+
+/// Consumer of the network events.
+///
+/// This trait is implemented for all the `FnMut(NetworkEvent)` closures.
+pub trait EventHandler<const N: usize> {
+    /// Handles the network event.
+    fn handle_event(&mut self, event: NetworkEvent<'_, N>);
+}
+
+impl<F, const N: usize> EventHandler<N> for F
+where
+    F: FnMut(NetworkEvent<'_, N>),
+{
+    fn handle_event(&mut self, event: NetworkEvent<'_, N>) {
+        self(event)
+    }
+}
+
+/// Dispatcher that passes each network event to the handler that has been registered
+/// for its link, so the several subsystems, e.g. an HTTP server and an MQTT client,
+/// receive only their own events.
+///
+/// Up to `H` handlers can be registered, the events that no handler is interested in,
+/// including the unrecognized lines, go to the fallback handler if any.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut dispatcher = Dispatcher::<256, 2>::new();
+/// dispatcher.register(&[0, 1], &mut http_server)?;
+/// dispatcher.register(&[4], &mut mqtt_client)?;
+/// loop {
+///     nb::block!(dispatcher.poll(&mut session))?;
+/// }
+/// ```
+pub struct Dispatcher<'a, const N: usize, const H: usize> {
+    /// Registered handlers with the bitmasks of their links.
+    handlers: Vec<(u8, &'a mut dyn EventHandler<N>), H>,
+    fallback: Option<&'a mut dyn EventHandler<N>>,
+}
+
+impl<'a, const N: usize, const H: usize> Dispatcher<'a, N, H> {
+    /// Creates a dispatcher without handlers.
+    pub fn new() -> Self {
+        Self {
+            handlers: Vec::new(),
+            fallback: None,
+        }
+    }
+
+    /// Registers the handler of the events of the given links.
+    ///
+    /// If several handlers are interested in the same link, its events go to the first
+    /// registered one.
+    pub fn register(
+        &mut self,
+        links: &[usize],
+        handler: &'a mut dyn EventHandler<N>,
+    ) -> crate::Result<()> {
+        let mut mask = 0;
+        for &link_id in links {
+            ensure!(
+                link_id < MAX_LINKS,
+                "Link identifier should be less than the maximum number of connections"
+            );
+            mask |= link_mask(link_id as u16);
+        }
+
+        self.handlers
+            .push((mask, handler))
+            .map_err(|_| Error::BufferFull)
+    }
+
+    /// Sets the handler of the events that no registered handler is interested in.
+    pub fn set_fallback(&mut self, handler: Option<&'a mut dyn EventHandler<N>>) {
+        self.fallback = handler;
+    }
+
+    /// Passes the event to its handler, the event is dropped if there is no suitable
+    /// handler.
+    pub fn dispatch(&mut self, event: NetworkEvent<'_, N>) {
+        let mask = event.link_id().map_or(0, link_mask);
+        let handler = self
+            .handlers
+            .iter_mut()
+            .find(|(links, _)| links & mask != 0)
+            .map(|(_, handler)| handler)
+            .or(self.fallback.as_mut());
+
+        if let Some(handler) = handler {
+            handler.handle_event(event);
+        }
+    }
+
+    /// Polls a next network event and dispatches it.
+    ///
+    /// Returns `WouldBlock` if there are no new events.
+    pub fn poll<Rx, Tx, C>(
+        &mut self,
+        session: &mut NetworkSession<Rx, Tx, C, N>,
+    ) -> nb::Result<(), Error>
+    where
+        Rx: serial::Read<u8> + 'static,
+        Tx: serial::Write<u8> + 'static,
+        C: SimpleClock,
+    {
+        let event = session.poll_network_event()?;
+        self.dispatch(event);
+        Ok(())
+    }
+}
+
+impl<const N: usize, const H: usize> Default for Dispatcher<'_, N, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
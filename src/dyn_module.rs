@@ -0,0 +1,128 @@
+//! Type-erased driver that is compiled only once regardless of the serial port types.
+
+use core::fmt;
+
+use embedded_hal::serial;
+use simple_clock::SimpleClock;
+
+use crate::{module::DEFAULT_BUFFER_LEN, Module, NetworkSession, SharedClock};
+
+/// Clock used by the type-erased driver.
+pub type DynClock = SharedClock<'static, dyn SimpleClock>;
+/// Module with the type-erased serial port and clock.
+///
+/// The whole AT engine is generic over the serial port and clock types, so a project that uses
+/// the driver with several port types gets a separate copy of it for each type. The type-erased
+/// module is monomorphized only once, at the cost of the dynamic dispatch per byte.
+pub type DynModule<const N: usize = DEFAULT_BUFFER_LEN> = Module<DynRx, DynTx, DynClock, N>;
+/// Network session over the [`DynModule`].
+pub type DynNetworkSession<const N: usize = DEFAULT_BUFFER_LEN> =
+    NetworkSession<DynRx, DynTx, DynClock, N>;
+
+/// Object-safe subset of the serial port receiver with the erased error type.
+trait ErasedRead {
+    fn read_byte(&mut self) -> nb::Result<u8, ()>;
+}
+
+impl<R: serial::Read<u8>> ErasedRead for R {
+    fn read_byte(&mut self) -> nb::Result<u8, ()> {
+        self.read().map_err(|err| err.map(drop))
+    }
+}
+
+/// Object-safe subset of the serial port transmitter with the erased error type.
+trait ErasedWrite {
+    fn write_byte(&mut self, byte: u8) -> nb::Result<(), ()>;
+
+    fn flush(&mut self) -> nb::Result<(), ()>;
+}
+
+impl<W: serial::Write<u8>> ErasedWrite for W {
+    fn write_byte(&mut self, byte: u8) -> nb::Result<(), ()> {
+        self.write(byte).map_err(|err| err.map(drop))
+    }
+
+    fn flush(&mut self) -> nb::Result<(), ()> {
+        serial::Write::flush(self).map_err(|err| err.map(drop))
+    }
+}
+
+/// Type-erased serial port receiver.
+///
+/// The receiving errors are erased, so they cannot be recognized by the
+/// [serial error classifier](Module::set_serial_error_classifier).
+pub struct DynRx(&'static mut dyn ErasedRead);
+
+impl DynRx {
+    /// Erases the type of the given receiver.
+    pub fn new<R: serial::Read<u8>>(rx: &'static mut R) -> Self {
+        Self(rx)
+    }
+}
+
+impl serial::Read<u8> for DynRx {
+    type Error = ();
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.0.read_byte()
+    }
+}
+
+impl fmt::Debug for DynRx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DynRx").finish()
+    }
+}
+
+/// Type-erased serial port transmitter.
+pub struct DynTx(&'static mut dyn ErasedWrite);
+
+impl DynTx {
+    /// Erases the type of the given transmitter.
+    pub fn new<W: serial::Write<u8>>(tx: &'static mut W) -> Self {
+        Self(tx)
+    }
+}
+
+impl serial::Write<u8> for DynTx {
+    type Error = ();
+
+    fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+        self.0.write_byte(byte)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.0.flush()
+    }
+}
+
+impl fmt::Debug for DynTx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DynTx").finish()
+    }
+}
+
+impl<const N: usize> DynModule<N> {
+    /// Establishes serial communication with the esp8266 module over the type-erased
+    /// serial port and clock.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let (tx, rx) = serial.split();
+    /// let rx = cortex_m::singleton!(: Rx<USART1> = rx).unwrap();
+    /// let tx = cortex_m::singleton!(: Tx<USART1> = tx).unwrap();
+    /// let module: DynModule<256> = Module::new_dyn(rx, tx, &CLOCK)?;
+    /// ```
+    pub fn new_dyn<R, W>(
+        rx: &'static mut R,
+        tx: &'static mut W,
+        clock: &'static dyn SimpleClock,
+    ) -> crate::Result<Self>
+    where
+        R: serial::Read<u8>,
+        W: serial::Write<u8>,
+    {
+        Module::new(DynRx::new(rx), DynTx::new(tx), SharedClock::new(clock))
+    }
+}
@@ -1,10 +1,18 @@
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
 /// Possible error types that may happen during manipulating the WiFi module.
 ///
 /// In order to the crate interface simplification, error details have been omitted.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub enum Error {
     /// An error occurred during the receiving bytes from the serial port.
     ReadBuffer,
+    /// A classified error occurred during the receiving bytes from the serial port.
+    ///
+    /// See [`Module::set_serial_error_classifier`](crate::Module::set_serial_error_classifier).
+    Serial(SerialErrorKind),
     /// An error occurred during the sending bytes into the serial port.
     WriteBuffer,
     /// Reader buffer is full.
@@ -13,7 +21,91 @@ pub enum Error {
     Timeout,
     /// Unable to join selected access point.
     JoinApError,
+    /// The module rejected the sent command.
+    CommandFailed,
+    /// The given configuration parameters are invalid.
+    InvalidConfig(ConfigError),
+    /// The command is not supported by the module firmware.
+    Unsupported,
+    /// Unable to drive the hardware reset pin.
+    ResetPin,
+    /// The connection has been closed before all the expected data was received.
+    ConnectionClosed,
+    /// The received message cannot be decoded.
+    MalformedMessage,
+    /// The method argument is out of the allowed range.
+    ///
+    /// The arguments that are usually constant, e.g. link identifiers, are checked by
+    /// assertions, this error is returned instead of panicking if the `no-panic` feature
+    /// is enabled.
+    InvalidArgument,
+}
+
+/// Kind of the serial port receiving error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum SerialErrorKind {
+    /// The received byte has been lost because the previous one hasn't been read in time.
+    ///
+    /// It usually means that the poll loop is too slow.
+    Overrun,
+    /// Received data doesn't conform to the frame format, e.g. during the module boot.
+    FrameFormat,
+    /// Parity check failed.
+    Parity,
+    /// Noise has been detected in the received data.
+    Noise,
+}
+
+impl Error {
+    /// Returns the kind of the serial port receiving error if it has been classified.
+    pub fn serial_kind(&self) -> Option<SerialErrorKind> {
+        match self {
+            Error::Serial(kind) => Some(*kind),
+            _ => None,
+        }
+    }
+}
+
+/// Describes the reason why the network configuration is invalid.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum ConfigError {
+    /// SSID is empty or longer than 32 bytes.
+    InvalidSsid,
+    /// Password length is not suitable for the selected WiFi mode.
+    ///
+    /// WPA modes require a password from 8 to 64 bytes.
+    InvalidPassword,
+    /// Channel number is not in the range from 1 to 14.
+    InvalidChannel,
+    /// The selected WiFi mode cannot be used by the software access point.
+    UnsupportedMode,
+    /// Hostname is empty, longer than 32 bytes, or contains characters other than
+    /// the letters, digits and hyphens.
+    InvalidHostname,
 }
 
 /// A specialized result type for the operations with the esp8266 module.
 pub type Result<T> = core::result::Result<T, Error>;
+
+/// Checks the method argument, panics with the given message if the check fails, or returns
+/// the `InvalidArgument` error if the `no-panic` feature is enabled.
+///
+/// The methods that don't return a result pass their own exit expression after the arrow,
+/// e.g. a plain `return`.
+macro_rules! ensure {
+    ($cond:expr, $msg:literal) => {
+        ensure!($cond, $msg => return Err($crate::Error::InvalidArgument.into()))
+    };
+    ($cond:expr, $msg:literal => $exit:expr) => {
+        #[cfg(not(feature = "no-panic"))]
+        assert!($cond, $msg);
+        #[cfg(feature = "no-panic")]
+        if !$cond {
+            $exit;
+        }
+    };
+}
+
+pub(crate) use ensure;
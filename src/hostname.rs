@@ -0,0 +1,78 @@
+//! Hostname of the station interface.
+
+use embedded_hal::serial;
+use heapless::String;
+use simple_clock::SimpleClock;
+
+use crate::{parser::find_line_value, ConfigError, Error, Module, NetworkSession};
+
+/// Maximum length of the hostname in bytes.
+pub const HOSTNAME_MAX_LEN: usize = 32;
+
+impl<Rx, Tx, C, const N: usize> Module<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Returns the hostname of the station interface.
+    pub fn hostname(&mut self) -> crate::Result<String<HOSTNAME_MAX_LEN>> {
+        self.query("AT+CWHOSTNAME?", |resp| {
+            let hostname = core::str::from_utf8(find_line_value(resp, "+CWHOSTNAME:")?).ok()?;
+            let mut owned = String::new();
+            owned.push_str(hostname).ok()?;
+            Some(owned)
+        })?
+        .ok_or(Error::CommandFailed)
+    }
+
+    /// Sets the hostname of the station interface, which is sent to the DHCP server, so
+    /// the device is shown with this name in the router client lists.
+    ///
+    /// # Notes
+    ///
+    /// The station mode should be enabled, e.g. by the [`JoinApConfig::join`] call. The name
+    /// set after joining the access point is sent by the next DHCP lease renewal, so it is
+    /// better to set it before.
+    ///
+    /// [`JoinApConfig::join`]: crate::JoinApConfig::join
+    pub fn set_hostname(&mut self, hostname: &str) -> crate::Result<()> {
+        validate_hostname(hostname)?;
+
+        self.send_at_command(format_args!("AT+CWHOSTNAME=\"{}\"", hostname))?
+            .map_err(|_| Error::CommandFailed)?;
+        Ok(())
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Returns the hostname of the station interface.
+    pub fn hostname(&mut self) -> crate::Result<String<HOSTNAME_MAX_LEN>> {
+        self.module.hostname()
+    }
+
+    /// Sets the hostname of the station interface.
+    ///
+    /// See [`Module::set_hostname`] for details.
+    pub fn set_hostname(&mut self, hostname: &str) -> crate::Result<()> {
+        self.module.set_hostname(hostname)
+    }
+}
+
+/// Checks that the hostname is a valid DNS label, so it doesn't need the escaping.
+pub(crate) fn validate_hostname(hostname: &str) -> crate::Result<()> {
+    let is_valid = !hostname.is_empty()
+        && hostname.len() <= HOSTNAME_MAX_LEN
+        && hostname
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-');
+    if !is_valid {
+        return Err(Error::InvalidConfig(ConfigError::InvalidHostname));
+    }
+    Ok(())
+}
@@ -0,0 +1,92 @@
+//! Formatter-free conversion of the IPv4 addresses.
+
+use core::convert::TryFrom;
+
+use crate::net::Ipv4Addr;
+
+/// Maximum length of the IPv4 address in the dotted decimal notation.
+pub const IPV4_MAX_LEN: usize = 15;
+
+/// Writes the IPv4 address in the dotted decimal notation into the given buffer and returns
+/// the written part of the buffer as a string.
+pub fn format_ipv4(addr: Ipv4Addr, buf: &mut [u8; IPV4_MAX_LEN]) -> &str {
+    let mut len = 0;
+    for (i, octet) in addr.octets().iter().copied().enumerate() {
+        if i > 0 {
+            buf[len] = b'.';
+            len += 1;
+        }
+        if octet >= 100 {
+            buf[len] = b'0' + octet / 100;
+            len += 1;
+        }
+        if octet >= 10 {
+            buf[len] = b'0' + octet / 10 % 10;
+            len += 1;
+        }
+        buf[len] = b'0' + octet % 10;
+        len += 1;
+    }
+    // Safety: the buffer contains only the ASCII digits and dots.
+    unsafe { core::str::from_utf8_unchecked(&buf[..len]) }
+}
+
+/// Parses the IPv4 address in the dotted decimal notation, e.g. `192.168.4.1`.
+pub fn parse_ipv4(bytes: &[u8]) -> Option<Ipv4Addr> {
+    let mut octets = [0_u8; 4];
+    let mut parts = bytes.split(|byte| *byte == b'.');
+    for octet in octets.iter_mut() {
+        let part = parts.next()?;
+        if part.is_empty() || part.len() > 3 {
+            return None;
+        }
+
+        let mut value = 0_u16;
+        for digit in part {
+            if !digit.is_ascii_digit() {
+                return None;
+            }
+            value = value * 10 + u16::from(digit - b'0');
+        }
+        *octet = u8::try_from(value).ok()?;
+    }
+
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]))
+}
+
+/// Serializes IPv4 addresses in the human-readable form.
+#[cfg(feature = "serde")]
+pub(crate) mod serde_ipv4 {
+    use core::fmt;
+
+    use serde::{de, Deserializer, Serializer};
+
+    use super::parse_ipv4;
+    use crate::net::Ipv4Addr;
+
+    pub fn serialize<S: Serializer>(addr: &Ipv4Addr, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(addr)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Ipv4Addr, D::Error> {
+        struct Visitor;
+
+        impl de::Visitor<'_> for Visitor {
+            type Value = Ipv4Addr;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("an IPv4 address in the dotted decimal notation")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                parse_ipv4(value.as_bytes())
+                    .ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self))
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
@@ -0,0 +1,112 @@
+//! Application-level keepalive of the TCP connections.
+
+use core::convert::TryFrom;
+
+use heapless::Vec;
+
+use crate::{
+    network_session::{link_mask, MAX_LINKS},
+    Error,
+};
+
+/// Maximum length of the keepalive probe payload in bytes.
+pub const KEEPALIVE_PROBE_MAX_LEN: usize = 32;
+
+/// Application-level keepalive service.
+///
+/// The service periodically sends the probe payload on the watched links and marks
+/// the link as dead if the peer has not sent any data in response within the given
+/// window. Unlike the TCP keepalive, it detects half-open application sessions.
+///
+/// See [`NetworkSession::set_keepalive`](crate::NetworkSession::set_keepalive).
+#[derive(Debug, Clone)]
+pub struct KeepAlive {
+    pub(crate) probe: Vec<u8, KEEPALIVE_PROBE_MAX_LEN>,
+    interval_us: u64,
+    window_us: u64,
+    /// Bitmask of the watched links.
+    watched: u8,
+    /// Bitmask of the links that haven't responded to the probe.
+    dead: u8,
+    /// Time of the last activity on the each link.
+    last_activity: [u64; MAX_LINKS],
+    /// Time of the unanswered probe on the each link.
+    probed_at: [Option<u64>; MAX_LINKS],
+}
+
+impl KeepAlive {
+    /// Creates a keepalive service that sends the given probe after the `interval_us`
+    /// of inactivity and waits `window_us` for the response.
+    pub fn new(probe: &[u8], interval_us: u64, window_us: u64) -> crate::Result<Self> {
+        Ok(Self {
+            probe: Vec::from_slice(probe).map_err(|_| Error::BufferFull)?,
+            interval_us,
+            window_us,
+            watched: 0,
+            dead: 0,
+            last_activity: [0; MAX_LINKS],
+            probed_at: [None; MAX_LINKS],
+        })
+    }
+
+    /// Returns the probe payload.
+    pub fn probe(&self) -> &[u8] {
+        &self.probe
+    }
+
+    pub(crate) fn watch(&mut self, link_id: usize, now: u64, enable: bool) {
+        let mask = mask(link_id);
+        if enable {
+            self.watched |= mask;
+            self.reset(link_id, now);
+        } else {
+            self.watched &= !mask;
+        }
+    }
+
+    pub(crate) fn is_dead(&self, link_id: usize) -> bool {
+        self.dead & mask(link_id) != 0
+    }
+
+    /// Marks the link as active, e.g. after receiving data or a new connection.
+    pub(crate) fn reset(&mut self, link_id: usize, now: u64) {
+        self.dead &= !mask(link_id);
+        self.last_activity[link_id] = now;
+        self.probed_at[link_id] = None;
+    }
+
+    /// Marks the link as dead, e.g. after the failed probe.
+    pub(crate) fn mark_dead(&mut self, link_id: usize) {
+        self.dead |= mask(link_id);
+    }
+
+    /// Updates the state of the links and returns the identifier of the link that
+    /// should be probed.
+    pub(crate) fn poll(&mut self, now: u64) -> Option<usize> {
+        for link_id in 0..MAX_LINKS {
+            let mask = mask(link_id);
+            if self.watched & mask == 0 || self.dead & mask != 0 {
+                continue;
+            }
+
+            match self.probed_at[link_id] {
+                Some(probed_at) if now.saturating_sub(probed_at) >= self.window_us => {
+                    self.dead |= mask;
+                }
+                Some(_) => {}
+                None if now.saturating_sub(self.last_activity[link_id]) >= self.interval_us => {
+                    self.probed_at[link_id] = Some(now);
+                    return Some(link_id);
+                }
+                None => {}
+            }
+        }
+        None
+    }
+}
+
+/// Returns a bit that corresponds to the given link identifier in the links bitmask,
+/// or zero if the identifier is out of range.
+fn mask(link_id: usize) -> u8 {
+    u16::try_from(link_id).map_or(0, link_mask)
+}
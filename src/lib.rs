@@ -1,4 +1,4 @@
-#![cfg_attr(not(test), no_std)]
+#![no_std]
 
 //! Driver to working with the esp8266 module over the serial port.
 //!
@@ -7,23 +7,129 @@
 //! This library is not completed and lack core features and has a lot of bugs and imperfections.
 //! And so, it is not ready for production purposes.
 
+#[cfg(feature = "async")]
+pub use crate::asynch::{AsyncModule, AsyncNetworkSession};
+#[cfg(feature = "postcard")]
+pub use crate::message::MESSAGE_MAX_LEN;
+#[cfg(feature = "rtic")]
+pub use crate::monotonic::{MicrosInstant, MonotonicClock};
+#[cfg(feature = "embedded-nal")]
+pub use crate::nal::{NalStack, TcpSocket, UdpSocket};
+#[cfg(feature = "embedded-io")]
+pub use crate::socket_handle::SocketHandle;
 pub use crate::{
-    error::{Error, Result},
-    module::{AtCommand, Module},
-    network_session::{NetworkEvent, NetworkSession},
+    backoff::Backoff,
+    blocking::BlockingSession,
+    buffered::{BufReader, BufWriter},
+    capabilities::{AtVersion, Capabilities},
+    captive_portal::{CaptivePortal, CAPTIVE_PORTAL_DNS_LINK},
+    chip::Chip,
+    dhcp::{DhcpLeaseRange, WifiInterface, DHCP_LEASE_TIME_RANGE},
+    diagnostics::{Diagnostics, FirmwareInfo, WifiState},
+    dialect::Dialect,
+    dispatcher::{Dispatcher, EventHandler},
+    dyn_module::{DynClock, DynModule, DynNetworkSession, DynRx, DynTx},
+    error::{ConfigError, Error, Result, SerialErrorKind},
+    hostname::HOSTNAME_MAX_LEN,
+    ipv4::{format_ipv4, parse_ipv4, IPV4_MAX_LEN},
+    keepalive::{KeepAlive, KEEPALIVE_PROBE_MAX_LEN},
+    link_queues::{LinkEvent, LinkQueues},
+    link_status::{LinkProtocol, LinkRole, LinkStatus},
+    mdns::{MdnsResponder, MdnsService},
+    module::{
+        AtCommand, Module, ResetConfig, ResetKind, ResetPin, RetryPolicy, DEFAULT_BUFFER_LEN,
+    },
+    network_session::{
+        ConnectedAp, Events, NetworkEvent, NetworkSession, OwnedNetworkEvent, SessionInfo,
+    },
+    ping::PingTime,
+    pipeline::MAX_IN_FLIGHT_SENDS,
+    provisioning::{CredentialStore, Provisioning, ProvisioningPages},
     reader_part::ReadData,
-    softap::{JoinApConfig, SoftApConfig, WifiMode},
+    reconnect::ReconnectPolicy,
+    recovery::RebootRecovery,
+    response::{Lines, Response},
+    send_queue::QUEUED_SEGMENT_MAX_LEN,
+    shared_clock::SharedClock,
+    sleep::{GpioWakeup, SleepMode},
+    smartconfig::{SmartConfig, SmartConfigProtocol},
+    softap::{
+        JoinApConfig, JoinApConfigBuilder, JoinApOptions, OwnedJoinApConfig, OwnedSoftApConfig,
+        ScanMode, SoftApAddress, SoftApConfig, SoftApConfigBuilder, WifiMode, PASSWORD_MAX_LEN,
+        SSID_MAX_LEN,
+    },
+    ssl::{SslAuth, SSL_BUFFER_SIZE_RANGE, SSL_PSK_MAX_LEN},
+    stream::{StreamSource, STREAM_SEGMENT_LEN},
+    synced_clock::{SyncedClock, DEFAULT_RESYNC_INTERVAL_US},
+    trace::{TraceBuffer, TRACE_BUFFER_LEN},
 };
 pub use no_std_net as net;
 
 pub use simple_clock as clock;
 
+#[cfg(feature = "async")]
+// The async functions in traits require Rust 1.75.
+#[clippy::msrv = "1.75"]
+mod asynch;
+mod backoff;
+mod blocking;
+mod bridge;
+mod buffered;
+mod capabilities;
+mod captive_portal;
+mod chip;
+mod dhcp;
+mod diagnostics;
+mod dialect;
+mod dispatcher;
+mod dyn_module;
 mod error;
+mod hostname;
+mod ipv4;
+mod keepalive;
+mod link_queues;
+mod link_status;
+mod mac;
+mod mdns;
+#[cfg(feature = "postcard")]
+mod message;
 mod module;
+#[cfg(feature = "rtic")]
+mod monotonic;
+#[cfg(feature = "embedded-nal")]
+mod nal;
 mod network_session;
-mod parser;
+pub mod parser;
+mod passive;
+mod ping;
+mod pipeline;
+mod provisioning;
+mod raw;
 mod reader_part;
+mod reconnect;
+mod recovery;
+mod response;
+mod rf_power;
+mod send_queue;
+mod shared_clock;
+mod sleep;
+mod smartconfig;
+mod sntp;
+#[cfg(feature = "embedded-io")]
+mod socket_handle;
 mod softap;
+mod ssl;
+mod stream;
+mod synced_clock;
+mod trace;
+mod transparent;
+mod wps;
 
+// The mock serial port and clock are built on the standard collections.
+#[cfg(any(test, feature = "test-utils"))]
+extern crate std;
+
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,284 @@
+//! Per-link receive queues.
+
+use embedded_hal::serial;
+use heapless::Deque;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use simple_clock::SimpleClock;
+
+use crate::{network_session::MAX_LINKS, Error, NetworkEvent, NetworkSession};
+
+/// Maximum number of bytes pulled into the queue by a single poll.
+const PULL_CHUNK_LEN: usize = 256;
+
+/// Network event whose received data has been moved to the link queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum LinkEvent {
+    /// A new peer connected.
+    Connected {
+        /// Connection identifier.
+        link_id: u16,
+    },
+    /// The connection with the peer is closed.
+    Closed {
+        /// Connection identifier.
+        link_id: u16,
+        /// The connection has been closed by the [`NetworkSession::close`] call rather
+        /// than by the peer.
+        closed_locally: bool,
+    },
+    /// Bytes received from the peer have been added to the link queue.
+    DataQueued {
+        /// Connection identifier.
+        link_id: u16,
+        /// Number of the queued bytes.
+        len: usize,
+        /// Some of the received bytes don't fit into the queue, they are kept in the session
+        /// or buffered by the module in the passive mode, and queued by the next polls once
+        /// the queue has been read.
+        pending: bool,
+    },
+    /// Bytes received from the peer have been buffered by the module in the passive
+    /// receiving mode, they are not queued until they are pulled.
+    DataBuffered {
+        /// Connection identifier.
+        link_id: u16,
+        /// Number of the buffered bytes.
+        len: usize,
+    },
+    /// The module has joined the access point.
+    WifiConnected,
+    /// The module has obtained the IP address from the joined access point.
+    WifiGotIp,
+    /// The module has been disconnected from the access point.
+    WifiDisconnected,
+    /// The module has rebooted unexpectedly, the queues have been cleared.
+    ModuleRebooted,
+}
+
+/// Receive queues of `Q` bytes for each link.
+///
+/// The received data borrows the session until it is dropped, so a slow consumer of one
+/// link stalls the events of the other links. The queues take the data out of the session
+/// right away, and each link is read at its own pace.
+///
+/// The received data is never discarded. In the active receiving mode the module pushes
+/// the data by itself, so if the queue of the link is full, the rest of the data is kept
+/// in the session and the next polls return `WouldBlock` until the queue is read, which
+/// stalls the other links.
+///
+/// In the [passive receiving mode](NetworkSession::set_passive_receive) the module buffers
+/// the data of each link, and the queues pull only as much data as fits, so a full queue
+/// never stalls the other links, and the peer is throttled by the TCP flow control.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut queues = LinkQueues::<512>::new();
+/// loop {
+///     if let Ok(event) = queues.poll(&mut session) {
+///         handle_event(event);
+///     }
+///     let len = queues.read(control_link, &mut buf);
+///     // Handle the control data.
+/// }
+/// ```
+#[derive(Debug)]
+pub struct LinkQueues<const Q: usize> {
+    queues: [Deque<u8, Q>; MAX_LINKS],
+    /// Number of the bytes buffered by the module for each link in the passive mode.
+    buffered: [usize; MAX_LINKS],
+    /// Link to be pulled first by the next poll, so the links are pulled in turn.
+    next_pull: usize,
+}
+
+impl<const Q: usize> LinkQueues<Q> {
+    /// Creates empty queues.
+    pub fn new() -> Self {
+        Self {
+            queues: Default::default(),
+            buffered: [0; MAX_LINKS],
+            next_pull: 0,
+        }
+    }
+
+    /// Polls a next network event and moves its data, if any, to the link queue.
+    ///
+    /// The data received from a new connection replaces the data that is left from
+    /// the previous connection with the same link identifier. The unrecognized lines are
+    /// skipped, use the [`NetworkSession::set_unknown_line_handler`] to handle them.
+    ///
+    /// In the passive receiving mode the data buffered by the module is pulled into
+    /// the queues that have free space first, the [`LinkEvent::DataBuffered`] event is
+    /// followed by the [`LinkEvent::DataQueued`] ones.
+    ///
+    /// Returns `WouldBlock` if there are no new events.
+    pub fn poll<Rx, Tx, C, const N: usize>(
+        &mut self,
+        session: &mut NetworkSession<Rx, Tx, C, N>,
+    ) -> nb::Result<LinkEvent, Error>
+    where
+        Rx: serial::Read<u8> + 'static,
+        Tx: serial::Write<u8> + 'static,
+        C: SimpleClock,
+    {
+        if let Some(event) = self.pull_buffered(session)? {
+            return Ok(event);
+        }
+
+        let event = session.poll_network_event()?;
+        self.handle(event)
+    }
+
+    /// Pulls the data buffered by the module into the queue of the next link that has
+    /// both the buffered data and the free space.
+    fn pull_buffered<Rx, Tx, C, const N: usize>(
+        &mut self,
+        session: &mut NetworkSession<Rx, Tx, C, N>,
+    ) -> crate::Result<Option<LinkEvent>>
+    where
+        Rx: serial::Read<u8> + 'static,
+        Tx: serial::Write<u8> + 'static,
+        C: SimpleClock,
+    {
+        for offset in 0..MAX_LINKS {
+            let link_id = (self.next_pull + offset) % MAX_LINKS;
+            let queue = &mut self.queues[link_id];
+            let free = queue.capacity() - queue.len();
+            let len = free.min(self.buffered[link_id]).min(PULL_CHUNK_LEN);
+            if len == 0 {
+                continue;
+            }
+
+            let mut chunk = [0_u8; PULL_CHUNK_LEN];
+            let len = match session.pull_data(link_id, &mut chunk[..len]) {
+                Ok(len) => len,
+                // The buffered data has been lost, e.g. the link has been closed.
+                Err(Error::CommandFailed) => 0,
+                Err(err) => return Err(err),
+            };
+            for &byte in &chunk[..len] {
+                // The length is limited by the free space of the queue.
+                queue.push_back(byte).ok();
+            }
+
+            self.buffered[link_id] = match len {
+                0 => 0,
+                len => self.buffered[link_id].saturating_sub(len),
+            };
+            self.next_pull = (link_id + 1) % MAX_LINKS;
+            if len > 0 {
+                return Ok(Some(LinkEvent::DataQueued {
+                    link_id: link_id as u16,
+                    len,
+                    pending: self.buffered[link_id] > 0,
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Moves the data of the polled event, if any, to the link queue.
+    pub(crate) fn handle<const N: usize>(
+        &mut self,
+        event: NetworkEvent<'_, N>,
+    ) -> nb::Result<LinkEvent, Error> {
+        match event {
+            NetworkEvent::Connected { link_id } => {
+                self.clear(usize::from(link_id));
+                Ok(LinkEvent::Connected { link_id })
+            }
+            NetworkEvent::Closed {
+                link_id,
+                closed_locally,
+            } => Ok(LinkEvent::Closed {
+                link_id,
+                closed_locally,
+            }),
+            NetworkEvent::DataAvailable {
+                link_id, mut data, ..
+            } => {
+                let queue = self
+                    .queues
+                    .get_mut(usize::from(link_id))
+                    .ok_or(nb::Error::WouldBlock)?;
+
+                let len = core::cmp::min(data.len(), queue.capacity() - queue.len());
+                for &byte in data.take(len) {
+                    // The length is limited by the free space of the queue.
+                    queue.push_back(byte).ok();
+                }
+                // The rest of the data waits in the session until the queue is read.
+                data.retain();
+                if len == 0 {
+                    return Err(nb::Error::WouldBlock);
+                }
+                Ok(LinkEvent::DataQueued {
+                    link_id,
+                    len,
+                    pending: !data.is_empty(),
+                })
+            }
+            NetworkEvent::DataBuffered { link_id, len } => {
+                // The notification reports the total length of the buffered data.
+                if let Some(buffered) = self.buffered.get_mut(usize::from(link_id)) {
+                    *buffered = len;
+                }
+                Ok(LinkEvent::DataBuffered { link_id, len })
+            }
+            NetworkEvent::WifiConnected => Ok(LinkEvent::WifiConnected),
+            NetworkEvent::WifiGotIp => Ok(LinkEvent::WifiGotIp),
+            NetworkEvent::WifiDisconnected => Ok(LinkEvent::WifiDisconnected),
+            NetworkEvent::ModuleRebooted => {
+                self.queues.iter_mut().for_each(Deque::clear);
+                self.buffered = [0; MAX_LINKS];
+                Ok(LinkEvent::ModuleRebooted)
+            }
+            NetworkEvent::Unknown(_) => Err(nb::Error::WouldBlock),
+        }
+    }
+
+    /// Moves the queued data of the given link into the buffer and returns the number of
+    /// bytes read.
+    pub fn read(&mut self, link_id: usize, buf: &mut [u8]) -> usize {
+        let queue = match self.queues.get_mut(link_id) {
+            Some(queue) => queue,
+            None => return 0,
+        };
+
+        let mut len = 0;
+        while len < buf.len() {
+            match queue.pop_front() {
+                Some(byte) => buf[len] = byte,
+                None => break,
+            }
+            len += 1;
+        }
+        len
+    }
+
+    /// Returns the number of the queued bytes of the given link.
+    pub fn len(&self, link_id: usize) -> usize {
+        self.queues.get(link_id).map_or(0, Deque::len)
+    }
+
+    /// Returns `true` if there is no queued data of the given link.
+    pub fn is_empty(&self, link_id: usize) -> bool {
+        self.len(link_id) == 0
+    }
+
+    /// Discards the queued data of the given link.
+    pub fn clear(&mut self, link_id: usize) {
+        if let Some(queue) = self.queues.get_mut(link_id) {
+            queue.clear();
+            self.buffered[link_id] = 0;
+        }
+    }
+}
+
+impl<const Q: usize> Default for LinkQueues<Q> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,138 @@
+//! State of the open links reported by the module.
+
+use core::str::FromStr;
+
+use embedded_hal::serial;
+use heapless::Vec;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer};
+use simple_clock::SimpleClock;
+
+use crate::{
+    net::SocketAddr, network_session::MAX_LINKS, parser::parse_ip_addr, Error, NetworkSession,
+};
+
+/// Transport protocol of the link.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum LinkProtocol {
+    /// TCP connection.
+    Tcp,
+    /// UDP socket.
+    Udp,
+    /// SSL connection over TCP.
+    Ssl,
+}
+
+impl LinkProtocol {
+    /// Parses the link type, e.g. `TCP`, the IPv6 types like `TCPv6` of the ESP32-series
+    /// modules are recognized as well.
+    fn parse(raw: &[u8]) -> Option<Self> {
+        match raw.strip_suffix(b"v6").unwrap_or(raw) {
+            b"TCP" => Some(Self::Tcp),
+            b"UDP" => Some(Self::Udp),
+            b"SSL" => Some(Self::Ssl),
+            _ => None,
+        }
+    }
+}
+
+/// Side of the link that has initiated the connection.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum LinkRole {
+    /// The link has been opened by the module, e.g. by the [`NetworkSession::connect`] call.
+    Client,
+    /// The link has been accepted by the server started by the [`NetworkSession::listen`] call.
+    Server,
+}
+
+/// State of the open link reported by the `AT+CIPSTATUS` command.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct LinkStatus {
+    /// Connection identifier.
+    pub link_id: u16,
+    /// Transport protocol.
+    pub protocol: LinkProtocol,
+    /// Address of the remote peer.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_socket_addr"))]
+    pub remote: SocketAddr,
+    /// Local port number.
+    pub local_port: u16,
+    /// Side that has initiated the connection.
+    pub role: LinkRole,
+}
+
+/// Serializes a socket address in the human-readable form.
+#[cfg(feature = "serde")]
+fn serialize_socket_addr<S: Serializer>(
+    addr: &SocketAddr,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(addr)
+}
+
+impl LinkStatus {
+    /// Parses the value of the `+CIPSTATUS:` line, e.g. `0,"TCP","192.168.1.5",80,5000,0`.
+    pub(crate) fn parse(value: &[u8]) -> Option<Self> {
+        let mut fields = value.split(|&byte| byte == b',');
+        let link_id = parse_field(fields.next()?)?;
+        let protocol = LinkProtocol::parse(unquote(fields.next()?)?)?;
+        let ip = parse_ip_addr(unquote(fields.next()?)?)?;
+        let remote_port = parse_field(fields.next()?)?;
+        let local_port = parse_field(fields.next()?)?;
+        let role = match fields.next()? {
+            b"0" => LinkRole::Client,
+            b"1" => LinkRole::Server,
+            _ => return None,
+        };
+
+        Some(Self {
+            link_id,
+            protocol,
+            remote: SocketAddr::new(ip, remote_port),
+            local_port,
+            role,
+        })
+    }
+}
+
+fn unquote(field: &[u8]) -> Option<&[u8]> {
+    field.strip_prefix(b"\"")?.strip_suffix(b"\"")
+}
+
+fn parse_field<T: FromStr>(field: &[u8]) -> Option<T> {
+    core::str::from_utf8(field).ok()?.parse().ok()
+}
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Queries the state of the open links by the `AT+CIPSTATUS` command.
+    ///
+    /// The module is the source of truth about the links, so it allows to recover the
+    /// application state after errors, e.g. after a missed `CLOSED` notification.
+    /// The lines that cannot be recognized are skipped.
+    pub fn status(&mut self) -> crate::Result<Vec<LinkStatus, MAX_LINKS>> {
+        let resp = self
+            .module
+            .send_at_command("AT+CIPSTATUS")?
+            .map_err(|_| Error::CommandFailed)?;
+
+        let mut links = Vec::new();
+        for status in resp
+            .lines()
+            .filter_map(|line| LinkStatus::parse(line.strip_prefix(b"+CIPSTATUS:")?))
+        {
+            // The module never reports more links than it supports.
+            if links.push(status).is_err() {
+                break;
+            }
+        }
+        Ok(links)
+    }
+}
@@ -0,0 +1,101 @@
+//! MAC addresses of the network interfaces.
+
+use embedded_hal::serial;
+use simple_clock::SimpleClock;
+
+use crate::{parser::parse_mac_response, Error, Module, NetworkSession};
+
+/// Formats the optional MAC address as a quoted AT command parameter, or as nothing if it
+/// is absent.
+pub(crate) struct MacParam(pub Option<[u8; 6]>);
+
+impl core::fmt::Display for MacParam {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if let Some(mac) = self.0 {
+            write!(
+                f,
+                "\"{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}\"",
+                mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> Module<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Returns the MAC address of the station interface.
+    pub fn sta_mac(&mut self) -> crate::Result<[u8; 6]> {
+        self.query_mac("CIPSTAMAC")
+    }
+
+    /// Returns the MAC address of the software access point interface.
+    pub fn ap_mac(&mut self) -> crate::Result<[u8; 6]> {
+        self.query_mac("CIPAPMAC")
+    }
+
+    /// Sets the MAC address of the station interface.
+    ///
+    /// # Notes
+    ///
+    /// The address should be unicast, i.e. the lowest bit of its first byte should be zero,
+    /// otherwise the [`Error::InvalidArgument`] error is returned. It should also differ from
+    /// the access point address.
+    pub fn set_sta_mac(&mut self, mac: [u8; 6]) -> crate::Result<()> {
+        self.set_mac("CIPSTAMAC", mac)
+    }
+
+    /// Sets the MAC address of the software access point interface.
+    ///
+    /// See [`set_sta_mac`](Self::set_sta_mac) for the address requirements.
+    pub fn set_ap_mac(&mut self, mac: [u8; 6]) -> crate::Result<()> {
+        self.set_mac("CIPAPMAC", mac)
+    }
+
+    fn query_mac(&mut self, name: &str) -> crate::Result<[u8; 6]> {
+        let suffix = self.dialect().current_suffix();
+        self.query(format_args!("AT+{}{}?", name, suffix), |resp| {
+            parse_mac_response(resp, name)
+        })?
+        .ok_or(Error::CommandFailed)
+    }
+
+    fn set_mac(&mut self, name: &str, mac: [u8; 6]) -> crate::Result<()> {
+        // The address usually comes from the stored configuration, so it's not asserted.
+        if mac[0] & 1 != 0 {
+            return Err(Error::InvalidArgument);
+        }
+
+        let suffix = self.dialect().current_suffix();
+        self.send_at_command(format_args!(
+            "AT+{}{}={}",
+            name,
+            suffix,
+            MacParam(Some(mac))
+        ))?
+        .map_err(|_| Error::CommandFailed)?;
+
+        Ok(())
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Returns the MAC address of the station interface.
+    pub fn sta_mac(&mut self) -> crate::Result<[u8; 6]> {
+        self.module.sta_mac()
+    }
+
+    /// Returns the MAC address of the software access point interface.
+    pub fn ap_mac(&mut self) -> crate::Result<[u8; 6]> {
+        self.module.ap_mac()
+    }
+}
@@ -0,0 +1,505 @@
+//! Multicast DNS responder with the DNS-SD service advertisement.
+
+use core::fmt::Write;
+
+use embedded_hal::serial;
+use heapless::String;
+use simple_clock::SimpleClock;
+
+use crate::{
+    captive_portal::DNS_MESSAGE_MAX_LEN, hostname::validate_hostname, module::Escaped,
+    net::Ipv4Addr, Error, NetworkEvent, NetworkSession,
+};
+
+/// Multicast group of the mDNS.
+const MDNS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_PORT: u16 = 5353;
+/// Size of the DNS message header.
+const DNS_HEADER_LEN: usize = 12;
+/// Maximum length of the domain name in the text form.
+const NAME_MAX_LEN: usize = 255;
+/// Time to live of the advertised records in seconds.
+const RECORD_TTL: u32 = 120;
+/// Name of the services enumeration query.
+const SERVICES_NAME: &str = "_services._dns-sd._udp.local";
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+/// The record is unique, so the other cached records with the same name should be flushed.
+const CLASS_IN_FLUSH: u16 = 0x8001;
+
+/// Service advertised by the [`MdnsResponder`].
+#[derive(Debug, Clone, Copy)]
+pub struct MdnsService<'a> {
+    /// Human-readable instance name, e.g. `Kitchen sensor`.
+    pub instance: &'a str,
+    /// Service type with the protocol, e.g. `_http._tcp`.
+    pub service_type: &'a str,
+    /// Port the service listens on.
+    pub port: u16,
+    /// TXT record entries in the `key=value` form.
+    pub txt: &'a [&'a str],
+}
+
+/// Multicast DNS responder that resolves the `<hostname>.local` name and advertises
+/// the service, so the desktop tools can discover the device without a vendor app.
+///
+/// # Example
+///
+/// ```ignore
+/// let responder = MdnsResponder::new(3, "sensor", address).with_service(MdnsService {
+///     instance: "Kitchen sensor",
+///     service_type: "_http._tcp",
+///     port: 80,
+///     txt: &["path=/"],
+/// });
+/// responder.start(&mut session)?;
+/// loop {
+///     nb::block!(responder.poll(&mut session))?;
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct MdnsResponder<'a> {
+    link_id: usize,
+    hostname: &'a str,
+    address: Ipv4Addr,
+    service: Option<MdnsService<'a>>,
+}
+
+/// Records that should be included in the response.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Records {
+    services: bool,
+    ptr: bool,
+    srv: bool,
+    txt: bool,
+    a: bool,
+}
+
+impl Records {
+    fn all() -> Self {
+        Self {
+            services: false,
+            ptr: true,
+            srv: true,
+            txt: true,
+            a: true,
+        }
+    }
+
+    fn count(self) -> u16 {
+        [self.services, self.ptr, self.srv, self.txt, self.a]
+            .iter()
+            .filter(|included| **included)
+            .count() as u16
+    }
+}
+
+impl<'a> MdnsResponder<'a> {
+    /// Creates a new responder of the `<hostname>.local` name that uses the UDP socket with
+    /// the given link identifier.
+    pub fn new(link_id: usize, hostname: &'a str, address: Ipv4Addr) -> Self {
+        Self {
+            link_id,
+            hostname,
+            address,
+            service: None,
+        }
+    }
+
+    /// Sets the advertised service.
+    pub fn with_service(mut self, service: MdnsService<'a>) -> Self {
+        self.service = Some(service);
+        self
+    }
+
+    /// Joins the mDNS multicast group and announces the records.
+    pub fn start<Rx, Tx, C, const N: usize>(
+        &self,
+        session: &mut NetworkSession<Rx, Tx, C, N>,
+    ) -> crate::Result<()>
+    where
+        Rx: serial::Read<u8> + 'static,
+        Tx: serial::Write<u8> + 'static,
+        C: SimpleClock,
+    {
+        session.join_multicast(self.link_id, MDNS_GROUP, MDNS_PORT)?;
+        self.announce(session)
+    }
+
+    /// Sends the unsolicited response with all the records, e.g. after the address change.
+    pub fn announce<Rx, Tx, C, const N: usize>(
+        &self,
+        session: &mut NetworkSession<Rx, Tx, C, N>,
+    ) -> crate::Result<()>
+    where
+        Rx: serial::Read<u8> + 'static,
+        Tx: serial::Write<u8> + 'static,
+        C: SimpleClock,
+    {
+        let mut buf = [0_u8; DNS_MESSAGE_MAX_LEN];
+        match self.write_response(&mut buf, Records::all()) {
+            Some(len) => session.send(self.link_id, buf[..len].iter().copied()),
+            None => Ok(()),
+        }
+    }
+
+    /// Handles a next query received on the responder link, answers the queries about
+    /// the advertised names.
+    ///
+    /// Returns `WouldBlock` if there are no new queries. The events of the other links are
+    /// left in place, so they are returned by the next poll of the session.
+    pub fn poll<Rx, Tx, C, const N: usize>(
+        &self,
+        session: &mut NetworkSession<Rx, Tx, C, N>,
+    ) -> nb::Result<(), crate::Error>
+    where
+        Rx: serial::Read<u8> + 'static,
+        Tx: serial::Write<u8> + 'static,
+        C: SimpleClock,
+    {
+        let mut buf = [0_u8; DNS_MESSAGE_MAX_LEN];
+        let len = match session.poll_network_event_if(|event| {
+            matches!(event, NetworkEvent::DataAvailable { link_id, .. }
+                if usize::from(*link_id) == self.link_id)
+        })? {
+            NetworkEvent::DataAvailable { mut data, .. } => data.read(&mut buf),
+            _ => return Ok(()),
+        };
+
+        self.send_answer(session, &mut buf, len)?;
+        Ok(())
+    }
+
+    /// Answers the query received on the responder link, e.g. if the events are routed
+    /// by the application itself.
+    ///
+    /// The queries that are not about the advertised names are ignored.
+    pub fn handle<Rx, Tx, C, const N: usize>(
+        &self,
+        session: &mut NetworkSession<Rx, Tx, C, N>,
+        query: &[u8],
+    ) -> crate::Result<()>
+    where
+        Rx: serial::Read<u8> + 'static,
+        Tx: serial::Write<u8> + 'static,
+        C: SimpleClock,
+    {
+        let mut buf = [0_u8; DNS_MESSAGE_MAX_LEN];
+        // The oversized queries are ignored as well as the truncated ones.
+        match buf.get_mut(..query.len()) {
+            Some(dst) => dst.copy_from_slice(query),
+            None => return Ok(()),
+        }
+        self.send_answer(session, &mut buf, query.len())
+    }
+
+    fn send_answer<Rx, Tx, C, const N: usize>(
+        &self,
+        session: &mut NetworkSession<Rx, Tx, C, N>,
+        buf: &mut [u8],
+        len: usize,
+    ) -> crate::Result<()>
+    where
+        Rx: serial::Read<u8> + 'static,
+        Tx: serial::Write<u8> + 'static,
+        C: SimpleClock,
+    {
+        match self.answer(buf, len) {
+            Some(len) => session.send(self.link_id, buf[..len].iter().copied()),
+            None => Ok(()),
+        }
+    }
+
+    /// Replaces the mDNS query of the given length in the buffer with the response,
+    /// returns the response length or `None` if the query is not about the advertised names.
+    pub fn answer(&self, buf: &mut [u8], len: usize) -> Option<usize> {
+        let records = self.requested_records(buf.get(..len)?)?;
+        self.write_response(buf, records)
+    }
+
+    /// Finds out which records are requested by the query.
+    fn requested_records(&self, query: &[u8]) -> Option<Records> {
+        // Skip the responses.
+        if query.len() < DNS_HEADER_LEN || query[2] & 0x80 != 0 {
+            return None;
+        }
+
+        let mut records = Records::default();
+        let mut pos = DNS_HEADER_LEN;
+        for _ in 0..u16::from_be_bytes([query[4], query[5]]) {
+            let mut name = String::<NAME_MAX_LEN>::new();
+            pos = read_name(query, pos, &mut name)?;
+            let qtype = u16::from_be_bytes([*query.get(pos)?, *query.get(pos + 1)?]);
+            // Skip the type and class.
+            pos += 4;
+
+            let is = |expected: &str| name.eq_ignore_ascii_case(expected);
+            let is_type = |expected: u16| qtype == expected || qtype == TYPE_ANY;
+            if is(&self.host_name()?) && is_type(TYPE_A) {
+                records.a = true;
+            }
+            if let Some(service) = self.service.as_ref() {
+                if is(SERVICES_NAME) && is_type(TYPE_PTR) {
+                    records.services = true;
+                }
+                // The service instance is resolved right away by the additional records.
+                if is(&service_name(service)?) && is_type(TYPE_PTR) {
+                    records.ptr = true;
+                    records.srv = true;
+                    records.txt = true;
+                    records.a = true;
+                }
+                if is(&instance_name(service)?) && (is_type(TYPE_SRV) || is_type(TYPE_TXT)) {
+                    records.srv |= is_type(TYPE_SRV);
+                    records.txt |= is_type(TYPE_TXT);
+                    records.a = true;
+                }
+            }
+        }
+
+        if records == Records::default() {
+            None
+        } else {
+            Some(records)
+        }
+    }
+
+    /// Writes the response with the given records into the buffer, returns its length.
+    fn write_response(&self, buf: &mut [u8], mut records: Records) -> Option<usize> {
+        let host_name = self.host_name()?;
+        if self.service.is_none() {
+            records = Records {
+                a: records.a,
+                ..Records::default()
+            };
+        }
+
+        let mut packet = Packet { buf, len: 0 };
+        // Identifier is zero, authoritative response, no questions.
+        packet.put(&[0, 0, 0x84, 0])?;
+        packet.put_u16(0)?;
+        packet.put_u16(records.count())?;
+        packet.put(&[0, 0, 0, 0])?;
+
+        if let Some(service) = self.service.as_ref() {
+            let service_name = service_name(service)?;
+
+            if records.services {
+                packet.put_record(SERVICES_NAME, TYPE_PTR, CLASS_IN, |packet| {
+                    packet.put_name(&service_name)
+                })?;
+            }
+            if records.ptr {
+                packet.put_record(&service_name, TYPE_PTR, CLASS_IN, |packet| {
+                    packet.put_label(service.instance)?;
+                    packet.put_name(&service_name)
+                })?;
+            }
+            if records.srv {
+                packet.put_instance_record(service, &service_name, TYPE_SRV, |packet| {
+                    // Priority and weight.
+                    packet.put(&[0, 0, 0, 0])?;
+                    packet.put_u16(service.port)?;
+                    packet.put_name(&host_name)
+                })?;
+            }
+            if records.txt {
+                packet.put_instance_record(service, &service_name, TYPE_TXT, |packet| {
+                    // The empty TXT record consists of a single zero byte.
+                    if service.txt.is_empty() {
+                        return packet.put(&[0]);
+                    }
+                    for entry in service.txt {
+                        packet.put_label(entry)?;
+                    }
+                    Some(())
+                })?;
+            }
+        }
+        if records.a {
+            packet.put_record(&host_name, TYPE_A, CLASS_IN_FLUSH, |packet| {
+                packet.put(&self.address.octets())
+            })?;
+        }
+
+        Some(packet.len)
+    }
+
+    /// Returns the `<hostname>.local` name.
+    fn host_name(&self) -> Option<String<NAME_MAX_LEN>> {
+        let mut name = String::new();
+        write!(name, "{}.local", self.hostname).ok()?;
+        Some(name)
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Enables the mDNS responder of the module firmware, which resolves the
+    /// `<hostname>.local` name and advertises the service, e.g. `_http` on the port 80.
+    ///
+    /// Unlike the [`MdnsResponder`], it doesn't occupy a link and doesn't need the polling,
+    /// but it advertises only one service without the TXT records.
+    ///
+    /// # Notes
+    ///
+    /// The module should be connected to the access point, the responder is stopped
+    /// after the disconnection.
+    pub fn enable_mdns(&mut self, hostname: &str, service: &str, port: u16) -> crate::Result<()> {
+        validate_hostname(hostname)?;
+
+        self.module
+            .send_at_command(format_args!(
+                "AT+MDNS=1,\"{}\",\"{}\",{}",
+                hostname,
+                Escaped(service),
+                port
+            ))?
+            .map_err(|_| Error::CommandFailed)?;
+        Ok(())
+    }
+
+    /// Disables the mDNS responder of the module firmware.
+    pub fn disable_mdns(&mut self) -> crate::Result<()> {
+        self.module
+            .send_at_command("AT+MDNS=0")?
+            .map_err(|_| Error::CommandFailed)?;
+        Ok(())
+    }
+}
+
+/// Returns the `<service type>.local` name.
+fn service_name(service: &MdnsService<'_>) -> Option<String<NAME_MAX_LEN>> {
+    let mut name = String::new();
+    write!(name, "{}.local", service.service_type).ok()?;
+    Some(name)
+}
+
+/// Returns the `<instance>.<service type>.local` name in the text form.
+///
+/// The instance name may contain dots, so this form is only suitable for the comparison.
+fn instance_name(service: &MdnsService<'_>) -> Option<String<NAME_MAX_LEN>> {
+    let mut name = String::new();
+    write!(name, "{}.{}.local", service.instance, service.service_type).ok()?;
+    Some(name)
+}
+
+/// Reads the possibly compressed name at the given position of the message in the dotted
+/// form, returns the position after the name.
+fn read_name(message: &[u8], mut pos: usize, name: &mut String<NAME_MAX_LEN>) -> Option<usize> {
+    // Position after the name in the original place, before the first pointer.
+    let mut end = None;
+    // Limit the number of pointers to avoid the loops.
+    let mut jumps = 0;
+    loop {
+        let len = usize::from(*message.get(pos)?);
+        match len {
+            0 => return Some(end.unwrap_or(pos + 1)),
+            len if len & 0xC0 == 0xC0 => {
+                jumps += 1;
+                if jumps > 16 {
+                    return None;
+                }
+                end.get_or_insert(pos + 2);
+                pos = (len & 0x3F) << 8 | usize::from(*message.get(pos + 1)?);
+            }
+            len if len & 0xC0 == 0 => {
+                let label = message.get(pos + 1..pos + 1 + len)?;
+                if !name.is_empty() {
+                    name.push('.').ok()?;
+                }
+                name.push_str(core::str::from_utf8(label).ok()?).ok()?;
+                pos += len + 1;
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// DNS message being written into the buffer.
+struct Packet<'b> {
+    buf: &'b mut [u8],
+    len: usize,
+}
+
+impl Packet<'_> {
+    fn put(&mut self, bytes: &[u8]) -> Option<()> {
+        self.buf
+            .get_mut(self.len..self.len + bytes.len())?
+            .copy_from_slice(bytes);
+        self.len += bytes.len();
+        Some(())
+    }
+
+    fn put_u16(&mut self, value: u16) -> Option<()> {
+        self.put(&value.to_be_bytes())
+    }
+
+    /// Writes the string prefixed by its length.
+    fn put_label(&mut self, label: &str) -> Option<()> {
+        if label.len() > 63 {
+            return None;
+        }
+        self.put(&[label.len() as u8])?;
+        self.put(label.as_bytes())
+    }
+
+    /// Writes the dotted name.
+    fn put_name(&mut self, name: &str) -> Option<()> {
+        for label in name.split('.') {
+            self.put_label(label)?;
+        }
+        self.put(&[0])
+    }
+
+    /// Writes the resource record, the record data is written by the given function.
+    fn put_record<F>(&mut self, name: &str, rtype: u16, class: u16, data: F) -> Option<()>
+    where
+        F: FnOnce(&mut Self) -> Option<()>,
+    {
+        self.put_name(name)?;
+        self.put_data(rtype, class, data)
+    }
+
+    /// Writes the resource record of the service instance.
+    fn put_instance_record<F>(
+        &mut self,
+        service: &MdnsService<'_>,
+        service_name: &str,
+        rtype: u16,
+        data: F,
+    ) -> Option<()>
+    where
+        F: FnOnce(&mut Self) -> Option<()>,
+    {
+        // The instance name is a single label even if it contains dots.
+        self.put_label(service.instance)?;
+        self.put_name(service_name)?;
+        self.put_data(rtype, CLASS_IN_FLUSH, data)
+    }
+
+    fn put_data<F>(&mut self, rtype: u16, class: u16, data: F) -> Option<()>
+    where
+        F: FnOnce(&mut Self) -> Option<()>,
+    {
+        self.put_u16(rtype)?;
+        self.put_u16(class)?;
+        self.put(&RECORD_TTL.to_be_bytes())?;
+
+        let len_pos = self.len;
+        self.put_u16(0)?;
+        data(self)?;
+        let data_len = (self.len - len_pos - 2) as u16;
+        self.buf[len_pos..len_pos + 2].copy_from_slice(&data_len.to_be_bytes());
+        Some(())
+    }
+}
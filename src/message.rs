@@ -0,0 +1,85 @@
+//! Typed messages transport over the links using the postcard format.
+//!
+//! Each message is framed by the two bytes little-endian length prefix followed by
+//! the postcard-encoded payload.
+
+use embedded_hal::serial;
+use serde::{Deserialize, Serialize};
+use simple_clock::SimpleClock;
+
+use crate::{BufReader, Error, NetworkSession};
+
+/// Size of the message length prefix.
+const PREFIX_LEN: usize = 2;
+/// Maximum size of the message payload, the whole frame is sent by a single command.
+pub const MESSAGE_MAX_LEN: usize = 2047 - PREFIX_LEN;
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Serializes the message into the given buffer and sends it via the link with
+    /// the given identifier.
+    ///
+    /// Returns the `BufferFull` error if the encoded message doesn't fit into the buffer
+    /// or is longer than the [`MESSAGE_MAX_LEN`] bytes.
+    pub fn send_message<T>(
+        &mut self,
+        link_id: usize,
+        message: &T,
+        buf: &mut [u8],
+    ) -> crate::Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        if buf.len() < PREFIX_LEN {
+            return Err(Error::BufferFull);
+        }
+
+        let (prefix, payload) = buf.split_at_mut(PREFIX_LEN);
+        let len = postcard::to_slice(message, payload)
+            .map_err(|_| Error::BufferFull)?
+            .len();
+        if len > MESSAGE_MAX_LEN {
+            return Err(Error::BufferFull);
+        }
+        prefix.copy_from_slice(&(len as u16).to_le_bytes());
+
+        self.send(link_id, buf[..PREFIX_LEN + len].iter().copied())
+    }
+}
+
+impl<'a, Rx, Tx, C, const N: usize, const B: usize> BufReader<'a, Rx, Tx, C, N, B>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Blocks until a complete message is received and deserializes it, the given buffer
+    /// is used to store the message payload.
+    ///
+    /// If the message doesn't fit into the buffer, it is skipped and the `BufferFull` error
+    /// is returned. The `MalformedMessage` error is returned if the message cannot be
+    /// deserialized.
+    pub fn read_message<'b, T>(&mut self, buf: &'b mut [u8]) -> crate::Result<T>
+    where
+        T: Deserialize<'b>,
+    {
+        let mut prefix = [0_u8; PREFIX_LEN];
+        self.read_exact(&mut prefix)?;
+        let len = usize::from(u16::from_le_bytes(prefix));
+
+        if len > buf.len() {
+            // Skip the message, so the next read starts from the next message.
+            for _ in 0..len {
+                self.read_exact(&mut prefix[..1])?;
+            }
+            return Err(Error::BufferFull);
+        }
+
+        self.read_exact(&mut buf[..len])?;
+        postcard::from_bytes(&buf[..len]).map_err(|_| Error::MalformedMessage)
+    }
+}
@@ -1,18 +1,120 @@
-use core::fmt::Write;
+use core::{convert::TryFrom, fmt::Write, time::Duration};
 
-use embedded_hal::serial;
+use embedded_hal::{digital::v2::OutputPin, serial};
 use simple_clock::{Deadline, ElapsedTimer, SimpleClock};
 
 use crate::{
-    error::{Error, Result},
-    parser::CifsrResponse,
-    reader_part::{ReadData, ReaderPart},
+    capabilities::{AtVersion, Capabilities},
+    error::{Error, Result, SerialErrorKind},
+    parser::{CifsrResponse, CommandResponse, CwjapResponse, IpConfigResponse},
+    pipeline::InFlight,
+    reader_part::{truncate_buf, ReadData, ReaderPart},
+    trace::{TraceBuffer, TraceRing, TRACE_BUFFER_LEN},
+    Chip, Dialect, Response,
 };
 
-const RESET_DELAY_US: u64 = 3_000_000;
+/// Default size of the reader buffer in bytes.
+pub const DEFAULT_BUFFER_LEN: usize = 1024;
 
-/// Raw response to a sent AT command.
-pub type RawResponse<'a, const N: usize> = core::result::Result<ReadData<'a, N>, ReadData<'a, N>>;
+/// Duration of the low level pulse on the hardware reset pin.
+const RESET_PULSE_US: u64 = 1_000;
+
+/// Output pin connected to the module `RST` pin.
+///
+/// This trait is implemented for all the [`OutputPin`] implementations.
+pub trait ResetPin {
+    /// Drives the pin to the given level.
+    fn set_level(&mut self, high: bool) -> Result<()>;
+}
+
+impl<P: OutputPin> ResetPin for P {
+    fn set_level(&mut self, high: bool) -> Result<()> {
+        let res = if high {
+            self.set_high()
+        } else {
+            self.set_low()
+        };
+        res.map_err(|_| Error::ResetPin)
+    }
+}
+
+/// The way to reset the module.
+///
+/// The kinds are listed in the order of the escalation of the recovery procedure.
+pub enum ResetKind<'a> {
+    /// Software reset by the `AT+RST` command.
+    Soft,
+    /// Hardware reset by pulling down the `RST` pin of the module.
+    Hardware(&'a mut dyn ResetPin),
+    /// Restore the factory settings by the `AT+RESTORE` command.
+    ///
+    /// All the parameters saved in the flash are erased, including the WiFi configuration.
+    Restore,
+}
+
+impl core::fmt::Debug for ResetKind<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Soft => f.write_str("Soft"),
+            Self::Hardware(_) => f.write_str("Hardware"),
+            Self::Restore => f.write_str("Restore"),
+        }
+    }
+}
+
+/// Parameters of the module resetting routine.
+///
+/// Clone modules vary wildly in boot timing, so the defaults may need to be tuned.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub struct ResetConfig {
+    /// Maximum time to wait for the `ready` banner in microseconds, or the fixed delay
+    /// after the reset if the banner is not awaited.
+    pub delay_us: u64,
+    /// Number of attempts to send the first command after the reset.
+    pub recovery_attempts: u8,
+    /// Wait for the `ready` banner after the reset.
+    pub wait_ready: bool,
+}
+
+impl Default for ResetConfig {
+    fn default() -> Self {
+        Self {
+            delay_us: 5_000_000,
+            recovery_attempts: 100,
+            wait_ready: true,
+        }
+    }
+}
+
+/// Describes how to retry AT commands that failed due to transient errors.
+///
+/// The transient errors are timeouts, serial port read errors and the `busy` responses
+/// of the module.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts to send a command, including the first one.
+    pub max_attempts: u8,
+    /// Delay between the attempts in microseconds.
+    pub delay_us: u64,
+}
+
+impl RetryPolicy {
+    /// Policy that sends each command only once.
+    pub const NONE: Self = Self {
+        max_attempts: 1,
+        delay_us: 0,
+    };
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+/// Raw response to a sent AT command, the `Err` variant contains the response to the rejected
+/// command.
+pub type RawResponse<'a, const N: usize> = core::result::Result<Response<'a, N>, Response<'a, N>>;
 
 /// The trait describes how to send a certain AT command.
 pub trait AtCommand: private::Sealed {
@@ -26,6 +128,10 @@ pub trait AtCommand: private::Sealed {
         Rx: serial::Read<u8> + 'static,
         Tx: serial::Write<u8> + 'static,
         C: SimpleClock;
+
+    /// Writes the AT command text into the given writer.
+    #[doc(hidden)]
+    fn write_to<W: Write>(self, writer: &mut W) -> core::fmt::Result;
 }
 
 impl AtCommand for &str {
@@ -40,6 +146,10 @@ impl AtCommand for &str {
     {
         module.send_at_command_str(self)
     }
+
+    fn write_to<W: Write>(self, writer: &mut W) -> core::fmt::Result {
+        writer.write_str(self)
+    }
 }
 
 impl AtCommand for core::fmt::Arguments<'_> {
@@ -54,15 +164,44 @@ impl AtCommand for core::fmt::Arguments<'_> {
     {
         module.send_at_command_fmt(self)
     }
+
+    fn write_to<W: Write>(self, writer: &mut W) -> core::fmt::Result {
+        writer.write_fmt(self)
+    }
 }
 
 const NEWLINE: &[u8] = b"\r\n";
 
+/// Formats the string argument of an AT command by escaping the special characters.
+///
+/// The `"`, `,` and `\` characters should be escaped with the backslash.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Escaped<'a>(pub &'a str);
+
+impl core::fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for c in self.0.chars() {
+            if matches!(c, '"' | ',' | '\\') {
+                f.write_char('\\')?;
+            }
+            f.write_char(c)?;
+        }
+        Ok(())
+    }
+}
+
 /// Basic communication interface with the esp8266 module.
 ///
 /// Provides basic functionality for sending AT commands and getting corresponding responses.
+///
+/// The `N` parameter is the size of the reader buffer, it should fit the longest expected
+/// response. If it's omitted in the type, the [`DEFAULT_BUFFER_LEN`] is used:
+///
+/// ```ignore
+/// let module: Module<_, _, _> = Module::new(rx, tx, clock)?;
+/// ```
 #[derive(Debug)]
-pub struct Module<Rx, Tx, C, const N: usize>
+pub struct Module<Rx, Tx, C, const N: usize = DEFAULT_BUFFER_LEN>
 where
     Rx: serial::Read<u8> + 'static,
     Tx: serial::Write<u8> + 'static,
@@ -72,9 +211,21 @@ where
     pub(crate) writer: WriterPart<Tx>,
     pub(crate) clock: C,
     pub(crate) timeout: Option<u64>,
+    pub(crate) retry_policy: RetryPolicy,
+    pub(crate) reset_config: ResetConfig,
+    /// Time of the last module reset.
+    pub(crate) reset_at: u64,
+    pub(crate) at_version: Option<AtVersion>,
+    pub(crate) capabilities: Capabilities,
+    /// Capabilities that have been probed already.
+    pub(crate) probed: Capabilities,
+    pub(crate) chip: Chip,
+    pub(crate) dialect: Dialect,
+    /// Sent segments that are waiting for the delivery confirmation.
+    pub(crate) in_flight: InFlight,
 }
 
-impl<'a, Rx, Tx, C, const N: usize> Module<Rx, Tx, C, N>
+impl<Rx, Tx, C, const N: usize> Module<Rx, Tx, C, N>
 where
     Rx: serial::Read<u8> + 'static,
     Tx: serial::Write<u8> + 'static,
@@ -82,11 +233,24 @@ where
 {
     /// Establishes serial communication with the esp8266 module.
     pub fn new(rx: Rx, tx: Tx, clock: C) -> Result<Self> {
+        let reset_at = clock.now_us();
         let mut module = Self {
             reader: ReaderPart::new(rx),
-            writer: WriterPart { tx },
+            writer: WriterPart {
+                tx,
+                trace: TraceRing::new(),
+            },
             clock,
             timeout: None,
+            retry_policy: RetryPolicy::NONE,
+            reset_config: ResetConfig::default(),
+            reset_at,
+            at_version: None,
+            capabilities: Capabilities::empty(),
+            probed: Capabilities::empty(),
+            chip: Chip::Esp8266,
+            dialect: Dialect::EspAt,
+            in_flight: InFlight::default(),
         };
         module.init()?;
         Ok(module)
@@ -94,41 +258,148 @@ where
 
     fn init(&mut self) -> Result<()> {
         self.disable_echo()?;
+        self.identify()?;
         Ok(())
     }
 
-    fn reset_cmd(&mut self) -> Result<()> {
-        self.write_command(b"AT+RST")?;
+    fn reset_cmd(&mut self, kind: ResetKind<'_>) -> Result<()> {
+        // The received data is meaningless after the reset.
+        self.reader.buf_mut().clear();
+        match kind {
+            ResetKind::Soft => self.write_command(b"AT+RST")?,
+            ResetKind::Hardware(pin) => {
+                pin.set_level(false)?;
+                self.delay(RESET_PULSE_US);
+                pin.set_level(true)?;
+            }
+            ResetKind::Restore => self.write_command(b"AT+RESTORE")?,
+        }
 
-        // Workaround to ignore the framing errors.
-        let timer = ElapsedTimer::new(&self.clock);
-        while timer.elapsed() < RESET_DELAY_US {
-            core::hint::spin_loop();
+        if self.reset_config.wait_ready {
+            self.wait_ready()
+        } else {
+            self.delay(self.reset_config.delay_us);
+            self.reader.buf_mut().clear();
+            Ok(())
         }
+    }
 
-        self.read_until(ReadyCondition)?;
+    /// Reads and discards the module boot output until the `ready` banner.
+    fn wait_ready(&mut self) -> Result<()> {
+        let deadline = Deadline::new(&self.clock, self.reset_config.delay_us);
+        let marker_len = ReadyCondition::MSG.len();
 
+        loop {
+            // It is ok to receive errors like "framing" during the module boot.
+            self.reader.read_bytes().ok();
+
+            let buf = self.reader.buf_mut();
+            if find_line(buf, ReadyCondition::MSG).is_some() {
+                break;
+            }
+            // Keep only the tail that may contain the beginning of the banner.
+            if buf.is_full() {
+                let len = buf.len();
+                truncate_buf(buf, len - (marker_len - 1));
+            }
+
+            deadline.reached().map_err(|_| Error::Timeout)?;
+        }
+
+        // The boot output is meaningless.
+        self.reader.buf_mut().clear();
         Ok(())
     }
 
-    /// Sets the operation timeout to the timeout specified.
+    /// Sets the operation timeout in microseconds.
     ///
     /// If the specified value is `None`, the operations will block infinitely.
     pub fn set_timeout(&mut self, us: Option<u64>) {
         self.timeout = us;
     }
 
+    /// Sets the operation timeout in milliseconds.
+    ///
+    /// See [`set_timeout`](Self::set_timeout).
+    pub fn set_timeout_ms(&mut self, ms: Option<u64>) {
+        self.timeout = ms.map(|ms| ms.saturating_mul(1_000));
+    }
+
+    /// Sets the operation timeout as a duration.
+    ///
+    /// See [`set_timeout`](Self::set_timeout).
+    pub fn set_timeout_duration(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout.map(duration_us);
+    }
+
+    /// Returns the operation timeout in microseconds.
+    pub fn timeout(&self) -> Option<u64> {
+        self.timeout
+    }
+
+    /// Returns the operation timeout in milliseconds.
+    pub fn timeout_ms(&self) -> Option<u64> {
+        self.timeout.map(|us| us / 1_000)
+    }
+
+    /// Returns the operation timeout as a duration.
+    pub fn timeout_duration(&self) -> Option<Duration> {
+        self.timeout.map(Duration::from_micros)
+    }
+
+    /// Sets the function that recognizes the kind of the serial port receiving errors.
+    ///
+    /// The recognized errors are reported as [`Error::Serial`], the rest of them as
+    /// [`Error::ReadBuffer`]. It allows, for example, to distinguish the overruns from
+    /// the framing noise during the module boot.
+    pub fn set_serial_error_classifier(
+        &mut self,
+        classify: fn(&Rx::Error) -> Option<SerialErrorKind>,
+    ) {
+        self.reader.classify = classify;
+    }
+
+    /// Sets the policy of retrying commands that failed due to transient errors.
+    ///
+    /// By default, each command is sent only once.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    /// Sets the parameters of the module resetting routine.
+    pub fn set_reset_config(&mut self, config: ResetConfig) {
+        self.reset_config = config;
+    }
+
     /// Performs the module resetting routine.
-    pub fn reset(&mut self) -> Result<()> {
-        // FIXME: It is ok to receive errors like "framing" during the reset procedure.
-        self.reset_cmd().ok();
-        // Workaround to catch the framing errors.
-        for _ in 0..100 {
-            self.send_at_command_str("ATE1").ok();
-        }
+    ///
+    /// See [`set_reset_config`](Self::set_reset_config) to tune it.
+    pub fn reset(&mut self, kind: ResetKind<'_>) -> Result<()> {
+        self.reset_cmd(kind)?;
+        self.reset_at = self.clock.now_us();
+
+        // The module may respond with the framing errors for a while after the reset.
+        let policy = core::mem::replace(
+            &mut self.retry_policy,
+            RetryPolicy {
+                max_attempts: self.reset_config.recovery_attempts,
+                delay_us: 0,
+            },
+        );
+        let res = self.disable_echo();
+        self.retry_policy = policy;
+        res
+    }
 
-        self.disable_echo()?;
-        Ok(())
+    /// Returns the last bytes exchanged with the module, e.g. to dump them after
+    /// an unexpected error.
+    pub fn trace_buffer(&self) -> TraceBuffer<'_> {
+        TraceBuffer::new(&self.writer.trace, &self.reader.trace)
+    }
+
+    /// Returns the time elapsed since the module has been reset in microseconds.
+    pub fn uptime(&self) -> u64 {
+        self.clock.now_us().saturating_sub(self.reset_at)
     }
 
     /// Sends an AT command and gets the response for it.
@@ -137,30 +408,86 @@ where
     }
 
     fn send_at_command_str(&mut self, cmd: &str) -> Result<RawResponse<'_, N>> {
-        self.write_command(cmd.as_ref())?;
-        self.read_until(OkCondition)
+        self.send_with_retries(|module| module.write_command(cmd.as_ref()))
     }
 
     fn send_at_command_fmt(&mut self, args: core::fmt::Arguments) -> Result<RawResponse<'_, N>> {
-        self.write_command_fmt(args)?;
-        self.read_until(OkCondition)
+        self.send_with_retries(|module| module.write_command_fmt(args))
+    }
+
+    /// Writes a command by the given function and waits for the response to it, the command
+    /// is repeated according to the retry policy.
+    fn send_with_retries<F>(&mut self, mut write: F) -> Result<RawResponse<'_, N>>
+    where
+        F: FnMut(&mut Self) -> Result<()>,
+    {
+        // The pending delivery confirmations would be taken for the command response.
+        self.complete_sends()?;
+
+        let mut kept = self.reader.buf().len();
+        let mut attempt = 1;
+        loop {
+            let res = write(self).and_then(|_| self.wait_until(OkCondition, &mut kept));
+            let is_transient = match res {
+                Ok(()) => OkCondition::is_busy(&self.reader.buf()[kept..]),
+                Err(Error::Timeout) | Err(Error::ReadBuffer) | Err(Error::Serial(_)) => true,
+                Err(_) => false,
+            };
+
+            if !is_transient || attempt >= self.retry_policy.max_attempts {
+                res?;
+                break;
+            }
+
+            attempt += 1;
+            self.reader.buf_mut().truncate(kept);
+            self.delay(self.retry_policy.delay_us);
+        }
+
+        Ok(OkCondition.output(ReadData::new(self.reader.buf_mut(), kept)))
     }
 
-    fn disable_echo(&mut self) -> Result<()> {
+    pub(crate) fn delay(&self, us: u64) {
+        let timer = ElapsedTimer::new(&self.clock);
+        while timer.elapsed() < us {
+            core::hint::spin_loop();
+        }
+    }
+
+    pub(crate) fn disable_echo(&mut self) -> Result<()> {
         self.send_at_command_str("ATE0").map(drop)
     }
 
     fn write_command(&mut self, cmd: &[u8]) -> Result<()> {
         self.writer.write_bytes(cmd)?;
-        self.writer.write_bytes(NEWLINE)
+        self.writer.write_bytes(NEWLINE)?;
+        // Make sure the command doesn't sit in the TX buffer while the deadline ticks.
+        self.writer.flush()
     }
 
     pub(crate) fn write_command_fmt(&mut self, args: core::fmt::Arguments) -> Result<()> {
         self.writer.write_fmt(args)?;
-        self.writer.write_bytes(NEWLINE)
+        self.writer.write_bytes(NEWLINE)?;
+        self.writer.flush()
     }
 
     pub(crate) fn read_until<'b, T>(&'b mut self, condition: T) -> Result<T::Output>
+    where
+        T: Condition<'b, N>,
+    {
+        let mut kept = self.reader.buf().len();
+        self.wait_until(condition, &mut kept)?;
+
+        let read_data = ReadData::new(self.reader.buf_mut(), kept);
+        Ok(condition.output(read_data))
+    }
+
+    /// Reads bytes into the buffer until the condition is performed.
+    ///
+    /// The bytes before the `kept` position are not the part of the response, the
+    /// unsolicited frames received while waiting for the response are diverted to this
+    /// part of the buffer, so the network session will handle them later.
+    pub(crate) fn wait_until<'b, T>(&mut self, condition: T, kept: &mut usize) -> Result<()>
     where
         T: Condition<'b, N>,
     {
@@ -175,12 +502,13 @@ where
                     }
                 }
                 Err(nb::Error::WouldBlock) => {}
-                Err(nb::Error::Other(_)) => {
-                    return Err(Error::ReadBuffer);
+                Err(nb::Error::Other(err)) => {
+                    return Err(err);
                 }
             };
 
-            if condition.is_performed(&self.reader.buf()) {
+            *kept = skip_unsolicited_frames(self.reader.buf(), *kept);
+            if condition.is_performed(&self.reader.buf()[*kept..]) {
                 break;
             }
 
@@ -189,22 +517,75 @@ where
             }
         }
 
-        let read_data = ReadData::new(self.reader.buf_mut());
-        Ok(condition.output(read_data))
+        Ok(())
     }
 
     pub(crate) fn get_network_info(&mut self) -> Result<CifsrResponse> {
         // Get assigned SoftAP address.
-        let res = self.send_at_command("AT+CIFSR")?;
-        let raw_resp = res.expect("Malformed command");
+        let raw_resp = self
+            .send_at_command("AT+CIFSR")?
+            .map_err(|_| Error::CommandFailed)?;
+        Ok(CifsrResponse::parse(&raw_resp))
+    }
 
-        let resp = CifsrResponse::parse(&raw_resp)
-            .unwrap_or_else(|| panic!("Unable to parse response: {:?}", raw_resp))
-            .1;
+    /// Gets the IP configuration of the interface by the given command name,
+    /// e.g. `CIPSTA` for the `AT+CIPSTA?` query.
+    ///
+    /// Returns `None` if the interface is not available in the current WiFi mode.
+    pub(crate) fn get_ip_config(&mut self, name: &str) -> Result<Option<IpConfigResponse>> {
+        let suffix = self.dialect.current_suffix();
+        self.query(format_args!("AT+{}{}?", name, suffix), |resp| {
+            IpConfigResponse::parse(resp, name).map(|(_, resp)| resp)
+        })
+    }
+
+    /// Gets the channel and RSSI of the joined access point.
+    ///
+    /// Returns `None` if the module is not connected to any access point.
+    pub(crate) fn get_ap_signal(&mut self) -> Result<Option<(u8, i8)>> {
+        let suffix = self.dialect.current_suffix();
+        self.query(format_args!("AT+CWJAP{}?", suffix), |resp| {
+            CwjapResponse::parse(resp).map(|(_, resp)| (resp.channel, resp.rssi))
+        })
+    }
+
+    /// Sends the query command and parses its response by the given function.
+    ///
+    /// Returns `None` if the module rejected the command or the response cannot be parsed.
+    pub(crate) fn query<T, F, R>(&mut self, cmd: T, parse: F) -> Result<Option<R>>
+    where
+        T: AtCommand,
+        F: FnOnce(&[u8]) -> Option<R>,
+    {
+        let resp = match self.send_at_command(cmd)? {
+            Ok(raw_resp) => parse(raw_resp.as_ref()),
+            Err(_) => None,
+        };
         Ok(resp)
     }
 }
 
+/// Converts the duration to microseconds, the too long durations are saturated.
+pub(crate) fn duration_us(duration: Duration) -> u64 {
+    u64::try_from(duration.as_micros()).unwrap_or(u64::MAX)
+}
+
+/// Returns the position after the complete unsolicited frames, like `+IPD` with its payload,
+/// that follow the given position in the buffer.
+pub(crate) fn skip_unsolicited_frames(buf: &[u8], mut pos: usize) -> usize {
+    while let Some((rest, response)) = CommandResponse::parse(&buf[pos..]) {
+        let mut frame_len = buf.len() - pos - rest.len();
+        if let CommandResponse::DataAvailable { size, .. } = response {
+            if (rest.len() as u64) < size {
+                break;
+            }
+            frame_len += size as usize;
+        }
+        pos += frame_len;
+    }
+    pos
+}
+
 pub(crate) trait Condition<'a, const N: usize>: Copy {
     type Output: 'a;
 
@@ -213,22 +594,48 @@ pub(crate) trait Condition<'a, const N: usize>: Copy {
     fn output(self, buf: ReadData<'a, N>) -> Self::Output;
 }
 
+/// Returns the position of the first occurrence of the needle in the haystack.
+pub(crate) fn find_subsequence(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Returns the position of the first occurrence of the needle that starts a new line,
+/// i.e. it is preceded by a line break or placed at the beginning of the haystack.
+pub(crate) fn find_line(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    let mut from = 0;
+    while let Some(pos) = find_subsequence(&haystack[from..], needle) {
+        let pos = from + pos;
+        if pos == 0 || haystack[pos - 1] == b'\n' {
+            return Some(pos);
+        }
+        from = pos + 1;
+    }
+    None
+}
+
 #[derive(Clone, Copy)]
-struct ReadyCondition;
+pub(crate) struct ReadyCondition;
 
 impl ReadyCondition {
-    const MSG: &'static [u8] = b"ready\r\n";
+    pub(crate) const MSG: &'static [u8] = b"ready\r\n";
 }
 
 impl<'a, const N: usize> Condition<'a, N> for ReadyCondition {
     type Output = ReadData<'a, N>;
 
     fn is_performed(self, buf: &[u8]) -> bool {
-        buf.ends_with(Self::MSG)
+        find_line(buf, Self::MSG).is_some()
     }
 
     fn output(self, mut buf: ReadData<'a, N>) -> Self::Output {
-        buf.subslice(0, buf.len() - Self::MSG.len());
+        // The condition is performed, so the banner is always found.
+        if let Some(pos) = find_line(&buf, Self::MSG) {
+            // The boot noise after the banner is meaningless.
+            let marker_len = buf.len() - pos;
+            buf.cut(pos, marker_len);
+        }
         buf
     }
 }
@@ -244,11 +651,13 @@ impl<'a, const N: usize> Condition<'a, N> for CarretCondition {
     type Output = ReadData<'a, N>;
 
     fn is_performed(self, buf: &[u8]) -> bool {
-        buf.ends_with(Self::MSG)
+        find_subsequence(buf, Self::MSG).is_some()
     }
 
     fn output(self, mut buf: ReadData<'a, N>) -> Self::Output {
-        buf.subslice(0, buf.len() - Self::MSG.len());
+        if let Some(pos) = find_subsequence(&buf, Self::MSG) {
+            buf.cut(pos, Self::MSG.len());
+        }
         buf
     }
 }
@@ -257,36 +666,85 @@ impl<'a, const N: usize> Condition<'a, N> for CarretCondition {
 pub(crate) struct OkCondition;
 
 impl OkCondition {
-    const OK: &'static [u8] = b"OK\r\n";
+    pub(crate) const OK: &'static [u8] = b"OK\r\n";
+    const SEND_OK: &'static [u8] = b"SEND OK\r\n";
+    const SEND_FAIL: &'static [u8] = b"SEND FAIL\r\n";
     const ERROR: &'static [u8] = b"ERROR\r\n";
     const FAIL: &'static [u8] = b"FAIL\r\n";
+    const BUSY: &'static [u8] = b"busy p...\r\n";
+    const BUSY_SENDING: &'static [u8] = b"busy s...\r\n";
+
+    const TERMINATORS: [&'static [u8]; 7] = [
+        Self::OK,
+        Self::SEND_OK,
+        Self::SEND_FAIL,
+        Self::ERROR,
+        Self::FAIL,
+        Self::BUSY,
+        Self::BUSY_SENDING,
+    ];
+
+    /// Finds the first terminator of the command response and returns its position.
+    ///
+    /// The terminators are matched only at the line beginnings, so the same bytes inside
+    /// the response data, e.g. in the SSID, are not confused with them.
+    pub(crate) fn find_terminator(buf: &[u8]) -> Option<(usize, &'static [u8])> {
+        Self::TERMINATORS
+            .iter()
+            .filter_map(|terminator| Some((find_line(buf, terminator)?, *terminator)))
+            .min_by_key(|(pos, _)| *pos)
+    }
+
+    /// Checks that the module is busy and hasn't processed the command.
+    pub(crate) fn is_busy(buf: &[u8]) -> bool {
+        matches!(
+            Self::find_terminator(buf),
+            Some((_, terminator)) if terminator == Self::BUSY || terminator == Self::BUSY_SENDING
+        )
+    }
 }
 
-// TODO optimize this condition.
 impl<'a, const N: usize> Condition<'a, N> for OkCondition {
     type Output = RawResponse<'a, N>;
 
     fn is_performed(self, buf: &[u8]) -> bool {
-        buf.ends_with(Self::OK) || buf.ends_with(Self::ERROR) || buf.ends_with(Self::FAIL)
+        Self::find_terminator(buf).is_some()
     }
 
     fn output(self, mut buf: ReadData<'a, N>) -> Self::Output {
-        if buf.ends_with(Self::OK) {
-            buf.subslice(0, buf.len() - Self::OK.len());
-            Ok(buf)
-        } else if buf.ends_with(Self::ERROR) {
-            buf.subslice(0, buf.len() - Self::ERROR.len());
-            Err(buf)
+        let (pos, terminator) = match Self::find_terminator(&buf) {
+            Some(found) => found,
+            None => return Err(Response::new(buf)),
+        };
+        buf.cut(pos, terminator.len());
+        if terminator == Self::OK || terminator == Self::SEND_OK {
+            Ok(Response::new(buf))
         } else {
-            buf.subslice(0, buf.len() - Self::FAIL.len());
-            Err(buf)
+            Err(Response::new(buf))
         }
     }
 }
 
+/// Extracts the successful response of the command that should never fail.
+pub(crate) trait ExpectOk<'a, const N: usize> {
+    /// Returns the `CommandFailed` error if the module rejected the command.
+    ///
+    /// The responses are runtime outcomes, so they never panic regardless of the `no-panic`
+    /// feature, unlike the argument checks.
+    fn expect_ok(self) -> Result<Response<'a, N>>;
+}
+
+impl<'a, const N: usize> ExpectOk<'a, N> for RawResponse<'a, N> {
+    fn expect_ok(self) -> Result<Response<'a, N>> {
+        self.map_err(|_| Error::CommandFailed)
+    }
+}
+
 #[derive(Debug)]
 pub struct WriterPart<Tx> {
     tx: Tx,
+    /// Last sent bytes.
+    trace: TraceRing,
 }
 
 impl<Tx> WriterPart<Tx>
@@ -294,14 +752,15 @@ where
     Tx: serial::Write<u8> + 'static,
 {
     fn write_fmt(&mut self, args: core::fmt::Arguments) -> Result<()> {
-        let writer = &mut self.tx as &mut (dyn serial::Write<u8, Error = Tx::Error> + 'static);
-        writer.write_fmt(args).map_err(|_| Error::WriteBuffer)
+        Write::write_fmt(self, args).map_err(|_| Error::WriteBuffer)
     }
 
     pub(crate) fn write_byte(&mut self, byte: u8) -> nb::Result<(), Error> {
         self.tx
             .write(byte)
-            .map_err(|err| err.map(|_| Error::WriteBuffer))
+            .map_err(|err| err.map(|_| Error::WriteBuffer))?;
+        self.trace.write(byte);
+        Ok(())
     }
 
     fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
@@ -310,6 +769,37 @@ where
         }
         Ok(())
     }
+
+    /// Writes the bytes in a tight loop, only the tail of them is traced.
+    pub(crate) fn write_all(&mut self, bytes: &[u8]) -> Result<()> {
+        for byte in bytes {
+            loop {
+                match self.tx.write(*byte) {
+                    Ok(()) => break,
+                    Err(nb::Error::WouldBlock) => {}
+                    Err(nb::Error::Other(_)) => return Err(Error::WriteBuffer),
+                }
+            }
+        }
+
+        let tail = bytes.len().saturating_sub(TRACE_BUFFER_LEN);
+        self.trace.extend_from_slice(&bytes[tail..]);
+        Ok(())
+    }
+
+    /// Blocks until all the written bytes have been transmitted.
+    pub(crate) fn flush(&mut self) -> Result<()> {
+        nb::block!(self.tx.flush()).map_err(|_| Error::WriteBuffer)
+    }
+}
+
+impl<Tx> Write for WriterPart<Tx>
+where
+    Tx: serial::Write<u8> + 'static,
+{
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.write_bytes(s.as_bytes()).map_err(|_| core::fmt::Error)
+    }
 }
 
 mod private {
@@ -0,0 +1,81 @@
+//! [`SimpleClock`] implementation over the RTIC monotonic timers.
+
+use core::marker::PhantomData;
+
+use rtic_monotonics::Monotonic;
+use simple_clock::SimpleClock;
+
+/// Instant of the monotonic timer that can be converted to microseconds.
+pub trait MicrosInstant: Copy {
+    /// Returns the number of microseconds elapsed since the timer has been started.
+    fn to_micros(self) -> u64;
+}
+
+/// Converts the timer ticks with the `NOM / DENOM` seconds period into microseconds.
+fn ticks_to_micros<const NOM: u32, const DENOM: u32>(ticks: u64) -> u64 {
+    (u128::from(ticks) * u128::from(NOM) * 1_000_000 / u128::from(DENOM)) as u64
+}
+
+impl<const NOM: u32, const DENOM: u32> MicrosInstant for fugit::Instant<u32, NOM, DENOM> {
+    fn to_micros(self) -> u64 {
+        ticks_to_micros::<NOM, DENOM>(self.ticks().into())
+    }
+}
+
+impl<const NOM: u32, const DENOM: u32> MicrosInstant for fugit::Instant<u64, NOM, DENOM> {
+    fn to_micros(self) -> u64 {
+        ticks_to_micros::<NOM, DENOM>(self.ticks())
+    }
+}
+
+/// Clock that reads the time from the RTIC monotonic timer `M`.
+///
+/// The timer should be started before the clock is used:
+///
+/// ```ignore
+/// systick_monotonic!(Mono, 1_000);
+/// Mono::start(cx.core.SYST, SYSCLK_HZ);
+/// let module = Module::new(rx, tx, MonotonicClock::<Mono>::new())?;
+/// ```
+///
+/// # Notes
+///
+/// The time of the timers with 32-bit instants wraps around on the timer overflow.
+pub struct MonotonicClock<M>(PhantomData<M>);
+
+impl<M> MonotonicClock<M> {
+    /// Creates a new clock over the monotonic timer.
+    pub const fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<M> Default for MonotonicClock<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M> Clone for MonotonicClock<M> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<M> Copy for MonotonicClock<M> {}
+
+impl<M> core::fmt::Debug for MonotonicClock<M> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("MonotonicClock")
+    }
+}
+
+impl<M> SimpleClock for MonotonicClock<M>
+where
+    M: Monotonic,
+    M::Instant: MicrosInstant,
+{
+    fn now_us(&self) -> u64 {
+        M::now().to_micros()
+    }
+}
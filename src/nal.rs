@@ -0,0 +1,526 @@
+//! Network stack traits of the `embedded-nal` crate.
+
+use core::net;
+
+use embedded_hal::serial;
+use embedded_nal::{
+    TcpClientStack, TcpError, TcpErrorKind, TcpFullStack, UdpClientStack, UdpFullStack,
+};
+use heapless::Vec;
+use simple_clock::SimpleClock;
+
+use crate::{
+    buffered::SEND_MAX_LEN,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    network_session::{link_mask, MAX_LINKS},
+    Error, LinkEvent, LinkQueues, NetworkEvent, NetworkSession,
+};
+
+impl TcpError for Error {
+    fn kind(&self) -> TcpErrorKind {
+        match self {
+            Error::ConnectionClosed => TcpErrorKind::PipeClosed,
+            _ => TcpErrorKind::Other,
+        }
+    }
+}
+
+/// TCP socket of the [`NalStack`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct TcpSocket {
+    state: TcpState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TcpState {
+    Unconnected,
+    Bound(u16),
+    Listening,
+    Connected(usize),
+}
+
+impl TcpSocket {
+    /// Returns the identifier of the link used by the connected socket.
+    pub fn link_id(&self) -> Option<usize> {
+        match self.state {
+            TcpState::Connected(link_id) => Some(link_id),
+            _ => None,
+        }
+    }
+}
+
+/// UDP socket of the [`NalStack`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct UdpSocket {
+    state: UdpState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UdpState {
+    Unconnected,
+    /// The socket sends the datagrams to the fixed remote address.
+    Connected(usize, SocketAddr),
+    /// The socket receives the datagrams on the local port.
+    Bound(usize),
+}
+
+impl UdpSocket {
+    /// Returns the identifier of the link used by the open socket.
+    pub fn link_id(&self) -> Option<usize> {
+        match self.state {
+            UdpState::Connected(link_id, _) | UdpState::Bound(link_id) => Some(link_id),
+            UdpState::Unconnected => None,
+        }
+    }
+}
+
+/// Received datagram that waits for the `receive` call.
+#[derive(Debug)]
+struct Datagram<const Q: usize> {
+    data: Vec<u8, Q>,
+    remote: Option<SocketAddr>,
+}
+
+/// Adapter of the network session to the `embedded-nal` stack traits, so the generic
+/// protocol crates can use the module links.
+///
+/// The module doesn't bind the links to the sockets, so the free link is chosen by the
+/// connecting, and the incoming connections are accepted on the links assigned by the module.
+/// The received data is moved to the receive queues of `Q` bytes for each link, see
+/// [`LinkQueues`] for details. The UDP sockets keep one datagram of up to `Q` bytes, the
+/// longer datagrams are truncated, and the datagrams received while the previous one has
+/// not been read yet are dropped. The queued data is lost when the stack is dropped.
+///
+/// # Notes
+///
+/// The module supports only one TCP server, and the stack operations block until the
+/// module responds like the other session methods. The source addresses of the received
+/// datagrams are reported only if the firmware supports the `AT+CIPDINFO` command, and
+/// the datagrams larger than the reader buffer are split.
+#[derive(Debug)]
+pub struct NalStack<'a, Rx, Tx, C, const N: usize, const Q: usize>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    session: &'a mut NetworkSession<Rx, Tx, C, N>,
+    queues: LinkQueues<Q>,
+    datagrams: [Option<Datagram<Q>>; MAX_LINKS],
+    /// Bitmask of the links used by the UDP sockets.
+    udp: u8,
+    /// Bitmask of the links used by the sockets.
+    open: u8,
+    /// Bitmask of the links that have been closed by the peer.
+    closed: u8,
+    /// Bitmask of the connections that have been accepted by the server but not taken
+    /// by the `accept` call yet.
+    incoming: u8,
+    /// The TCP server has been started by one of the sockets.
+    listening: bool,
+}
+
+impl<'a, Rx, Tx, C, const N: usize, const Q: usize> NalStack<'a, Rx, Tx, C, N, Q>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Creates a new stack over the given session.
+    pub fn new(session: &'a mut NetworkSession<Rx, Tx, C, N>) -> Self {
+        Self {
+            session,
+            queues: LinkQueues::new(),
+            datagrams: Default::default(),
+            udp: 0,
+            open: 0,
+            closed: 0,
+            incoming: 0,
+            listening: false,
+        }
+    }
+
+    /// Returns the underlying session.
+    pub fn session(&mut self) -> &mut NetworkSession<Rx, Tx, C, N> {
+        self.session
+    }
+
+    /// Handles all the pending network events.
+    fn poll_events(&mut self) -> crate::Result<()> {
+        loop {
+            let event = match self.session.poll_network_event() {
+                Ok(NetworkEvent::DataAvailable {
+                    link_id,
+                    remote,
+                    data,
+                    ..
+                }) if self.udp & link_mask(link_id) != 0 => {
+                    let slot = &mut self.datagrams[usize::from(link_id)];
+                    // The socket buffer is full, the datagram is dropped, so the data of
+                    // the other links doesn't wait until the socket is read.
+                    if slot.is_some() {
+                        data.discard();
+                        continue;
+                    }
+
+                    let len = core::cmp::min(data.len(), Q);
+                    *slot = Some(Datagram {
+                        // The length is limited by the capacity.
+                        data: Vec::from_slice(&data[..len]).unwrap_or_default(),
+                        remote,
+                    });
+                    // The next datagram of the link is kept until the next poll, the
+                    // received one may be read before it.
+                    return Ok(());
+                }
+                Ok(event) => self.queues.handle(event),
+                Err(err) => Err(err),
+            };
+            let event = match event {
+                Ok(event) => event,
+                Err(nb::Error::WouldBlock) => return Ok(()),
+                Err(nb::Error::Other(err)) => return Err(err),
+            };
+
+            match event {
+                LinkEvent::Connected { link_id } => {
+                    let mask = link_mask(link_id);
+                    // The links opened by the `connect` are already known.
+                    if self.open & mask == 0 {
+                        self.incoming |= mask;
+                        self.closed &= !mask;
+                    }
+                }
+                LinkEvent::Closed { link_id, .. } => {
+                    let mask = link_mask(link_id);
+                    self.incoming &= !mask;
+                    if self.open & mask != 0 {
+                        self.closed |= mask;
+                    }
+                }
+                LinkEvent::ModuleRebooted => {
+                    self.closed = self.open;
+                    self.datagrams = Default::default();
+                    self.incoming = 0;
+                    self.listening = false;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the lowest link that is neither used by the sockets nor accepted by the server.
+    fn free_link(&self) -> Option<usize> {
+        (0..MAX_LINKS).find(|&link_id| (self.open | self.incoming) & link_mask(link_id as u16) == 0)
+    }
+
+    fn release_link(&mut self, link_id: usize) {
+        let mask = link_mask(link_id as u16);
+        self.open &= !mask;
+        self.closed &= !mask;
+        self.udp &= !mask;
+        self.queues.clear(link_id);
+        self.datagrams[link_id] = None;
+    }
+
+    /// Takes the free link for the UDP socket.
+    fn open_udp_link(&mut self) -> crate::Result<usize> {
+        // The incoming connections occupy the links as well.
+        self.poll_events()?;
+        let link_id = self.free_link().ok_or(Error::BufferFull)?;
+        self.release_link(link_id);
+        Ok(link_id)
+    }
+
+    fn mark_udp_link(&mut self, link_id: usize) {
+        self.open |= link_mask(link_id as u16);
+        self.udp |= link_mask(link_id as u16);
+    }
+}
+
+impl<Rx, Tx, C, const N: usize, const Q: usize> TcpClientStack for NalStack<'_, Rx, Tx, C, N, Q>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    type TcpSocket = TcpSocket;
+    type Error = Error;
+
+    fn socket(&mut self) -> crate::Result<TcpSocket> {
+        Ok(TcpSocket {
+            state: TcpState::Unconnected,
+        })
+    }
+
+    /// Establishes the connection on the free link, returns the [`Error::BufferFull`] error
+    /// if all the links are in use, or the [`Error::CommandFailed`] error if the connection
+    /// has been refused.
+    fn connect(
+        &mut self,
+        socket: &mut TcpSocket,
+        remote: net::SocketAddr,
+    ) -> nb::Result<(), Error> {
+        if socket.state != TcpState::Unconnected {
+            return Err(nb::Error::Other(Error::InvalidArgument));
+        }
+        // The incoming connections occupy the links as well.
+        self.poll_events()?;
+        let link_id = self.free_link().ok_or(Error::BufferFull)?;
+
+        self.session.connect(link_id, from_core_addr(remote))?;
+        self.open |= link_mask(link_id as u16);
+        self.closed &= !link_mask(link_id as u16);
+        self.queues.clear(link_id);
+        socket.state = TcpState::Connected(link_id);
+        Ok(())
+    }
+
+    fn send(&mut self, socket: &mut TcpSocket, buffer: &[u8]) -> nb::Result<usize, Error> {
+        let link_id = socket.link_id().ok_or(Error::InvalidArgument)?;
+        self.poll_events()?;
+        if self.closed & link_mask(link_id as u16) != 0 {
+            return Err(nb::Error::Other(Error::ConnectionClosed));
+        }
+
+        let len = core::cmp::min(buffer.len(), SEND_MAX_LEN);
+        self.session.send(link_id, buffer[..len].iter().copied())?;
+        Ok(len)
+    }
+
+    /// Reads the queued data, returns the [`Error::ConnectionClosed`] error once all
+    /// the data of the closed connection has been read.
+    fn receive(&mut self, socket: &mut TcpSocket, buffer: &mut [u8]) -> nb::Result<usize, Error> {
+        let link_id = socket.link_id().ok_or(Error::InvalidArgument)?;
+        self.poll_events()?;
+
+        match self.queues.read(link_id, buffer) {
+            0 if self.closed & link_mask(link_id as u16) != 0 => {
+                Err(nb::Error::Other(Error::ConnectionClosed))
+            }
+            0 => Err(nb::Error::WouldBlock),
+            len => Ok(len),
+        }
+    }
+
+    fn close(&mut self, socket: TcpSocket) -> crate::Result<()> {
+        match socket.state {
+            TcpState::Connected(link_id) => {
+                self.poll_events()?;
+                let is_closed = self.closed & link_mask(link_id as u16) != 0;
+                self.release_link(link_id);
+                if !is_closed {
+                    self.session.close(link_id)?;
+                }
+            }
+            TcpState::Listening => {
+                self.session
+                    .module
+                    .send_at_command("AT+CIPSERVER=0")?
+                    .map_err(|_| Error::CommandFailed)?;
+                self.listening = false;
+            }
+            TcpState::Unconnected | TcpState::Bound(_) => {}
+        }
+        Ok(())
+    }
+}
+
+impl<Rx, Tx, C, const N: usize, const Q: usize> TcpFullStack for NalStack<'_, Rx, Tx, C, N, Q>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    fn bind(&mut self, socket: &mut TcpSocket, local_port: u16) -> crate::Result<()> {
+        if socket.state != TcpState::Unconnected {
+            return Err(Error::InvalidArgument);
+        }
+        socket.state = TcpState::Bound(local_port);
+        Ok(())
+    }
+
+    /// Starts the TCP server, returns the [`Error::Unsupported`] error if the server has
+    /// been already started by the other socket.
+    fn listen(&mut self, socket: &mut TcpSocket) -> crate::Result<()> {
+        let port = match socket.state {
+            TcpState::Bound(port) => port,
+            _ => return Err(Error::InvalidArgument),
+        };
+        if self.listening {
+            return Err(Error::Unsupported);
+        }
+
+        self.session.listen(port)?;
+        self.listening = true;
+        socket.state = TcpState::Listening;
+        Ok(())
+    }
+
+    fn accept(
+        &mut self,
+        socket: &mut TcpSocket,
+    ) -> nb::Result<(TcpSocket, net::SocketAddr), Error> {
+        if socket.state != TcpState::Listening {
+            return Err(nb::Error::Other(Error::InvalidArgument));
+        }
+        self.poll_events()?;
+
+        let link_id = (0..MAX_LINKS)
+            .find(|&link_id| self.incoming & link_mask(link_id as u16) != 0)
+            .ok_or(nb::Error::WouldBlock)?;
+        self.incoming &= !link_mask(link_id as u16);
+
+        // The connection notification doesn't contain the peer address.
+        let remote = self
+            .session
+            .status()?
+            .into_iter()
+            .find(|status| usize::from(status.link_id) == link_id)
+            .ok_or(Error::ConnectionClosed)?
+            .remote;
+
+        self.open |= link_mask(link_id as u16);
+        let accepted = TcpSocket {
+            state: TcpState::Connected(link_id),
+        };
+        Ok((accepted, to_core_addr(remote)))
+    }
+}
+
+impl<Rx, Tx, C, const N: usize, const Q: usize> UdpClientStack for NalStack<'_, Rx, Tx, C, N, Q>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    type UdpSocket = UdpSocket;
+    type Error = Error;
+
+    fn socket(&mut self) -> crate::Result<UdpSocket> {
+        Ok(UdpSocket {
+            state: UdpState::Unconnected,
+        })
+    }
+
+    /// Opens the UDP socket with the fixed remote address on the free link, returns
+    /// the [`Error::BufferFull`] error if all the links are in use, or
+    /// the [`Error::CommandFailed`] error if the module has rejected the socket.
+    fn connect(&mut self, socket: &mut UdpSocket, remote: net::SocketAddr) -> crate::Result<()> {
+        if socket.state != UdpState::Unconnected {
+            return Err(Error::InvalidArgument);
+        }
+        let link_id = self.open_udp_link()?;
+
+        let remote = from_core_addr(remote);
+        self.session.connect_with(link_id, "UDP", remote)?;
+        self.mark_udp_link(link_id);
+        socket.state = UdpState::Connected(link_id, remote);
+        Ok(())
+    }
+
+    /// Sends the datagram to the remote address of the connected socket, or to the sender
+    /// of the last received datagram for the bound one.
+    fn send(&mut self, socket: &mut UdpSocket, buffer: &[u8]) -> nb::Result<(), Error> {
+        let link_id = socket.link_id().ok_or(Error::InvalidArgument)?;
+        // The datagram cannot be split.
+        if buffer.len() > SEND_MAX_LEN {
+            return Err(nb::Error::Other(Error::BufferFull));
+        }
+
+        self.session.send(link_id, buffer.iter().copied())?;
+        Ok(())
+    }
+
+    /// Reads the received datagram, the source address of the connected socket is its
+    /// remote address if the firmware doesn't report it.
+    fn receive(
+        &mut self,
+        socket: &mut UdpSocket,
+        buffer: &mut [u8],
+    ) -> nb::Result<(usize, net::SocketAddr), Error> {
+        let link_id = socket.link_id().ok_or(Error::InvalidArgument)?;
+        self.poll_events()?;
+
+        let datagram = match self.datagrams[link_id].take() {
+            Some(datagram) => datagram,
+            None if self.closed & link_mask(link_id as u16) != 0 => {
+                return Err(nb::Error::Other(Error::ConnectionClosed))
+            }
+            None => return Err(nb::Error::WouldBlock),
+        };
+
+        let remote = match (datagram.remote, socket.state) {
+            (Some(remote), _) | (None, UdpState::Connected(_, remote)) => remote,
+            (None, _) => SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 0),
+        };
+        let len = core::cmp::min(buffer.len(), datagram.data.len());
+        buffer[..len].copy_from_slice(&datagram.data[..len]);
+        Ok((len, to_core_addr(remote)))
+    }
+
+    fn close(&mut self, socket: UdpSocket) -> crate::Result<()> {
+        if let Some(link_id) = socket.link_id() {
+            self.poll_events()?;
+            let is_closed = self.closed & link_mask(link_id as u16) != 0;
+            self.release_link(link_id);
+            if !is_closed {
+                self.session.close(link_id)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<Rx, Tx, C, const N: usize, const Q: usize> UdpFullStack for NalStack<'_, Rx, Tx, C, N, Q>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Opens the UDP socket that receives the datagrams on the given local port.
+    fn bind(&mut self, socket: &mut UdpSocket, local_port: u16) -> crate::Result<()> {
+        if socket.state != UdpState::Unconnected {
+            return Err(Error::InvalidArgument);
+        }
+        let link_id = self.open_udp_link()?;
+
+        self.session.listen_udp(link_id, local_port)?;
+        self.mark_udp_link(link_id);
+        socket.state = UdpState::Bound(link_id);
+        Ok(())
+    }
+
+    fn send_to(
+        &mut self,
+        socket: &mut UdpSocket,
+        remote: net::SocketAddr,
+        buffer: &[u8],
+    ) -> nb::Result<(), Error> {
+        let link_id = socket.link_id().ok_or(Error::InvalidArgument)?;
+        // The datagram cannot be split.
+        if buffer.len() > SEND_MAX_LEN {
+            return Err(nb::Error::Other(Error::BufferFull));
+        }
+
+        self.session
+            .send_to(link_id, from_core_addr(remote), buffer.iter().copied())?;
+        Ok(())
+    }
+}
+
+fn from_core_addr(addr: net::SocketAddr) -> SocketAddr {
+    let ip = match addr.ip() {
+        net::IpAddr::V4(ip) => IpAddr::V4(Ipv4Addr::from(ip.octets())),
+        net::IpAddr::V6(ip) => IpAddr::V6(Ipv6Addr::from(ip.octets())),
+    };
+    SocketAddr::new(ip, addr.port())
+}
+
+fn to_core_addr(addr: SocketAddr) -> net::SocketAddr {
+    let ip = match addr.ip() {
+        IpAddr::V4(ip) => net::IpAddr::V4(net::Ipv4Addr::from(ip.octets())),
+        IpAddr::V6(ip) => net::IpAddr::V6(net::Ipv6Addr::from(ip.octets())),
+    };
+    net::SocketAddr::new(ip, addr.port())
+}
@@ -1,33 +1,186 @@
-use core::format_args;
+use core::{format_args, time::Duration};
 
 use embedded_hal::serial;
-use heapless::Vec;
+use heapless::{Deque, String, Vec};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer};
 use simple_clock::SimpleClock;
 
 use crate::{
-    module::{CarretCondition, Module, OkCondition},
-    net::{IpAddr, SocketAddr},
-    parser::CommandResponse,
-    reader_part::{ReadData, ReaderPart},
-    Error,
+    buffered::SEND_MAX_LEN,
+    error::ensure,
+    ipv4::{format_ipv4, IPV4_MAX_LEN},
+    module::{
+        find_subsequence, AtCommand, CarretCondition, ExpectOk, Module, OkCondition, RetryPolicy,
+        DEFAULT_BUFFER_LEN,
+    },
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    parser::{parse_mac, CommandResponse, CwjapResponse},
+    reader_part::{truncate_buf, PendingData, ReadData},
+    send_queue::SendQueue,
+    trace::TraceBuffer,
+    Backoff, Capabilities, Error, JoinApConfig, KeepAlive, RebootRecovery, ReconnectPolicy,
+    SSID_MAX_LEN,
 };
 
+/// Maximum number of the simultaneous connections supported by the module.
+pub(crate) const MAX_LINKS: usize = 5;
+
 /// Network session information.
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct SessionInfo {
+    /// Software access point IP address.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_ip_addr"))]
     pub softap_address: Option<IpAddr>,
+    /// Software access point gateway address.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_ip_addr"))]
+    pub softap_gateway: Option<IpAddr>,
+    /// Software access point network mask.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_ip_addr"))]
+    pub softap_netmask: Option<IpAddr>,
+    /// Station IP address.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_ip_addr"))]
     pub listen_address: Option<IpAddr>,
+    /// Station gateway address.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_ip_addr"))]
+    pub listen_gateway: Option<IpAddr>,
+    /// Station network mask.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_ip_addr"))]
+    pub listen_netmask: Option<IpAddr>,
+    /// Channel of the joined access point.
+    pub channel: Option<u8>,
+    /// Signal strength of the joined access point in dBm.
+    pub rssi: Option<i8>,
+}
+
+/// Access point the station is connected to.
+#[derive(Debug, PartialEq, Clone, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ConnectedAp {
+    /// Access point SSID.
+    pub ssid: String<SSID_MAX_LEN>,
+    /// Access point MAC address.
+    pub bssid: [u8; 6],
+    /// WiFi channel.
+    pub channel: u8,
+    /// Signal strength in dBm.
+    pub rssi: i8,
+}
+
+impl ConnectedAp {
+    fn parse(resp: &[u8]) -> Option<Self> {
+        let (_, resp) = CwjapResponse::parse(resp)?;
+        let mut ssid = String::new();
+        ssid.push_str(core::str::from_utf8(resp.ssid).ok()?).ok()?;
+        Some(Self {
+            ssid,
+            bssid: parse_mac(resp.bssid)?,
+            channel: resp.channel,
+            rssi: resp.rssi,
+        })
+    }
+}
+
+/// Serializes an IP address in the human-readable form.
+#[cfg(feature = "serde")]
+fn serialize_ip_addr<S: Serializer>(
+    addr: &Option<IpAddr>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    struct Display<'a>(&'a IpAddr);
+
+    impl Serialize for Display<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(self.0)
+        }
+    }
+
+    addr.as_ref().map(Display).serialize(serializer)
+}
+
+/// Serializes a socket address in the human-readable form.
+#[cfg(feature = "serde")]
+fn serialize_socket_addr<S: Serializer>(
+    addr: &Option<SocketAddr>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    struct Display<'a>(&'a SocketAddr);
+
+    impl Serialize for Display<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(self.0)
+        }
+    }
+
+    addr.as_ref().map(Display).serialize(serializer)
+}
+
+/// Maximum number of the deferred commands.
+const MAX_DEFERRED_COMMANDS: usize = 4;
+/// Maximum length of the deferred AT command.
+const DEFERRED_COMMAND_MAX_LEN: usize = 128;
+
+/// Command that has been enqueued to be executed when the line is free.
+#[derive(Debug)]
+enum DeferredCommand {
+    /// Close the connection with the given link identifier.
+    Close(usize),
+    /// Send the raw AT command.
+    Raw(String<DEFERRED_COMMAND_MAX_LEN>),
+}
+
+/// Response received by the session.
+#[derive(Debug, Clone, Copy)]
+enum Response {
+    /// Message recognized by the event parser.
+    Known(CommandResponse),
+    /// Complete line of the given length unrecognized by the event parser, the line is placed
+    /// at the beginning of the reader buffer.
+    Unknown { len: usize },
+    /// The module has printed the `ready` banner after an unexpected reboot.
+    Rebooted,
 }
 
 /// A session with the typical network operations.
+///
+/// The `N` parameter is the size of the reader buffer of the underlying [`Module`].
 #[derive(Debug)]
-pub struct NetworkSession<Rx, Tx, C, const N: usize>
+pub struct NetworkSession<Rx, Tx, C, const N: usize = DEFAULT_BUFFER_LEN>
 where
     Rx: serial::Read<u8> + 'static,
     Tx: serial::Write<u8> + 'static,
     C: SimpleClock,
 {
-    module: Module<Rx, Tx, C, N>,
+    pub(crate) module: Module<Rx, Tx, C, N>,
+    /// Events that have been received during the command execution.
+    pending_events: Deque<CommandResponse, MAX_LINKS>,
+    /// Bitmask of the links that have been closed by the driver.
+    closed_locally: u8,
+    /// Partially consumed data that remains in the reader buffer.
+    pending_data: Option<PendingData>,
+    /// Response that has been left for the next poll by the filtering poll.
+    pending_response: Option<Response>,
+    /// Keep the unconsumed received data instead of discarding it.
+    retain_unconsumed: bool,
+    /// Commands to be executed when the line is free.
+    deferred_commands: Deque<DeferredCommand, MAX_DEFERRED_COMMANDS>,
+    /// Application-level keepalive service.
+    keepalive: Option<KeepAlive>,
+    /// Time of the last incoming activity on the each open link.
+    last_activity: [Option<u64>; MAX_LINKS],
+    /// Links that are idle longer than this duration are closed automatically.
+    idle_timeout: Option<u64>,
+    /// Function that receives the lines unrecognized by the event parser.
+    unknown_line_handler: Option<fn(&[u8])>,
+    /// Data to be sent when the line is free.
+    send_queue: SendQueue,
+    /// Configuration to be restored after an unexpected module reboot.
+    pub(crate) reboot_recovery: Option<RebootRecovery>,
+    /// Automatic reconnection to the access point.
+    pub(crate) reconnect_policy: Option<ReconnectPolicy>,
+    /// The module buffers the received data until it is pulled.
+    pub(crate) passive_receive: bool,
 }
 
 impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
@@ -37,7 +190,23 @@ where
     C: SimpleClock,
 {
     pub(crate) fn new(module: Module<Rx, Tx, C, N>) -> Self {
-        Self { module }
+        Self {
+            module,
+            pending_events: Deque::new(),
+            closed_locally: 0,
+            pending_data: None,
+            pending_response: None,
+            retain_unconsumed: false,
+            deferred_commands: Deque::new(),
+            keepalive: None,
+            last_activity: [None; MAX_LINKS],
+            idle_timeout: None,
+            unknown_line_handler: None,
+            send_queue: SendQueue::default(),
+            reboot_recovery: None,
+            reconnect_policy: None,
+            passive_receive: false,
+        }
     }
 
     /// Begins to listen to the incoming TCP connections on the specified port.
@@ -45,31 +214,592 @@ where
         // Setup a TCP server.
         self.module
             .send_at_command(format_args!("AT+CIPSERVER=1,{}", port))?
-            .expect("Malformed command");
+            .expect_ok()?;
 
         Ok(())
     }
 
+    /// Limits the number of the simultaneous incoming TCP connections.
+    ///
+    /// # Notes
+    ///
+    /// This method should be called before the [listen](Self::listen), the allowed values
+    /// are in the range from 1 to 5, otherwise the [`Error::InvalidArgument`] error
+    /// is returned.
+    pub fn set_max_connections(&mut self, count: u8) -> crate::Result<()> {
+        if !(1..=MAX_LINKS as u8).contains(&count) {
+            return Err(Error::InvalidArgument);
+        }
+
+        self.module
+            .send_at_command(format_args!("AT+CIPSERVERMAXCONN={}", count))?
+            .map_err(|_| Error::CommandFailed)?;
+
+        Ok(())
+    }
+
+    /// Enables or disables retention of the unconsumed received data.
+    ///
+    /// If enabled, the unconsumed part of the [`NetworkEvent::DataAvailable`] data will be
+    /// returned as a new event by the next poll instead of being discarded. Use the
+    /// [`ReadData::discard`] method to drop such data explicitly.
+    ///
+    /// The data skipped by the [`wait_for`](Self::wait_for) method or by the buffered reader
+    /// of the other link is discarded regardless of this setting.
+    pub fn set_retain_unconsumed(&mut self, retain: bool) {
+        self.retain_unconsumed = retain;
+    }
+
+    /// Sets the function that receives the complete lines unrecognized by the event parser,
+    /// e.g. vendor-specific notifications like `+TIME_UPDATED`.
+    ///
+    /// The handler is called before such line is returned as the [`NetworkEvent::Unknown`].
+    pub fn set_unknown_line_handler(&mut self, handler: Option<fn(&[u8])>) {
+        self.unknown_line_handler = handler;
+    }
+
     /// Establishes a TCP connection with the specified IP address, link identifier will
     /// be associated with the given IP address.
     /// Then it will be possible to [send](Self::send) data using this link ID.
     pub fn connect(&mut self, link_id: usize, address: SocketAddr) -> crate::Result<()> {
+        self.connect_with(link_id, "TCP", address)
+    }
+
+    /// Establishes a connection of the given type, e.g. `TCP` or `SSL`, with the specified
+    /// IP address.
+    pub(crate) fn connect_with(
+        &mut self,
+        link_id: usize,
+        kind: &str,
+        address: SocketAddr,
+    ) -> crate::Result<()> {
+        let mut ip_buf = [0; IPV4_MAX_LEN];
+        match address.ip() {
+            IpAddr::V4(ip) => {
+                self.start_connection(link_id, kind, &format_ipv4(ip, &mut ip_buf), address.port())
+            }
+            IpAddr::V6(ip) => self.start_connection(link_id, kind, &ip, address.port()),
+        }
+    }
+
+    pub(crate) fn start_connection(
+        &mut self,
+        link_id: usize,
+        kind: &str,
+        ip: &dyn core::fmt::Display,
+        port: u16,
+    ) -> crate::Result<()> {
         self.module
             .send_at_command(format_args!(
                 "AT+CIPSTART={},\"{}\",\"{}\",{}",
+                link_id, kind, ip, port,
+            ))?
+            .map_err(|_| Error::CommandFailed)?;
+
+        Ok(())
+    }
+
+    /// Opens a UDP socket with the given link identifier that receives datagrams on the given
+    /// local port.
+    ///
+    /// The data [sent](Self::send) via this link goes to the sender of the last received
+    /// datagram.
+    pub fn listen_udp(&mut self, link_id: usize, port: u16) -> crate::Result<()> {
+        ensure!(
+            link_id < MAX_LINKS,
+            "Link identifier should be less than the maximum number of connections"
+        );
+
+        // The remote address is updated by each received datagram in the mode `2`.
+        self.module
+            .send_at_command(format_args!(
+                "AT+CIPSTART={},\"UDP\",\"0.0.0.0\",{},{},2",
+                link_id, port, port,
+            ))?
+            .map_err(|_| Error::CommandFailed)?;
+
+        Ok(())
+    }
+
+    /// Opens a UDP socket with the given link identifier that joins the multicast group
+    /// with the given address and port.
+    ///
+    /// The data [sent](Self::send) via this link goes to the whole group.
+    pub fn join_multicast(
+        &mut self,
+        link_id: usize,
+        group: Ipv4Addr,
+        port: u16,
+    ) -> crate::Result<()> {
+        ensure!(
+            link_id < MAX_LINKS,
+            "Link identifier should be less than the maximum number of connections"
+        );
+        ensure!(
+            group.is_multicast(),
+            "The group address should be a multicast one"
+        );
+
+        let mut ip_buf = [0; IPV4_MAX_LEN];
+        self.module
+            .send_at_command(format_args!(
+                "AT+CIPSTART={},\"UDP\",\"{}\",{},{},0",
                 link_id,
-                "TCP",
-                address.ip(),
-                address.port(),
+                format_ipv4(group, &mut ip_buf),
+                port,
+                port,
             ))?
-            .expect("Malformed command");
+            .map_err(|_| Error::CommandFailed)?;
+
+        Ok(())
+    }
+
+    /// Closes the TCP connection with the given link identifier.
+    ///
+    /// The corresponding [`NetworkEvent::Closed`] event will be marked as closed locally.
+    pub fn close(&mut self, link_id: usize) -> crate::Result<()> {
+        ensure!(
+            link_id < MAX_LINKS,
+            "Link identifier should be less than the maximum number of connections"
+        );
+
+        // The `n,CLOSED` notification that precedes the command response is kept in the reader
+        // buffer, so it will be returned by the next poll.
+        self.module
+            .send_at_command(format_args!("AT+CIPCLOSE={}", link_id))?
+            .map_err(|_| Error::CommandFailed)?;
+
+        self.closed_locally |= link_mask(link_id as u16);
+        Ok(())
+    }
+
+    /// Enqueues closing the TCP connection with the given link identifier.
+    ///
+    /// See [`defer_command`](Self::defer_command) for details.
+    pub fn defer_close(&mut self, link_id: usize) -> crate::Result<()> {
+        ensure!(
+            link_id < MAX_LINKS,
+            "Link identifier should be less than the maximum number of connections"
+        );
+
+        self.deferred_commands
+            .push_back(DeferredCommand::Close(link_id))
+            .map_err(|_| Error::BufferFull)
+    }
+
+    /// Enqueues the AT command to be executed when the line is free.
+    ///
+    /// The deferred commands are executed in order by the [`service`](Self::service) method
+    /// once all the received bytes have been handled, so a partially received frame is never
+    /// split by the command response, or by the [`run_deferred`](Self::run_deferred) method.
+    pub fn defer_command<T: AtCommand>(&mut self, cmd: T) -> crate::Result<()> {
+        let mut raw = String::new();
+        cmd.write_to(&mut raw).map_err(|_| Error::BufferFull)?;
+
+        self.deferred_commands
+            .push_back(DeferredCommand::Raw(raw))
+            .map_err(|_| Error::BufferFull)
+    }
+
+    /// Executes all the deferred commands.
+    ///
+    /// If a command fails, it is discarded and the rest of the commands remain in the queue.
+    pub fn run_deferred(&mut self) -> crate::Result<()> {
+        while let Some(cmd) = self.deferred_commands.pop_front() {
+            match cmd {
+                DeferredCommand::Close(link_id) => self.close(link_id)?,
+                DeferredCommand::Raw(raw) => {
+                    self.module
+                        .send_at_command(raw.as_str())?
+                        .map_err(|_| Error::CommandFailed)?;
+                }
+            }
+        }
+        Ok(())
+    }
 
+    /// Enqueues the data to be sent via the link with the given identifier when the line
+    /// is free.
+    ///
+    /// The data is split into segments of the [`QUEUED_SEGMENT_MAX_LEN`] bytes, either all
+    /// of them are enqueued or the `BufferFull` error is returned. The queued data is sent
+    /// by the [`service`](Self::service) or [`send_queued`](Self::send_queued) methods.
+    ///
+    /// [`QUEUED_SEGMENT_MAX_LEN`]: crate::QUEUED_SEGMENT_MAX_LEN
+    pub fn enqueue_send(&mut self, link_id: usize, bytes: &[u8]) -> crate::Result<()> {
+        ensure!(
+            link_id < MAX_LINKS,
+            "Link identifier should be less than the maximum number of connections"
+        );
+
+        self.send_queue.push(link_id, bytes)
+    }
+
+    /// Marks the link as high-priority, or resets the priority if `None`.
+    ///
+    /// The queued segments of the high-priority link, e.g. a control channel, are sent
+    /// before the segments of the other links regardless of the order of enqueueing.
+    pub fn set_priority_link(&mut self, link_id: Option<usize>) {
+        self.send_queue.set_priority_link(link_id);
+    }
+
+    /// Sends all the queued data, the segments of the high-priority link go first.
+    ///
+    /// If sending fails, the segment is discarded and the rest of the segments remain
+    /// in the queue.
+    pub fn send_queued(&mut self) -> crate::Result<()> {
+        while let Some(segment) = self.send_queue.pop() {
+            self.send(segment.link_id, segment.data.iter().copied())?;
+        }
+        Ok(())
+    }
+
+    /// Sets the application-level keepalive service, or disables it if `None`.
+    ///
+    /// The probes are sent by the [`service`](Self::service) method, use the
+    /// [`watch_link`](Self::watch_link) method to select the links to be probed.
+    pub fn set_keepalive(&mut self, keepalive: Option<KeepAlive>) {
+        self.keepalive = keepalive;
+    }
+
+    /// Enables or disables the keepalive probing of the link with the given identifier.
+    ///
+    /// The link stops being watched after its closing.
+    pub fn watch_link(&mut self, link_id: usize, enable: bool) {
+        ensure!(
+            link_id < MAX_LINKS,
+            "Link identifier should be less than the maximum number of connections" => return
+        );
+
+        let now = self.clock().now_us();
+        if let Some(keepalive) = self.keepalive.as_mut() {
+            keepalive.watch(link_id, now, enable);
+        }
+    }
+
+    /// Returns `true` if the watched link has not responded to the keepalive probe.
+    ///
+    /// Returns `false` for the unknown link identifiers.
+    pub fn is_link_dead(&self, link_id: usize) -> bool {
+        matches!(self.keepalive.as_ref(), Some(keepalive) if keepalive.is_dead(link_id))
+    }
+
+    /// Sends the keepalive probes to the links that have been inactive for too long.
+    ///
+    /// The link that fails to send the probe, e.g. the half-open one, is marked as dead.
+    fn service_keepalive(&mut self) {
+        let now = self.clock().now_us();
+        while let Some((link_id, probe)) = self
+            .keepalive
+            .as_mut()
+            .and_then(|keepalive| Some((keepalive.poll(now)?, keepalive.probe.clone())))
+        {
+            if self.send(link_id, probe.iter().copied()).is_err() {
+                if let Some(keepalive) = self.keepalive.as_mut() {
+                    keepalive.mark_dead(link_id);
+                }
+            }
+        }
+    }
+
+    /// Returns the time elapsed since the last incoming activity on the link, or `None`
+    /// if the link is not open.
+    pub fn link_idle_time(&self, link_id: usize) -> Option<u64> {
+        let last_activity = (*self.last_activity.get(link_id)?)?;
+        Some(self.clock().now_us().saturating_sub(last_activity))
+    }
+
+    /// Closes the links that have not received any data for at least the given duration.
+    ///
+    /// Returns the number of the closed links.
+    pub fn close_idle_links(&mut self, max_idle_us: u64) -> crate::Result<usize> {
+        let mut closed = 0;
+        for link_id in 0..MAX_LINKS {
+            if matches!(self.link_idle_time(link_id), Some(idle) if idle >= max_idle_us) {
+                // Don't try to close the same link again.
+                self.last_activity[link_id] = None;
+                self.close(link_id)?;
+                closed += 1;
+            }
+        }
+        Ok(closed)
+    }
+
+    /// Sets the duration after which the idle links are closed automatically by the
+    /// [`service`](Self::service) method, or disables the automatic closing if `None`.
+    ///
+    /// See also [`close_idle_links`](Self::close_idle_links).
+    pub fn set_idle_timeout(&mut self, us: Option<u64>) {
+        self.idle_timeout = us;
+    }
+
+    /// Runs the housekeeping of the session: executes the deferred commands, sends
+    /// the queued data and the keepalive probes, and closes the idle links.
+    ///
+    /// It should be called periodically from the application loop, the network event
+    /// polling only parses the received data. Each step blocks until the module responds.
+    /// Nothing is done until all the received bytes have been polled, so a partially
+    /// received frame is never split by a command response.
+    ///
+    /// See also [`service_reconnect`](Self::service_reconnect).
+    pub fn service(&mut self) -> crate::Result<()> {
+        if !self.module.reader.buf().is_empty() {
+            return Ok(());
+        }
+
+        self.run_deferred()?;
+        self.send_queued()?;
+        self.service_keepalive();
+        if let Some(idle_timeout) = self.idle_timeout {
+            self.close_idle_links(idle_timeout)?;
+        }
         Ok(())
     }
 
     /// Non-blocking polling to get a new network event.
     pub fn poll_network_event(&mut self) -> nb::Result<NetworkEvent<'_, N>, Error> {
-        let reader = self.reader_mut();
+        let response = self.next_response()?;
+        self.make_event(response)
+    }
+
+    /// Non-blocking polling to get a new network event that doesn't borrow the session.
+    ///
+    /// The received data larger than `M` bytes is split into the several events.
+    pub fn poll_network_event_owned<const M: usize>(
+        &mut self,
+    ) -> nb::Result<OwnedNetworkEvent<M>, Error> {
+        self.poll_network_event().map(NetworkEvent::into_owned)
+    }
+
+    /// Non-blocking polling to get a new network event only if it matches the given predicate.
+    ///
+    /// The event that doesn't match is left in place and will be returned by the next poll,
+    /// so the services that handle their own links, like the [`MdnsResponder`], can share
+    /// the session with the main loop.
+    ///
+    /// [`MdnsResponder`]: crate::MdnsResponder
+    pub fn poll_network_event_if<F>(
+        &mut self,
+        predicate: F,
+    ) -> nb::Result<NetworkEvent<'_, N>, Error>
+    where
+        F: FnOnce(&NetworkEvent<'_, N>) -> bool,
+    {
+        let response = self.next_response()?;
+        // Preparing the response again on the next poll changes nothing.
+        let response = self.prepare(response)?;
+        if predicate(&self.peek_event(response)) {
+            return Ok(self.event(response));
+        }
+
+        self.pending_response = Some(response);
+        Err(nb::Error::WouldBlock)
+    }
+
+    /// Polls for a new network event during the given time in microseconds.
+    ///
+    /// Returns `WouldBlock` if there is no new events during this time.
+    pub fn poll_network_event_for(&mut self, us: u64) -> nb::Result<NetworkEvent<'_, N>, Error> {
+        let started_at = self.clock().now_us();
+        let response = loop {
+            match self.next_response() {
+                Ok(response) => break response,
+                Err(nb::Error::WouldBlock) => {}
+                Err(nb::Error::Other(err)) => return Err(nb::Error::Other(err)),
+            }
+
+            if self.clock().now_us().saturating_sub(started_at) >= us {
+                return Err(nb::Error::WouldBlock);
+            }
+        };
+        self.make_event(response)
+    }
+
+    /// Blocks until an event that matches the given predicate is received.
+    ///
+    /// Non-matching connection events are kept and will be returned by the subsequent polls,
+    /// whereas non-matching received data is discarded.
+    ///
+    /// Returns the [`Error::Timeout`] error if the operation [timeout](Self::timeout) is reached,
+    /// or the [`Error::BufferFull`] error if there are too many non-matching connection events
+    /// to keep, the kept events remain available for the polling.
+    pub fn wait_for<F>(&mut self, predicate: F) -> crate::Result<NetworkEvent<'_, N>>
+    where
+        F: FnMut(&NetworkEvent<'_, N>) -> bool,
+    {
+        let timeout = self.timeout();
+        self.wait_for_within(predicate, timeout)
+    }
+
+    /// Blocks until data is received or the connection is closed on any of the given links.
+    ///
+    /// It allows to serve several connections at once, e.g. a control and a data one,
+    /// without filtering the events manually. The events are kept or discarded in the same
+    /// way as by the [`wait_for`](Self::wait_for) method.
+    ///
+    /// Returns the [`Error::Timeout`] error if there is no such event during the given time
+    /// in microseconds.
+    pub fn wait_any(
+        &mut self,
+        links: &[usize],
+        timeout_us: u64,
+    ) -> crate::Result<NetworkEvent<'_, N>> {
+        let predicate = |event: &NetworkEvent<'_, N>| match event {
+            NetworkEvent::DataAvailable { link_id, .. }
+            | NetworkEvent::DataBuffered { link_id, .. }
+            | NetworkEvent::Closed { link_id, .. } => links.contains(&usize::from(*link_id)),
+            _ => false,
+        };
+        self.wait_for_within(predicate, Some(timeout_us))
+    }
+
+    fn wait_for_within<F>(
+        &mut self,
+        mut predicate: F,
+        timeout: Option<u64>,
+    ) -> crate::Result<NetworkEvent<'_, N>>
+    where
+        F: FnMut(&NetworkEvent<'_, N>) -> bool,
+    {
+        let mut skipped = Deque::new();
+        let response = self.find_response(&mut predicate, &mut skipped, timeout);
+        // Return the skipped events back to the queue.
+        while let Some(response) = skipped.pop_back() {
+            self.pending_events
+                .push_front(response)
+                .map_err(|_| Error::BufferFull)?;
+        }
+
+        Ok(self.event(response?))
+    }
+
+    /// Returns the first prepared response whose event matches the given predicate.
+    ///
+    /// The data of the returned response is kept in the reader buffer, so the event can be
+    /// created from it.
+    fn find_response<F>(
+        &mut self,
+        predicate: &mut F,
+        skipped: &mut Deque<CommandResponse, MAX_LINKS>,
+        timeout: Option<u64>,
+    ) -> crate::Result<Response>
+    where
+        F: FnMut(&NetworkEvent<'_, N>) -> bool,
+    {
+        let started_at = self.clock().now_us();
+        loop {
+            // Stop before the next connection event would be lost.
+            if skipped.is_full() {
+                return Err(Error::BufferFull);
+            }
+
+            match self
+                .next_response()
+                .and_then(|response| self.prepare(response))
+            {
+                // The reboot is returned regardless of the predicate, otherwise the caller
+                // would never learn that the links have been lost. The skipped events
+                // belong to the links lost by the reboot.
+                Ok(response @ Response::Rebooted) => {
+                    skipped.clear();
+                    return Ok(response);
+                }
+                Ok(response) => {
+                    if predicate(&self.peek_event(response)) {
+                        return Ok(response);
+                    }
+                    self.skip_response(response, skipped)?;
+                }
+                Err(nb::Error::WouldBlock) => {}
+                Err(nb::Error::Other(err)) => return Err(err),
+            }
+
+            // A steady stream of the non-matching events should not prolong the waiting.
+            let elapsed = self.clock().now_us().saturating_sub(started_at);
+            if matches!(timeout, Some(timeout) if elapsed >= timeout) {
+                return Err(Error::Timeout);
+            }
+        }
+    }
+
+    /// Discards the prepared response that doesn't match the predicate, the connection
+    /// events are kept to be returned by the subsequent polls.
+    fn skip_response(
+        &mut self,
+        response: Response,
+        skipped: &mut Deque<CommandResponse, MAX_LINKS>,
+    ) -> crate::Result<()> {
+        let buf = self.module.reader.buf_mut();
+        match response {
+            // The received data is discarded even if the retention is enabled, otherwise
+            // it would be returned again and again.
+            Response::Known(CommandResponse::DataAvailable { size, .. }) => {
+                truncate_buf(buf, size as usize);
+            }
+            Response::Known(
+                response @ (CommandResponse::Connected { .. }
+                | CommandResponse::Closed { .. }
+                | CommandResponse::DataBuffered { .. }
+                | CommandResponse::WifiDisconnect
+                | CommandResponse::WifiConnected
+                | CommandResponse::WifiGotIp),
+            ) => {
+                skipped.push_back(response).map_err(|_| Error::BufferFull)?;
+            }
+            // Remove the line together with its terminator.
+            Response::Unknown { len } => truncate_buf(buf, len + 2),
+            // The reboot is never skipped, see `find_response`.
+            Response::Rebooted => {}
+        }
+        Ok(())
+    }
+
+    /// Returns an iterator over the already received network events.
+    ///
+    /// The iteration stops when there are no more events to handle, so it is convenient
+    /// to drain all pending events in each iteration of the main loop.
+    ///
+    /// # Notes
+    ///
+    /// Each event owns a copy of the received data, so it occupies about `N` bytes on the
+    /// stack. Use the [`events_owned`](Self::events_owned) method to limit the event size,
+    /// or the [`poll_network_event`](Self::poll_network_event) method to avoid copying.
+    pub fn events(&mut self) -> Events<'_, Rx, Tx, C, N> {
+        Events { session: self }
+    }
+
+    /// Returns an iterator over the already received network events that own up to `M` bytes
+    /// of the received data.
+    ///
+    /// The received data larger than `M` bytes is split into the several events.
+    pub fn events_owned<const M: usize>(&mut self) -> Events<'_, Rx, Tx, C, N, M> {
+        Events { session: self }
+    }
+
+    fn next_response(&mut self) -> nb::Result<Response, Error> {
+        if let Some(response) = self.pending_response.take() {
+            return Ok(response);
+        }
+        if let Some(data) = self.pending_data.take() {
+            return Ok(Response::Known(CommandResponse::DataAvailable {
+                link_id: data.link_id,
+                size: data.len as u64,
+                remote: data.remote,
+            }));
+        }
+
+        match self.pending_events.pop_front() {
+            Some(response) => Ok(Response::Known(response)),
+            None => self.poll_response(),
+        }
+    }
+
+    /// Non-blocking polling to get a next parsed response from the reader buffer.
+    fn poll_response(&mut self) -> nb::Result<Response, Error> {
+        let handler = self.unknown_line_handler;
+        let Module {
+            reader, in_flight, ..
+        } = &mut self.module;
 
         let response =
             CommandResponse::parse(reader.buf()).map(|(remainder, event)| (remainder.len(), event));
@@ -77,32 +807,200 @@ where
         if let Some((remaining_bytes, response)) = response {
             let pos = reader.buf().len() - remaining_bytes;
             truncate_buf(reader.buf_mut(), pos);
+            return Ok(Response::Known(response));
+        }
 
-            let event = match response {
-                CommandResponse::Connected { link_id } => NetworkEvent::Connected { link_id },
-                CommandResponse::Closed { link_id } => NetworkEvent::Closed { link_id },
-                CommandResponse::DataAvailable { link_id, size } => {
-                    let current_pos = reader.buf().len();
-                    for _ in current_pos..size as usize {
-                        let byte = nb::block!(reader.read_byte())?;
-                        reader.buf_mut().push(byte).map_err(|_| Error::BufferFull)?;
-                    }
-
-                    NetworkEvent::DataAvailable {
-                        link_id,
-                        data: ReadData::new(reader.buf_mut()),
-                    }
+        // The complete line that doesn't look like any known event should be returned as is,
+        // otherwise it would stay in the buffer forever.
+        match find_subsequence(reader.buf(), b"\r\n") {
+            Some(0) => {
+                truncate_buf(reader.buf_mut(), 2);
+                return Err(nb::Error::WouldBlock);
+            }
+            Some(len) if in_flight.resolve_line(&reader.buf()[..len]) => {
+                truncate_buf(reader.buf_mut(), len + 2);
+                return Err(nb::Error::WouldBlock);
+            }
+            // The module reboot wipes out all the links and the network configuration.
+            Some(len) if &reader.buf()[..len] == b"ready" => {
+                truncate_buf(reader.buf_mut(), len + 2);
+                self.handle_reboot();
+                return Ok(Response::Rebooted);
+            }
+            Some(len) => {
+                if let Some(handler) = handler {
+                    handler(&reader.buf()[..len]);
                 }
-                CommandResponse::WifiDisconnect => return Err(nb::Error::WouldBlock),
-            };
-
-            return Ok(event);
+                return Ok(Response::Unknown { len });
+            }
+            None => {}
         }
 
         reader.read_bytes()?;
         Err(nb::Error::WouldBlock)
     }
 
+    fn make_event(&mut self, response: Response) -> nb::Result<NetworkEvent<'_, N>, Error> {
+        let response = self.prepare(response)?;
+        Ok(self.event(response))
+    }
+
+    /// Updates the session state according to the response and receives the rest of the data
+    /// if any.
+    ///
+    /// It should be called exactly once for each response.
+    fn prepare(&mut self, response: Response) -> nb::Result<Response, Error> {
+        match response {
+            Response::Known(CommandResponse::Connected { link_id }) => {
+                self.closed_locally &= !link_mask(link_id);
+                self.touch_link(link_id);
+            }
+            Response::Known(CommandResponse::Closed { link_id }) => self.forget_link(link_id),
+            Response::Known(CommandResponse::DataBuffered { link_id, .. }) => {
+                self.touch_link(link_id)
+            }
+            Response::Known(CommandResponse::DataAvailable { link_id, size, .. }) => {
+                self.touch_link(link_id);
+                let reader = &mut self.module.reader;
+                let current_pos = reader.buf().len();
+                for _ in current_pos..size as usize {
+                    let byte = nb::block!(reader.read_byte())?;
+                    reader.buf_mut().push(byte).map_err(|_| Error::BufferFull)?;
+                }
+            }
+            Response::Known(
+                ref wifi @ (CommandResponse::WifiDisconnect
+                | CommandResponse::WifiConnected
+                | CommandResponse::WifiGotIp),
+            ) => self.track_wifi_state(wifi),
+            Response::Unknown { .. } | Response::Rebooted => {}
+        }
+        Ok(response)
+    }
+
+    /// Creates an event from the prepared response, the event data is removed from the reader
+    /// buffer after dropping.
+    fn event(&mut self, response: Response) -> NetworkEvent<'_, N> {
+        let response = match response {
+            Response::Known(response) => response,
+            Response::Unknown { len } => {
+                let mut line = ReadData::new(self.module.reader.buf_mut(), 0);
+                // Remove the line together with its terminator.
+                line.cut(len, 2);
+                return NetworkEvent::Unknown(line);
+            }
+            Response::Rebooted => return NetworkEvent::ModuleRebooted,
+        };
+
+        match response {
+            CommandResponse::Connected { link_id } => NetworkEvent::Connected { link_id },
+            // The flag will be reset by the next connection with the same link identifier.
+            CommandResponse::Closed { link_id } => NetworkEvent::Closed {
+                link_id,
+                closed_locally: self.closed_locally & link_mask(link_id) != 0,
+            },
+            CommandResponse::DataAvailable {
+                link_id,
+                size,
+                remote,
+            } => {
+                let data = ReadData::received(
+                    self.module.reader.buf_mut(),
+                    size as usize,
+                    link_id,
+                    remote,
+                    &mut self.pending_data,
+                    self.retain_unconsumed,
+                );
+                NetworkEvent::DataAvailable {
+                    link_id,
+                    remote,
+                    len: data.len(),
+                    data,
+                }
+            }
+            CommandResponse::DataBuffered { link_id, size } => NetworkEvent::DataBuffered {
+                link_id,
+                len: size as usize,
+            },
+            CommandResponse::WifiDisconnect => NetworkEvent::WifiDisconnected,
+            CommandResponse::WifiConnected => NetworkEvent::WifiConnected,
+            CommandResponse::WifiGotIp => NetworkEvent::WifiGotIp,
+        }
+    }
+
+    /// Creates an event from the prepared response without removing its data from the reader
+    /// buffer.
+    fn peek_event(&mut self, response: Response) -> NetworkEvent<'_, N> {
+        match response {
+            Response::Known(CommandResponse::DataAvailable {
+                link_id,
+                size,
+                remote,
+            }) => {
+                let data = ReadData::peek(self.module.reader.buf_mut(), size as usize);
+                NetworkEvent::DataAvailable {
+                    link_id,
+                    remote,
+                    len: data.len(),
+                    data,
+                }
+            }
+            Response::Unknown { len } => {
+                NetworkEvent::Unknown(ReadData::peek(self.module.reader.buf_mut(), len))
+            }
+            _ => self.event(response),
+        }
+    }
+
+    /// Marks the link as active after a new connection or received data.
+    fn touch_link(&mut self, link_id: u16) {
+        let link_id = usize::from(link_id);
+        if link_id >= MAX_LINKS {
+            return;
+        }
+
+        let now = self.module.clock.now_us();
+        self.last_activity[link_id] = Some(now);
+        if let Some(keepalive) = self.keepalive.as_mut() {
+            keepalive.reset(link_id, now);
+        }
+    }
+
+    /// Forgets the links lost by the module reboot.
+    ///
+    /// The configuration is not restored here, since it blocks the polling for a long time,
+    /// the application should call the [`restore_after_reboot`](Self::restore_after_reboot)
+    /// method itself.
+    fn handle_reboot(&mut self) {
+        self.module.reset_at = self.module.clock.now_us();
+        for link_id in 0..MAX_LINKS as u16 {
+            self.forget_link(link_id);
+        }
+        self.closed_locally = 0;
+        self.pending_events.clear();
+        self.pending_data = None;
+        self.pending_response = None;
+        self.deferred_commands.clear();
+        self.module.in_flight.abort();
+        // The module boots in the active receiving mode.
+        self.passive_receive = false;
+    }
+
+    /// Stops tracking the activity of the closed link.
+    fn forget_link(&mut self, link_id: u16) {
+        let link_id = usize::from(link_id);
+        if link_id >= MAX_LINKS {
+            return;
+        }
+
+        self.last_activity[link_id] = None;
+        self.send_queue.remove_link(link_id);
+        if let Some(keepalive) = self.keepalive.as_mut() {
+            keepalive.watch(link_id, 0, false);
+        }
+    }
+
     /// Sends data packet via the TCP socket with the link given identifier.
     ///
     /// # Notes
@@ -114,56 +1012,185 @@ where
     {
         let bytes_len = bytes.len();
         // TODO Implement sending of the whole bytes by splitting them into chunks.
-        assert!(
-            bytes_len < 2048,
+        ensure!(
+            bytes_len <= SEND_MAX_LEN,
             "Total packet size should not be greater than the 2048 bytes"
         );
-        assert!(self.reader().buf().is_empty());
+        if self.module.in_flight.depth > 0 {
+            return self.module.send_pipelined(link_id, bytes);
+        }
 
         self.module
             .write_command_fmt(format_args!("AT+CIPSEND={},{}", link_id, bytes_len))?;
+        self.transmit(bytes)
+    }
+
+    /// Sends the datagram via the UDP socket with the given link identifier to the given
+    /// remote address instead of the link one.
+    ///
+    /// # Notes
+    ///
+    /// No more than 2048 bytes can be sent at a time, the in-flight segments of the
+    /// [pipelining](Self::set_send_pipeline_depth) are confirmed first.
+    pub fn send_to<I>(&mut self, link_id: usize, remote: SocketAddr, bytes: I) -> crate::Result<()>
+    where
+        I: Iterator<Item = u8> + ExactSizeIterator,
+    {
+        let bytes_len = bytes.len();
+        ensure!(
+            bytes_len <= SEND_MAX_LEN,
+            "Total packet size should not be greater than the 2048 bytes"
+        );
+        // The confirmations of the previous segments would be taken for this one.
+        self.module.complete_sends()?;
+
+        let mut ip_buf = [0; IPV4_MAX_LEN];
+        let remote_ip = remote.ip();
+        let ip: &dyn core::fmt::Display = match &remote_ip {
+            IpAddr::V4(ip) => &format_ipv4(*ip, &mut ip_buf),
+            IpAddr::V6(ip) => ip,
+        };
+        self.module.write_command_fmt(format_args!(
+            "AT+CIPSEND={},{},\"{}\",{}",
+            link_id,
+            bytes_len,
+            ip,
+            remote.port()
+        ))?;
+        self.transmit(bytes)
+    }
+
+    /// Writes the data after the prompt of the sent command and waits for the confirmation.
+    fn transmit<I>(&mut self, bytes: I) -> crate::Result<()>
+    where
+        I: Iterator<Item = u8>,
+    {
         self.module.read_until(CarretCondition)?;
 
         for byte in bytes {
             nb::block!(self.module.writer.write_byte(byte))?;
         }
+        self.module.writer.flush()?;
 
+        // The `SEND FAIL` is a runtime outcome, e.g. the peer has gone.
         self.module
             .read_until(OkCondition)?
-            .expect("Malformed command");
+            .map_err(|_| Error::CommandFailed)?;
         Ok(())
     }
 
+    /// Blocks until all the bytes written to the module have been transmitted.
+    ///
+    /// The commands and the sent data are flushed automatically, so this method is only
+    /// needed as an explicit barrier, e.g. before putting the MCU into a sleep mode.
+    pub fn flush(&mut self) -> crate::Result<()> {
+        self.module.writer.flush()
+    }
+
     /// Gets network session information.
     pub fn get_info(&mut self) -> crate::Result<SessionInfo> {
         let info = self.module.get_network_info()?;
+        let ap = self.module.get_ip_config("CIPAP")?.unwrap_or_default();
+        let sta = self.module.get_ip_config("CIPSTA")?.unwrap_or_default();
+        let signal = self.module.get_ap_signal()?;
+
         Ok(SessionInfo {
             softap_address: info.ap_ip,
+            softap_gateway: ap.gateway,
+            softap_netmask: ap.netmask,
             listen_address: info.sta_ip,
+            listen_gateway: sta.gateway,
+            listen_netmask: sta.netmask,
+            channel: signal.map(|(channel, _)| channel),
+            rssi: signal.map(|(_, rssi)| rssi),
         })
     }
 
+    /// Queries the access point the station is connected to, e.g. to check the link quality.
+    ///
+    /// Returns `None` if the station is not connected to any access point.
+    pub fn current_ap(&mut self) -> crate::Result<Option<ConnectedAp>> {
+        let suffix = self.module.dialect().current_suffix();
+        self.module
+            .query(format_args!("AT+CWJAP{}?", suffix), ConnectedAp::parse)
+    }
+
+    /// Returns the last bytes exchanged with the module.
+    ///
+    /// See [`Module::trace_buffer`].
+    pub fn trace_buffer(&self) -> TraceBuffer<'_> {
+        self.module.trace_buffer()
+    }
+
     /// Returns a reference to underlying clock instance.
     pub fn clock(&self) -> &C {
         &self.module.clock
     }
 
-    /// Returns an operations timeout.
+    /// Returns the set of the optional commands that are known to be supported by the module
+    /// firmware.
+    ///
+    /// See [`Module::capabilities`].
+    pub fn capabilities(&self) -> Capabilities {
+        self.module.capabilities()
+    }
+
+    /// Probes all the optional commands that haven't been probed yet.
+    ///
+    /// See [`Module::probe_capabilities`].
+    pub fn probe_capabilities(&mut self) -> crate::Result<Capabilities> {
+        self.module.probe_capabilities()
+    }
+
+    /// Sets the operation timeout in microseconds.
+    ///
+    /// See [`Module::set_timeout`].
+    pub fn set_timeout(&mut self, us: Option<u64>) {
+        self.module.set_timeout(us);
+    }
+
+    /// Sets the operation timeout in milliseconds.
+    pub fn set_timeout_ms(&mut self, ms: Option<u64>) {
+        self.module.set_timeout_ms(ms);
+    }
+
+    /// Sets the operation timeout as a duration.
+    pub fn set_timeout_duration(&mut self, timeout: Option<Duration>) {
+        self.module.set_timeout_duration(timeout);
+    }
+
+    /// Returns the operation timeout in microseconds.
     pub fn timeout(&self) -> Option<u64> {
-        self.module.timeout
+        self.module.timeout()
     }
 
-    fn reader(&self) -> &ReaderPart<Rx, N> {
-        &self.module.reader
+    /// Returns the operation timeout in milliseconds.
+    pub fn timeout_ms(&self) -> Option<u64> {
+        self.module.timeout_ms()
     }
 
-    fn reader_mut(&mut self) -> &mut ReaderPart<Rx, N> {
-        &mut self.module.reader
+    /// Returns the operation timeout as a duration.
+    pub fn timeout_duration(&self) -> Option<Duration> {
+        self.module.timeout_duration()
+    }
+
+    /// Rejoins the access point after the connection loss, the failed attempts are repeated
+    /// according to the given backoff.
+    ///
+    /// The session should be established by the [`JoinApConfig::join`] method.
+    pub fn rejoin(&mut self, config: &JoinApConfig, backoff: &mut Backoff) -> crate::Result<()> {
+        config.join_ap_with_backoff(&mut self.module, backoff)
+    }
+
+    /// Sets the policy of retrying commands that failed due to transient errors.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.module.set_retry_policy(policy);
     }
 }
 
 /// Incoming network event.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub enum NetworkEvent<'a, const N: usize> {
     /// A new peer connected.
     Connected {
@@ -174,30 +1201,190 @@ pub enum NetworkEvent<'a, const N: usize> {
     Closed {
         /// Connection identifier.
         link_id: u16,
+        /// The connection has been closed by the [`NetworkSession::close`] call rather
+        /// than by the peer.
+        closed_locally: bool,
     },
     /// Bytes received from the peer.
     DataAvailable {
         /// Connection identifier.
         link_id: u16,
+        /// Address of the peer, e.g. the sender of the UDP datagram, or `None` if it is not
+        /// reported by the module firmware.
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_socket_addr"))]
+        remote: Option<SocketAddr>,
+        /// Number of the received bytes, it's kept after the data has been consumed or
+        /// dropped.
+        len: usize,
         /// Received data.
         data: ReadData<'a, N>,
     },
+    /// Bytes received from the peer have been buffered by the module in the
+    /// [passive receiving mode](NetworkSession::set_passive_receive), they should be pulled
+    /// by the [`pull_data`](NetworkSession::pull_data) call.
+    DataBuffered {
+        /// Connection identifier.
+        link_id: u16,
+        /// Number of the buffered bytes.
+        len: usize,
+    },
+    /// The module has joined the access point, e.g. after the automatic reconnection.
+    WifiConnected,
+    /// The module has obtained the IP address from the joined access point, the links
+    /// can be opened.
+    WifiGotIp,
+    /// The module has been disconnected from the access point, the uplink is lost until
+    /// the next [`WifiConnected`](Self::WifiConnected) event.
+    WifiDisconnected,
+    /// Complete line that is not recognized as any known event, e.g. a vendor-specific
+    /// notification like `+TIME_UPDATED`, without the line terminator.
+    Unknown(ReadData<'a, N>),
+    /// The module has rebooted unexpectedly, e.g. due to a brownout, all the links have
+    /// been lost.
+    ///
+    /// The [recovery](NetworkSession::set_reboot_recovery) configuration should be restored
+    /// by the [`restore_after_reboot`](NetworkSession::restore_after_reboot) call.
+    ModuleRebooted,
 }
 
-// FIXME: Reduce complexity of this operation.
-fn truncate_buf<const N: usize>(buf: &mut Vec<u8, N>, at: usize) {
-    let buf_len = buf.len();
+/// Returns a bit that corresponds to the given link identifier in the links bitmask.
+pub(crate) fn link_mask(link_id: u16) -> u8 {
+    1_u8.checked_shl(link_id.into()).unwrap_or_default()
+}
 
-    assert!(at <= buf_len);
+impl<'a, const N: usize> NetworkEvent<'a, N> {
+    /// Returns the identifier of the link this event belongs to, or `None` if the event
+    /// is not related to any link.
+    pub fn link_id(&self) -> Option<u16> {
+        match self {
+            NetworkEvent::Connected { link_id }
+            | NetworkEvent::Closed { link_id, .. }
+            | NetworkEvent::DataAvailable { link_id, .. }
+            | NetworkEvent::DataBuffered { link_id, .. } => Some(*link_id),
+            NetworkEvent::WifiConnected
+            | NetworkEvent::WifiGotIp
+            | NetworkEvent::WifiDisconnected
+            | NetworkEvent::Unknown(_)
+            | NetworkEvent::ModuleRebooted => None,
+        }
+    }
 
-    for from in at..buf_len {
-        let to = from - at;
-        buf[to] = buf[from];
+    /// Converts this event into the owned one by copying the received data.
+    ///
+    /// If the received data doesn't fit into the owned event, only the first `M` bytes
+    /// are copied and the rest of the data will be returned by the next poll.
+    pub fn into_owned<const M: usize>(self) -> OwnedNetworkEvent<M> {
+        match self {
+            NetworkEvent::Connected { link_id } => OwnedNetworkEvent::Connected { link_id },
+            NetworkEvent::Closed {
+                link_id,
+                closed_locally,
+            } => OwnedNetworkEvent::Closed {
+                link_id,
+                closed_locally,
+            },
+            NetworkEvent::DataAvailable {
+                link_id,
+                remote,
+                mut data,
+                ..
+            } => {
+                OwnedNetworkEvent::DataAvailable {
+                    link_id,
+                    remote,
+                    // The taken slice is never longer than the vector capacity.
+                    data: Vec::from_slice(data.take(M)).unwrap_or_default(),
+                }
+            }
+            NetworkEvent::DataBuffered { link_id, len } => {
+                OwnedNetworkEvent::DataBuffered { link_id, len }
+            }
+            NetworkEvent::WifiConnected => OwnedNetworkEvent::WifiConnected,
+            NetworkEvent::WifiGotIp => OwnedNetworkEvent::WifiGotIp,
+            NetworkEvent::WifiDisconnected => OwnedNetworkEvent::WifiDisconnected,
+            // The line is truncated to the first `M` bytes.
+            NetworkEvent::Unknown(mut line) => {
+                OwnedNetworkEvent::Unknown(Vec::from_slice(line.take(M)).unwrap_or_default())
+            }
+            NetworkEvent::ModuleRebooted => OwnedNetworkEvent::ModuleRebooted,
+        }
     }
+}
+
+/// Incoming network event that owns the received data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum OwnedNetworkEvent<const M: usize> {
+    /// A new peer connected.
+    Connected {
+        /// Connection identifier.
+        link_id: u16,
+    },
+    /// The connection with the peer is closed.
+    Closed {
+        /// Connection identifier.
+        link_id: u16,
+        /// The connection has been closed by the [`NetworkSession::close`] call rather
+        /// than by the peer.
+        closed_locally: bool,
+    },
+    /// Bytes received from the peer.
+    DataAvailable {
+        /// Connection identifier.
+        link_id: u16,
+        /// Address of the peer, or `None` if it is not reported by the module firmware.
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_socket_addr"))]
+        remote: Option<SocketAddr>,
+        /// Received data.
+        data: Vec<u8, M>,
+    },
+    /// Bytes received from the peer have been buffered by the module in the passive
+    /// receiving mode.
+    DataBuffered {
+        /// Connection identifier.
+        link_id: u16,
+        /// Number of the buffered bytes.
+        len: usize,
+    },
+    /// The module has joined the access point.
+    WifiConnected,
+    /// The module has obtained the IP address from the joined access point.
+    WifiGotIp,
+    /// The module has been disconnected from the access point.
+    WifiDisconnected,
+    /// Complete line that is not recognized as any known event.
+    Unknown(Vec<u8, M>),
+    /// The module has rebooted unexpectedly.
+    ModuleRebooted,
+}
 
-    // Safety: `u8` is aprimitive type and doesn't have drop implementation so we can just
-    // modify the buffer length.
-    unsafe {
-        buf.set_len(buf_len - at);
+/// An iterator over the received network events.
+///
+/// This struct is created by the [`NetworkSession::events`] and
+/// [`NetworkSession::events_owned`] methods.
+#[derive(Debug)]
+pub struct Events<'a, Rx, Tx, C, const N: usize, const M: usize = N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    session: &'a mut NetworkSession<Rx, Tx, C, N>,
+}
+
+impl<'a, Rx, Tx, C, const N: usize, const M: usize> Iterator for Events<'a, Rx, Tx, C, N, M>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    type Item = crate::Result<OwnedNetworkEvent<M>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.session.poll_network_event_owned() {
+            Ok(event) => Some(Ok(event)),
+            Err(nb::Error::WouldBlock) => None,
+            Err(nb::Error::Other(err)) => Some(Err(err)),
+        }
     }
 }
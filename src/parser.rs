@@ -1,15 +1,45 @@
+//! Parsers of the module AT responses.
+//!
+//! The parsers are used by the driver itself, but they may be also reused by applications
+//! that talk to the module on their own.
+
 use core::str::FromStr;
 
-use nom::{alt, char, character::streaming::digit1, do_parse, named, opt, tag, IResult};
+use nom::{
+    alt, char, character::streaming::digit1, do_parse, named, opt, pair, recognize, tag, take_till,
+    take_until, IResult,
+};
 
-use crate::net::{IpAddr, Ipv4Addr};
+use crate::{
+    ipv4::parse_ipv4,
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+};
 
+/// Unsolicited message sent by the module.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CommandResponse {
+    /// A new connection has been established, e.g. `0,CONNECT`.
     Connected { link_id: u16 },
+    /// The connection has been closed, e.g. `0,CLOSED`.
     Closed { link_id: u16 },
-    DataAvailable { link_id: u16, size: u64 },
+    /// The data has been received, e.g. `+IPD,0,5:`, the data itself follows the header.
+    ///
+    /// The remote address is reported if it is enabled by the `AT+CIPDINFO=1` command,
+    /// e.g. `+IPD,0,5,"192.168.1.5",5000:`.
+    DataAvailable {
+        link_id: u16,
+        size: u64,
+        remote: Option<SocketAddr>,
+    },
+    /// The data has been buffered by the module in the passive receiving mode, e.g. `+IPD,0,5`,
+    /// it should be pulled by the `AT+CIPRECVDATA` command.
+    DataBuffered { link_id: u16, size: u64 },
+    /// The module has been disconnected from the access point.
     WifiDisconnect,
+    /// The module has joined the access point.
+    WifiConnected,
+    /// The module has obtained the IP address from the joined access point.
+    WifiGotIp,
 }
 
 fn parse_error(input: &[u8]) -> nom::Err<nom::error::Error<&[u8]>> {
@@ -33,14 +63,55 @@ fn parse_u64(input: &[u8]) -> IResult<&[u8], u64> {
     IResult::Ok((input, num))
 }
 
+fn parse_u16(input: &[u8]) -> IResult<&[u8], u16> {
+    let (input, digits) = digit1(input)?;
+    let num = atoi(digits)?;
+    IResult::Ok((input, num))
+}
+
 fn parse_u8(input: &[u8]) -> IResult<&[u8], u8> {
     let (input, digits) = digit1(input)?;
     let num = atoi(digits)?;
     IResult::Ok((input, num))
 }
 
+fn parse_i8(input: &[u8]) -> IResult<&[u8], i8> {
+    let (input, digits) = recognize!(input, pair!(opt!(char!('-')), digit1))?;
+    let num = atoi(digits)?;
+    IResult::Ok((input, num))
+}
+
 named!(crlf, tag!("\r\n"));
 
+/// Parses the IPv4 address, or the IPv6 one reported by the ESP32-series modules.
+pub(crate) fn parse_ip_addr(raw: &[u8]) -> Option<IpAddr> {
+    match parse_ipv4(raw) {
+        Some(ip) => Some(IpAddr::V4(ip)),
+        None => {
+            let ip = core::str::from_utf8(raw).ok()?.parse::<Ipv6Addr>().ok()?;
+            Some(IpAddr::V6(ip))
+        }
+    }
+}
+
+/// Parses the remote address of the received data, e.g. `,"192.168.1.5",5000`, the address
+/// is not quoted by the NonOS firmware.
+///
+/// The unrecognized address is `None`, so the frame is consumed anyway.
+fn remote_addr(input: &[u8]) -> IResult<&[u8], Option<SocketAddr>> {
+    let (input, (ip, port)) = do_parse!(
+        input,
+        char!(',')
+            >> opt!(char!('"'))
+            >> ip: take_till!(|byte| byte == b'"' || byte == b',')
+            >> opt!(char!('"'))
+            >> char!(',')
+            >> port: parse_u16
+            >> ((ip, port))
+    )?;
+    IResult::Ok((input, parse_ip_addr(ip).map(|ip| SocketAddr::new(ip, port))))
+}
+
 named!(
     connected<CommandResponse>,
     do_parse!(
@@ -71,9 +142,28 @@ named!(
             >> link_id: parse_link_id
             >> char!(',')
             >> size: parse_u64
+            >> remote: opt!(remote_addr)
             >> char!(':')
             >> opt!(crlf)
-            >> (CommandResponse::DataAvailable { link_id, size })
+            >> (CommandResponse::DataAvailable {
+                link_id,
+                size,
+                remote: remote.flatten()
+            })
+    )
+);
+
+named!(
+    data_buffered<CommandResponse>,
+    do_parse!(
+        opt!(crlf)
+            >> tag!("+IPD,")
+            >> link_id: parse_link_id
+            >> char!(',')
+            >> size: parse_u64
+            >> opt!(remote_addr)
+            >> crlf
+            >> (CommandResponse::DataBuffered { link_id, size })
     )
 );
 
@@ -84,77 +174,266 @@ named!(
     )
 );
 
+named!(
+    wifi_connected<CommandResponse>,
+    do_parse!(
+        opt!(crlf) >> tag!("WIFI CONNECTED") >> opt!(crlf) >> (CommandResponse::WifiConnected)
+    )
+);
+
+named!(
+    wifi_got_ip<CommandResponse>,
+    do_parse!(opt!(crlf) >> tag!("WIFI GOT IP") >> opt!(crlf) >> (CommandResponse::WifiGotIp))
+);
+
 named!(
     parse<CommandResponse>,
-    alt!(connected | closed | data_available | wifi_disconnect)
+    alt!(
+        connected
+            | closed
+            | data_available
+            | data_buffered
+            | wifi_disconnect
+            | wifi_connected
+            | wifi_got_ip
+    )
 );
 
 impl CommandResponse {
+    /// Parses the message at the beginning of the input, returns the remaining input and
+    /// the parsed message or `None` if the input doesn't start with a complete message.
     pub fn parse(input: &[u8]) -> Option<(&[u8], Self)> {
         parse(input).ok()
     }
 }
 
-pub struct CifsrResponse {
-    pub ap_ip: Option<IpAddr>,
-    pub sta_ip: Option<IpAddr>,
-}
-
 named!(
-    parse_ip4_addr<IpAddr>,
+    recv_data_header<u64>,
     do_parse!(
         opt!(crlf)
-            >> a: parse_u8
-            >> char!('.')
-            >> b: parse_u8
-            >> char!('.')
-            >> c: parse_u8
-            >> char!('.')
-            >> d: parse_u8
-            >> (IpAddr::V4(Ipv4Addr::new(a, b, c, d)))
+            >> tag!("+CIPRECVDATA")
+            >> alt!(char!(':') | char!(','))
+            >> size: parse_u64
+            >> alt!(char!(',') | char!(':'))
+            >> (size)
     )
 );
 
-named!(
-    parse_apip<IpAddr>,
+/// Skips the remote address that precedes the pulled data if it is enabled by the
+/// `AT+CIPDINFO=1` command, e.g. `"192.168.1.5",5000,`.
+///
+/// The data itself may look like an incomplete address, so the address is skipped only
+/// if it has been received completely.
+fn skip_recv_data_remote(data: &[u8]) -> &[u8] {
+    let skip = || {
+        let rest = data.strip_prefix(b"\"")?;
+        let quote = rest.iter().position(|&byte| byte == b'"')?;
+        parse_ip_addr(&rest[..quote])?;
+        let rest = rest[quote + 1..].strip_prefix(b",")?;
+        let comma = rest.iter().position(|&byte| byte == b',')?;
+        atoi::<u16>(&rest[..comma]).ok()?;
+        Some(&rest[comma + 1..])
+    };
+    skip().unwrap_or(data)
+}
+
+/// Parses the header of the `AT+CIPRECVDATA` response, e.g. `+CIPRECVDATA:5,` or
+/// `+CIPRECVDATA,5:` of the NonOS firmware, returns the data that follows the header and
+/// its length.
+pub fn parse_recv_data_header(input: &[u8]) -> Option<(&[u8], u64)> {
+    let (data, size) = recv_data_header(input).ok()?;
+    Some((skip_recv_data_remote(data), size))
+}
+
+/// Response to the `AT+CIFSR` command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CifsrResponse {
+    /// Software access point IP address.
+    pub ap_ip: Option<IpAddr>,
+    /// Station IP address.
+    pub sta_ip: Option<IpAddr>,
+}
+
+/// Parses the quoted IPv4 address, e.g. `"192.168.4.1"`.
+fn parse_quoted_ip4_addr(input: &[u8]) -> Option<IpAddr> {
+    let raw = input.strip_prefix(b"\"")?.strip_suffix(b"\"")?;
+    parse_ipv4(raw).map(IpAddr::V4)
+}
+
+impl CifsrResponse {
+    /// Parses the complete response, the lines may come in any order and may be interleaved
+    /// with the other lines like `+CIFSR:APMAC`.
+    ///
+    /// The addresses that are missing from the response are `None`.
+    pub fn parse(input: &[u8]) -> Self {
+        Self {
+            ap_ip: find_line_value(input, "+CIFSR:APIP,").and_then(parse_quoted_ip4_addr),
+            sta_ip: find_line_value(input, "+CIFSR:STAIP,").and_then(parse_quoted_ip4_addr),
+        }
+    }
+}
+
+/// Response to the `AT+CIPSTA?` and `AT+CIPAP?` commands, or to their `_CUR` suffixed
+/// variants.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IpConfigResponse {
+    /// IP address.
+    pub ip: Option<IpAddr>,
+    /// Gateway address.
+    pub gateway: Option<IpAddr>,
+    /// Network mask.
+    pub netmask: Option<IpAddr>,
+}
+
+/// Parses a single line of the IP configuration, the value is `None` if it is not an IPv4
+/// address, e.g. the `ip6ll` line of the ESP32-series modules.
+fn ip_config_line<'a>(
+    input: &'a [u8],
+    name: &str,
+) -> IResult<&'a [u8], (&'a [u8], Option<IpAddr>)> {
     do_parse!(
+        input,
         opt!(crlf)
-            >> tag!("+CIFSR:APIP,")
+            >> char!('+')
+            >> tag!(name)
+            >> opt!(tag!("_CUR"))
+            >> char!(':')
+            >> field: take_until!(":")
+            >> char!(':')
             >> char!('"')
-            >> ip_addr: parse_ip4_addr
+            >> value: take_until!("\"")
             >> char!('"')
             >> opt!(crlf)
-            >> (ip_addr)
+            >> ((field, parse_ipv4(value).map(IpAddr::V4)))
     )
-);
+}
+
+impl IpConfigResponse {
+    /// Parses the response lines of the command with the given name, e.g. `CIPSTA`.
+    pub fn parse<'a>(mut input: &'a [u8], name: &str) -> Option<(&'a [u8], Self)> {
+        let mut response = Self::default();
+        while let Ok((remainder, (field, ip_addr))) = ip_config_line(input, name) {
+            match field {
+                b"ip" => response.ip = ip_addr,
+                b"gateway" => response.gateway = ip_addr,
+                b"netmask" => response.netmask = ip_addr,
+                _ => {}
+            }
+            input = remainder;
+        }
+        Some((input, response))
+    }
+}
+
+/// Response to the `AT+CWJAP?` or `AT+CWJAP_CUR?` command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CwjapResponse<'a> {
+    /// Access point SSID.
+    pub ssid: &'a [u8],
+    /// Access point MAC address.
+    pub bssid: &'a [u8],
+    /// WiFi channel.
+    pub channel: u8,
+    /// Signal strength in dBm.
+    pub rssi: i8,
+}
 
 named!(
-    parse_staip<IpAddr>,
+    cwjap_response<CwjapResponse>,
     do_parse!(
         opt!(crlf)
-            >> tag!("+CIFSR:STAIP,")
+            >> tag!("+CWJAP")
+            >> opt!(tag!("_CUR"))
+            >> char!(':')
             >> char!('"')
-            >> ip_addr: parse_ip4_addr
+            >> ssid: take_until!("\",\"")
+            >> tag!("\",\"")
+            >> bssid: take_until!("\"")
             >> char!('"')
-            >> opt!(crlf)
-            >> (ip_addr)
+            >> char!(',')
+            >> channel: parse_u8
+            >> char!(',')
+            >> rssi: parse_i8
+            >> (CwjapResponse {
+                ssid,
+                bssid,
+                channel,
+                rssi
+            })
     )
 );
 
-named!(
-    cifsr_response<CifsrResponse>,
+impl<'a> CwjapResponse<'a> {
+    /// Parses the response, if the module is not connected to any access point,
+    /// the `None` will be returned.
+    pub fn parse(input: &'a [u8]) -> Option<(&'a [u8], Self)> {
+        cwjap_response(input).ok()
+    }
+}
+
+/// Parses the MAC address in the colon separated hexadecimal notation, e.g. `18:fe:34:a1:b2:c3`.
+pub fn parse_mac(raw: &[u8]) -> Option<[u8; 6]> {
+    let mut mac = [0_u8; 6];
+    let mut parts = raw.split(|&byte| byte == b':');
+    for octet in mac.iter_mut() {
+        let part = parts.next()?;
+        if part.len() != 2 || !part.iter().all(u8::is_ascii_hexdigit) {
+            return None;
+        }
+        *octet = u8::from_str_radix(core::str::from_utf8(part).ok()?, 16).ok()?;
+    }
+
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(mac)
+}
+
+fn mac_line<'a>(input: &'a [u8], name: &str) -> IResult<&'a [u8], &'a [u8]> {
     do_parse!(
+        input,
         opt!(crlf)
-            >> ap_ip: opt!(parse_apip)
-            >> sta_ip: opt!(parse_staip)
-            >> (CifsrResponse { ap_ip, sta_ip })
+            >> char!('+')
+            >> tag!(name)
+            >> opt!(tag!("_CUR"))
+            >> char!(':')
+            >> char!('"')
+            >> value: take_until!("\"")
+            >> char!('"')
+            >> (value)
     )
-);
+}
 
-impl CifsrResponse {
-    pub fn parse(input: &[u8]) -> Option<(&[u8], Self)> {
-        cifsr_response(input).ok()
-    }
+/// Parses the response to the MAC address query with the given name, e.g. `CIPSTAMAC`
+/// for the `+CIPSTAMAC:"18:fe:34:a1:b2:c3"` response.
+pub fn parse_mac_response(input: &[u8], name: &str) -> Option<[u8; 6]> {
+    mac_line(input, name)
+        .ok()
+        .and_then(|(_, raw)| parse_mac(raw))
+}
+
+fn numeric_value<'a, T: FromStr>(input: &'a [u8], prefix: &str) -> IResult<&'a [u8], T> {
+    let (input, digits) = do_parse!(
+        input,
+        opt!(crlf) >> tag!(prefix) >> digits: digit1 >> (digits)
+    )?;
+    let num = atoi(digits)?;
+    IResult::Ok((input, num))
+}
+
+/// Parses the numeric value of a response like `+SYSRAM:53432`, where `+SYSRAM:` is
+/// the given prefix.
+pub fn parse_numeric<T: FromStr>(input: &[u8], prefix: &str) -> Option<T> {
+    numeric_value(input, prefix).ok().map(|(_, num)| num)
+}
+
+/// Returns the rest of the first response line that starts with the given prefix,
+/// e.g. `AT version:` in the `AT+GMR` response.
+pub fn find_line_value<'a>(input: &'a [u8], prefix: &str) -> Option<&'a [u8]> {
+    input
+        .split(|&byte| byte == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .find_map(|line| line.strip_prefix(prefix.as_bytes()))
 }
 
 #[test]
@@ -182,7 +461,22 @@ fn test_parse_data_available() {
         event,
         CommandResponse::DataAvailable {
             link_id: 12,
-            size: 6
+            size: 6,
+            remote: None,
+        }
+    )
+}
+
+#[test]
+fn test_parse_data_buffered() {
+    let raw = b"+IPD,2,1460\r\n";
+    let event = CommandResponse::parse(raw.as_ref()).unwrap().1;
+
+    assert_eq!(
+        event,
+        CommandResponse::DataBuffered {
+            link_id: 2,
+            size: 1460
         }
     )
 }
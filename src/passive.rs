@@ -0,0 +1,174 @@
+//! Passive receiving mode, in which the module buffers the received data until the driver
+//! pulls it.
+
+use embedded_hal::serial;
+use simple_clock::SimpleClock;
+
+use crate::{
+    error::ensure,
+    module::{Condition, OkCondition},
+    network_session::MAX_LINKS,
+    parser::{find_line_value, parse_recv_data_header},
+    reader_part::ReadData,
+    Capabilities, Error, NetworkSession,
+};
+
+/// Maximum number of bytes pulled by a single `AT+CIPRECVDATA` command.
+const PULL_MAX_LEN: usize = 2048;
+/// Space of the reader buffer reserved for the header and the terminator of the pulled data.
+const PULL_RESPONSE_OVERHEAD: usize = 32;
+
+/// Waits for the complete `AT+CIPRECVDATA` response.
+///
+/// The pulled data may contain anything, including the response terminators, so the
+/// terminator is looked for only after the data of the length given in the header.
+#[derive(Clone, Copy)]
+struct PullCondition;
+
+impl PullCondition {
+    /// Returns the length of the complete response and `true` if the command succeeded.
+    fn response_len(buf: &[u8]) -> Option<(usize, bool)> {
+        let (from, rest) = match parse_recv_data_header(buf) {
+            Some((data, size)) => {
+                let size = size as usize;
+                if data.len() < size {
+                    return None;
+                }
+                (buf.len() - data.len() + size, &data[size..])
+            }
+            None => (0, buf),
+        };
+
+        let (pos, terminator) = OkCondition::find_terminator(rest)?;
+        Some((from + pos + terminator.len(), terminator == OkCondition::OK))
+    }
+}
+
+impl<'a, const N: usize> Condition<'a, N> for PullCondition {
+    type Output = ReadData<'a, N>;
+
+    fn is_performed(self, buf: &[u8]) -> bool {
+        Self::response_len(buf).is_some()
+    }
+
+    fn output(self, buf: ReadData<'a, N>) -> Self::Output {
+        buf
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Switches the module to the passive receiving mode or back to the active one by
+    /// the `AT+CIPRECVMODE` command.
+    ///
+    /// In the passive mode the module doesn't send the received data to the driver, but
+    /// reports it by the [`NetworkEvent::DataBuffered`] event. The length of the buffered
+    /// data can be [queried](Self::pending_len), and the data itself should be
+    /// [pulled](Self::pull_data) by the application.
+    ///
+    /// [`NetworkEvent::DataBuffered`]: crate::NetworkEvent::DataBuffered
+    ///
+    /// Returns [`Error::Unsupported`] if the module firmware doesn't support the passive
+    /// receiving mode.
+    pub fn set_passive_receive(&mut self, enabled: bool) -> crate::Result<()> {
+        self.module.require(Capabilities::PASSIVE_RECEIVE)?;
+        self.module
+            .send_at_command(format_args!("AT+CIPRECVMODE={}", enabled as u8))?
+            .map_err(|_| Error::CommandFailed)?;
+
+        self.passive_receive = enabled;
+        Ok(())
+    }
+
+    /// Returns `true` if the module is in the passive receiving mode.
+    pub fn is_passive_receive(&self) -> bool {
+        self.passive_receive
+    }
+
+    /// Returns the number of the received bytes buffered by the module for the link with
+    /// the given identifier in the passive receiving mode.
+    ///
+    /// It allows the application to decide when and how much data to pull, so the peer
+    /// is throttled by the TCP flow control instead of overflowing the reader buffer.
+    /// The length is zero if the link is not connected.
+    ///
+    /// Returns [`Error::Unsupported`] if the module firmware doesn't support the passive
+    /// receiving mode, or [`Error::CommandFailed`] if the mode is not
+    /// [enabled](Self::set_passive_receive).
+    pub fn pending_len(&mut self, link_id: usize) -> crate::Result<usize> {
+        ensure!(
+            link_id < MAX_LINKS,
+            "Link identifier should be less than the maximum number of connections"
+        );
+        self.module.require(Capabilities::PASSIVE_RECEIVE)?;
+        // The module rejects the query in the active mode.
+        if !self.passive_receive {
+            return Err(Error::CommandFailed);
+        }
+
+        let lens = self
+            .module
+            .send_at_command("AT+CIPRECVLEN?")?
+            .map_err(|_| Error::CommandFailed)?;
+        let len = find_line_value(&lens, "+CIPRECVLEN:")
+            .and_then(|lens| lens.split(|&byte| byte == b',').nth(link_id))
+            .and_then(|len| core::str::from_utf8(len).ok()?.trim().parse().ok())
+            // The disconnected links are reported as empty or negative values.
+            .unwrap_or(0);
+        Ok(len)
+    }
+
+    /// Pulls the data buffered by the module for the link with the given identifier in the
+    /// passive receiving mode into the given buffer by the `AT+CIPRECVDATA` command.
+    ///
+    /// Returns the number of the pulled bytes. No more than 2048 bytes are pulled at a time,
+    /// and the pulled data with its response header should fit into the reader buffer, so
+    /// the rest of the buffered data is left in the module for the next pull.
+    ///
+    /// Returns [`Error::Unsupported`] if the module firmware doesn't support the passive
+    /// receiving mode, or [`Error::CommandFailed`] if the mode is not
+    /// [enabled](Self::set_passive_receive) or the module rejected the command, e.g. if
+    /// there is no buffered data.
+    pub fn pull_data(&mut self, link_id: usize, buf: &mut [u8]) -> crate::Result<usize> {
+        ensure!(
+            link_id < MAX_LINKS,
+            "Link identifier should be less than the maximum number of connections"
+        );
+        self.module.require(Capabilities::PASSIVE_RECEIVE)?;
+        if !self.passive_receive {
+            return Err(Error::CommandFailed);
+        }
+
+        let len = buf
+            .len()
+            .min(PULL_MAX_LEN)
+            .min(N.saturating_sub(PULL_RESPONSE_OVERHEAD));
+        if len == 0 {
+            return Ok(0);
+        }
+
+        // The pending delivery confirmations would be taken for the command response.
+        self.module.complete_sends()?;
+        self.module
+            .write_command_fmt(format_args!("AT+CIPRECVDATA={},{}", link_id, len))?;
+
+        let mut resp = self.module.read_until(PullCondition)?;
+        // The condition is performed, so the response is always complete.
+        let (resp_len, succeeded) =
+            PullCondition::response_len(&resp).ok_or(Error::CommandFailed)?;
+        // The frames received after the response are kept in the reader buffer.
+        resp.cut(resp_len, 0);
+        if !succeeded {
+            return Err(Error::CommandFailed);
+        }
+
+        let (data, size) = parse_recv_data_header(&resp).ok_or(Error::CommandFailed)?;
+        let size = core::cmp::min(size as usize, len);
+        buf[..size].copy_from_slice(&data[..size]);
+        Ok(size)
+    }
+}
@@ -0,0 +1,72 @@
+//! Connectivity check by the ICMP echo requests.
+
+use core::time::Duration;
+
+use embedded_hal::serial;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use simple_clock::SimpleClock;
+
+use crate::{module::Escaped, parser::find_line_value, Error, Module, NetworkSession};
+
+/// Round-trip time of the ICMP echo request in milliseconds.
+#[derive(Debug, PartialEq, Clone, Copy, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PingTime(pub u32);
+
+impl PingTime {
+    /// Returns the round-trip time as a duration.
+    pub fn as_duration(self) -> Duration {
+        Duration::from_millis(u64::from(self.0))
+    }
+
+    /// Parses the `+PING:<time>` response, or the `+<time>` one of the NonOS firmware.
+    pub(crate) fn parse(resp: &[u8]) -> Option<Self> {
+        let value = find_line_value(resp, "+PING:").or_else(|| find_line_value(resp, "+"))?;
+        core::str::from_utf8(value)
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+            .map(Self)
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> Module<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Sends the ICMP echo request to the given host name or IP address and returns
+    /// the round-trip time.
+    ///
+    /// Returns the [`Error::CommandFailed`] error if the host cannot be resolved or doesn't
+    /// respond.
+    ///
+    /// # Notes
+    ///
+    /// The module waits for the echo reply for several seconds, so the operation timeout
+    /// should be long enough.
+    pub fn ping(&mut self, host: &str) -> crate::Result<PingTime> {
+        self.query(
+            format_args!("AT+PING=\"{}\"", Escaped(host)),
+            PingTime::parse,
+        )?
+        .ok_or(Error::CommandFailed)
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Sends the ICMP echo request to the given host and returns the round-trip time.
+    ///
+    /// See [`Module::ping`] for details.
+    pub fn ping(&mut self, host: &str) -> crate::Result<PingTime> {
+        self.module.ping(host)
+    }
+}
@@ -0,0 +1,229 @@
+//! Pipelined sending of the data segments.
+
+use embedded_hal::serial;
+use heapless::Deque;
+use simple_clock::SimpleClock;
+
+use crate::{
+    module::{CarretCondition, Condition, OkCondition},
+    parser::CommandResponse,
+    reader_part::{remove_range, ReadData},
+    Error, Module, NetworkSession,
+};
+
+/// Maximum number of the sent segments that may wait for the delivery confirmation.
+pub const MAX_IN_FLIGHT_SENDS: usize = 4;
+
+const SEND_OK: &[u8] = b"SEND OK\r\n";
+const SEND_FAIL: &[u8] = b"SEND FAIL\r\n";
+
+/// Table of the sent segments that are waiting for the `SEND OK` confirmation.
+#[derive(Debug, Default)]
+pub(crate) struct InFlight {
+    /// Link identifiers of the sent segments in the sending order.
+    links: Deque<u8, MAX_IN_FLIGHT_SENDS>,
+    /// Maximum number of the unconfirmed segments, pipelining is disabled if zero.
+    pub depth: usize,
+    /// Some of the segments have not been delivered.
+    pub failed: bool,
+}
+
+impl InFlight {
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    pub fn push(&mut self, link_id: usize) {
+        // The length is limited by the depth.
+        self.links.push_back(link_id as u8).ok();
+    }
+
+    /// Forgets all the segments, they are considered as not delivered.
+    pub fn abort(&mut self) {
+        if !self.is_empty() {
+            self.failed = true;
+        }
+        self.links.clear();
+    }
+
+    /// Resolves the oldest segment by the delivery result.
+    fn resolve(&mut self, delivered: bool) {
+        if self.links.pop_front().is_some() && !delivered {
+            self.failed = true;
+        }
+    }
+
+    /// Resolves the segment if the line is its delivery confirmation, returns `true` if
+    /// the line belongs to the pipelined sending.
+    pub fn resolve_line(&mut self, line: &[u8]) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+
+        match line {
+            b"SEND OK" => self.resolve(true),
+            b"SEND FAIL" => self.resolve(false),
+            // Some firmwares report about the received bytes before the confirmation.
+            _ if line.starts_with(b"Recv ") => {}
+            _ => return false,
+        }
+        true
+    }
+
+    /// Resolves the segments by the confirmations in the given bytes.
+    pub fn resolve_all(&mut self, bytes: &[u8]) {
+        let mut from = 0;
+        while !self.is_empty() {
+            match find_confirmation(&bytes[from..]) {
+                Some((pos, len, delivered)) => {
+                    self.resolve(delivered);
+                    from += pos + len;
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// Finds the first delivery confirmation line, returns its position, length and
+/// the delivery result.
+///
+/// The received data frames are skipped, since their data may contain anything, including
+/// the confirmation lines.
+pub(crate) fn find_confirmation(bytes: &[u8]) -> Option<(usize, usize, bool)> {
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let rest = &bytes[pos..];
+        if rest.starts_with(b"+IPD,") {
+            match CommandResponse::parse(rest) {
+                Some((data, CommandResponse::DataAvailable { size, .. })) => {
+                    pos += rest.len() - data.len() + size as usize;
+                    continue;
+                }
+                // The data buffered by the module doesn't follow the notification.
+                Some((data, CommandResponse::DataBuffered { .. })) => {
+                    pos += rest.len() - data.len();
+                    continue;
+                }
+                // Nothing can follow the incomplete frame header.
+                _ => return None,
+            }
+        }
+
+        let found = [(SEND_OK, true), (SEND_FAIL, false)]
+            .iter()
+            .find(|(line, _)| rest.starts_with(line));
+        if let Some((line, delivered)) = found {
+            return Some((pos, line.len(), *delivered));
+        }
+        pos += rest.iter().position(|byte| *byte == b'\n')? + 1;
+    }
+    None
+}
+
+impl<Rx, Tx, C, const N: usize> Module<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Waits for the delivery confirmation of the oldest in-flight segment.
+    pub(crate) fn complete_send(&mut self) -> crate::Result<()> {
+        // The confirmation may have been received by the network event polling.
+        if let Some((pos, len, delivered)) = find_confirmation(self.reader.buf()) {
+            remove_range(self.reader.buf_mut(), pos, pos + len);
+            self.in_flight.resolve(delivered);
+            return Ok(());
+        }
+
+        let delivered = self.read_until(OkCondition)?.is_ok();
+        self.in_flight.resolve(delivered);
+        Ok(())
+    }
+
+    /// Waits for the delivery confirmations of all the in-flight segments.
+    pub(crate) fn complete_sends(&mut self) -> crate::Result<()> {
+        while !self.in_flight.is_empty() {
+            self.complete_send()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the data segment without waiting for its delivery confirmation.
+    pub(crate) fn send_pipelined<I>(&mut self, link_id: usize, bytes: I) -> crate::Result<()>
+    where
+        I: Iterator<Item = u8> + ExactSizeIterator,
+    {
+        while self.in_flight.len() >= self.in_flight.depth {
+            self.complete_send()?;
+        }
+
+        self.write_command_fmt(format_args!("AT+CIPSEND={},{}", link_id, bytes.len()))?;
+        let mut kept = self.reader.buf().len();
+        self.wait_until(CarretCondition, &mut kept)?;
+        let prompt = CarretCondition.output(ReadData::new(self.reader.buf_mut(), kept));
+        // The confirmations of the previous segments may precede the prompt.
+        self.in_flight.resolve_all(&prompt);
+        drop(prompt);
+
+        for byte in bytes {
+            nb::block!(self.writer.write_byte(byte))?;
+        }
+        self.writer.flush()?;
+
+        self.in_flight.push(link_id);
+        Ok(())
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Sets the maximum number of the sent segments that may wait for the delivery
+    /// confirmation, pipelining is disabled if zero.
+    ///
+    /// With pipelining the [`send`](Self::send) method returns right after writing the data,
+    /// so the next segment can be sent while the module is still delivering the previous
+    /// one. It raises the throughput when serving several clients, but the delivery errors
+    /// are reported later by the [`complete_sends`](Self::complete_sends) method.
+    ///
+    /// # Notes
+    ///
+    /// The module firmware should accept the `AT+CIPSEND` command before the previous
+    /// segment has been delivered, the allowed depth is up to [`MAX_IN_FLIGHT_SENDS`],
+    /// otherwise the [`Error::InvalidArgument`] error is returned.
+    pub fn set_send_pipeline_depth(&mut self, depth: usize) -> crate::Result<()> {
+        if depth > MAX_IN_FLIGHT_SENDS {
+            return Err(Error::InvalidArgument);
+        }
+
+        self.module.complete_sends()?;
+        self.module.in_flight.depth = depth;
+        Ok(())
+    }
+
+    /// Returns the number of the sent segments that are waiting for the delivery
+    /// confirmation.
+    pub fn in_flight_sends(&self) -> usize {
+        self.module.in_flight.len()
+    }
+
+    /// Blocks until all the sent segments are confirmed by the module.
+    ///
+    /// Returns the [`Error::CommandFailed`] error if any of the segments sent since the last
+    /// call have not been delivered.
+    pub fn complete_sends(&mut self) -> crate::Result<()> {
+        self.module.complete_sends()?;
+        if core::mem::take(&mut self.module.in_flight.failed) {
+            return Err(Error::CommandFailed);
+        }
+        Ok(())
+    }
+}
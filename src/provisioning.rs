@@ -0,0 +1,327 @@
+//! WiFi credentials provisioning over the SoftAP mode.
+
+use embedded_hal::serial;
+use heapless::{String, Vec};
+use simple_clock::SimpleClock;
+
+use crate::{
+    captive_portal::{send_html, DNS_MESSAGE_MAX_LEN},
+    module::find_subsequence,
+    net::IpAddr,
+    network_session::link_mask,
+    CaptivePortal, Error, JoinApConfig, Module, NetworkEvent, NetworkSession, OwnedJoinApConfig,
+    SoftApConfig, CAPTIVE_PORTAL_DNS_LINK, PASSWORD_MAX_LEN, SSID_MAX_LEN,
+};
+
+/// Maximum size of the HTTP request with the credentials.
+const REQUEST_MAX_LEN: usize = 1024;
+
+/// Persistent storage of the WiFi credentials.
+pub trait CredentialStore {
+    /// Saves the credentials of the successfully joined access point.
+    fn save(&mut self, config: &JoinApConfig<'_>) -> crate::Result<()>;
+}
+
+/// HTML pages served during the provisioning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProvisioningPages<'a> {
+    /// Page with the form that posts the `ssid` and `password` fields.
+    pub form: &'a str,
+    /// Page shown after the device has joined the access point.
+    pub joined: &'a str,
+    /// Page shown if the device is unable to join the access point, e.g. due to the wrong
+    /// password.
+    pub failed: &'a str,
+}
+
+impl Default for ProvisioningPages<'static> {
+    fn default() -> Self {
+        Self {
+            form: "<!DOCTYPE html><html><body><h1>WiFi setup</h1>\
+                <form method=\"post\" action=\"/\">\
+                <p><input name=\"ssid\" placeholder=\"SSID\"></p>\
+                <p><input name=\"password\" type=\"password\" placeholder=\"Password\"></p>\
+                <p><button>Join</button></p></form></body></html>",
+            joined: "<!DOCTYPE html><html><body>\
+                <h1>The device has joined the network</h1></body></html>",
+            failed: "<!DOCTYPE html><html><body>\
+                <h1>Unable to join the network</h1>\
+                <p>Check the SSID and the password.</p>\
+                <p><a href=\"/\">Try again</a></p></body></html>",
+        }
+    }
+}
+
+/// Provisioning flow that receives the WiFi credentials from a user.
+///
+/// The module starts the software access point with the captive portal, which serves
+/// the credentials form. The credentials can be also posted as a JSON object with the
+/// `ssid` and `password` fields. Once the module has joined the entered access point,
+/// the credentials are saved to the store, the software access point is stopped and
+/// the joined session is returned.
+#[derive(Debug, Clone, Copy)]
+pub struct Provisioning<'a> {
+    softap: SoftApConfig<'a>,
+    pages: ProvisioningPages<'a>,
+}
+
+impl<'a> Provisioning<'a> {
+    /// Creates a new provisioning flow with the given software access point configuration
+    /// and the default pages.
+    pub fn new(softap: SoftApConfig<'a>) -> Self {
+        Self {
+            softap,
+            pages: ProvisioningPages::default(),
+        }
+    }
+
+    /// Sets the pages served during the provisioning.
+    pub fn pages(mut self, pages: ProvisioningPages<'a>) -> Self {
+        self.pages = pages;
+        self
+    }
+
+    /// Runs the provisioning until the module joins an access point.
+    pub fn run<Rx, Tx, C, S, const N: usize>(
+        &self,
+        module: Module<Rx, Tx, C, N>,
+        store: &mut S,
+    ) -> crate::Result<NetworkSession<Rx, Tx, C, N>>
+    where
+        Rx: serial::Read<u8> + 'static,
+        Tx: serial::Write<u8> + 'static,
+        C: SimpleClock,
+        S: CredentialStore,
+    {
+        let mut session = self.softap.start(module)?;
+        let address = match session.get_info()?.softap_address {
+            Some(IpAddr::V4(address)) => address,
+            _ => return Err(Error::CommandFailed),
+        };
+        let portal = CaptivePortal::new(address, self.pages.form);
+        portal.start(&mut session)?;
+
+        let mut request = Vec::<u8, REQUEST_MAX_LEN>::new();
+        let mut request_link = None;
+        // Bitmask of the links that have been answered, the rest of their requests
+        // is ignored.
+        let mut answered = 0_u8;
+        loop {
+            let mut buf = [0_u8; DNS_MESSAGE_MAX_LEN];
+            let (link_id, len) = nb::block!(poll_data(&mut session, &mut buf, &mut answered))?;
+            if link_id == CAPTIVE_PORTAL_DNS_LINK {
+                portal.answer_dns(&mut session, &mut buf, len)?;
+                continue;
+            }
+
+            if request_link != Some(link_id) {
+                request.clear();
+                request_link = Some(link_id);
+            }
+            // The oversized requests are answered with the form.
+            if request.extend_from_slice(&buf[..len]).is_err() {
+                request_link = None;
+                answered |= link_mask(link_id as u16);
+                send_html(&mut session, link_id, self.pages.form)?;
+                continue;
+            }
+
+            let body = match http_request_body(&request) {
+                Some(body) => body,
+                None => continue,
+            };
+            request_link = None;
+            answered |= link_mask(link_id as u16);
+
+            let config = match body.and_then(parse_credentials) {
+                Some(config) => config,
+                None => {
+                    send_html(&mut session, link_id, self.pages.form)?;
+                    continue;
+                }
+            };
+
+            let config = config.as_config();
+            match config
+                .validate()
+                .and_then(|_| config.join_ap(&mut session.module))
+            {
+                Ok(()) => {
+                    store.save(&config)?;
+                    send_html(&mut session, link_id, self.pages.joined)?;
+                    break;
+                }
+                Err(Error::JoinApError) | Err(Error::InvalidConfig(_)) => {
+                    send_html(&mut session, link_id, self.pages.failed)?;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        // Stop the captive portal.
+        session
+            .module
+            .send_at_command("AT+CIPSERVER=0")?
+            .map_err(|_| Error::CommandFailed)?;
+        session.close(CAPTIVE_PORTAL_DNS_LINK)?;
+        // Stop the software access point, the module stays connected to the joined one.
+        let suffix = session.module.dialect().current_suffix();
+        session
+            .module
+            .send_at_command(format_args!("AT+CWMODE{}=1", suffix))?
+            .map_err(|_| Error::CommandFailed)?;
+        Ok(session)
+    }
+}
+
+/// Polls the received data and copies it into the given buffer, the data of the answered
+/// links and the other events are skipped.
+fn poll_data<Rx, Tx, C, const N: usize>(
+    session: &mut NetworkSession<Rx, Tx, C, N>,
+    buf: &mut [u8],
+    answered: &mut u8,
+) -> nb::Result<(usize, usize), Error>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    match session.poll_network_event()? {
+        NetworkEvent::DataAvailable { link_id, data, .. }
+            if *answered & link_mask(link_id) != 0 =>
+        {
+            data.discard();
+            Err(nb::Error::WouldBlock)
+        }
+        NetworkEvent::DataAvailable {
+            link_id, mut data, ..
+        } => Ok((usize::from(link_id), data.read(buf))),
+        NetworkEvent::Connected { link_id } => {
+            *answered &= !link_mask(link_id);
+            Err(nb::Error::WouldBlock)
+        }
+        _ => Err(nb::Error::WouldBlock),
+    }
+}
+
+/// Checks that the HTTP request is complete and returns its body if it's a `POST` request.
+///
+/// Returns `None` if the request has not been received completely yet.
+pub(crate) fn http_request_body(request: &[u8]) -> Option<Option<&[u8]>> {
+    let headers_len = find_subsequence(request, b"\r\n\r\n")?;
+    let (headers, body) = (&request[..headers_len], &request[headers_len + 4..]);
+    if !headers.starts_with(b"POST ") {
+        return Some(None);
+    }
+
+    let content_length = headers
+        .split(|&byte| byte == b'\n')
+        .filter_map(|line| {
+            let (name, value) = split_once(line, b':')?;
+            if !name.eq_ignore_ascii_case(b"content-length") {
+                return None;
+            }
+            core::str::from_utf8(value).ok()?.trim().parse().ok()
+        })
+        .next()
+        .unwrap_or(0);
+    if body.len() < content_length {
+        return None;
+    }
+    Some(Some(&body[..content_length]))
+}
+
+/// Parses the credentials from the URL encoded form or from the JSON object.
+pub(crate) fn parse_credentials(body: &[u8]) -> Option<OwnedJoinApConfig> {
+    let is_json = body.iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'{');
+    let (ssid, password) = if is_json {
+        (
+            json_string_field::<SSID_MAX_LEN>(body, "ssid")?,
+            json_string_field::<PASSWORD_MAX_LEN>(body, "password"),
+        )
+    } else {
+        (
+            form_field::<SSID_MAX_LEN>(body, "ssid")?,
+            form_field::<PASSWORD_MAX_LEN>(body, "password"),
+        )
+    };
+
+    Some(OwnedJoinApConfig {
+        ssid,
+        // Empty password means the open network.
+        password: password.filter(|password| !password.is_empty()),
+        options: None,
+    })
+}
+
+fn split_once(bytes: &[u8], delimiter: u8) -> Option<(&[u8], &[u8])> {
+    let pos = bytes.iter().position(|&byte| byte == delimiter)?;
+    Some((&bytes[..pos], &bytes[pos + 1..]))
+}
+
+/// Returns the decoded value of the URL encoded form field.
+fn form_field<const N: usize>(body: &[u8], name: &str) -> Option<String<N>> {
+    let value = body
+        .split(|&byte| byte == b'&')
+        .filter_map(|pair| split_once(pair, b'='))
+        .find(|(key, _)| *key == name.as_bytes())?
+        .1;
+
+    let mut decoded = Vec::<u8, N>::new();
+    let mut bytes = value.iter();
+    while let Some(&byte) = bytes.next() {
+        let byte = match byte {
+            b'+' => b' ',
+            b'%' => {
+                let hex = [*bytes.next()?, *bytes.next()?];
+                u8::from_str_radix(core::str::from_utf8(&hex).ok()?, 16).ok()?
+            }
+            other => other,
+        };
+        decoded.push(byte).ok()?;
+    }
+    to_string(decoded)
+}
+
+/// Returns the value of the string field of the flat JSON object.
+///
+/// Only the `\"`, `\\` and `\/` escape sequences are supported.
+fn json_string_field<const N: usize>(body: &[u8], name: &str) -> Option<String<N>> {
+    let mut key = String::<32>::new();
+    key.push('"').ok()?;
+    key.push_str(name).ok()?;
+    key.push('"').ok()?;
+
+    let rest = &body[find_subsequence(body, key.as_bytes())? + key.len()..];
+    let rest = skip_whitespace(rest).strip_prefix(b":")?;
+    let rest = skip_whitespace(rest).strip_prefix(b"\"")?;
+
+    let mut decoded = Vec::<u8, N>::new();
+    let mut bytes = rest.iter();
+    loop {
+        let byte = match *bytes.next()? {
+            b'"' => break,
+            b'\\' => match *bytes.next()? {
+                escaped @ (b'"' | b'\\' | b'/') => escaped,
+                _ => return None,
+            },
+            other => other,
+        };
+        decoded.push(byte).ok()?;
+    }
+    to_string(decoded)
+}
+
+fn skip_whitespace(bytes: &[u8]) -> &[u8] {
+    let pos = bytes
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace())
+        .unwrap_or(bytes.len());
+    &bytes[pos..]
+}
+
+fn to_string<const N: usize>(bytes: Vec<u8, N>) -> Option<String<N>> {
+    let mut s = String::new();
+    s.push_str(core::str::from_utf8(&bytes).ok()?).ok()?;
+    Some(s)
+}
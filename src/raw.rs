@@ -0,0 +1,75 @@
+//! Raw access to the serial port for the AT extensions that don't fit the line-oriented
+//! command and response model, e.g. vendor binary upload commands.
+
+use embedded_hal::serial;
+use simple_clock::SimpleClock;
+
+use crate::{reader_part::truncate_buf, Error, Module, NetworkSession};
+
+impl<Rx, Tx, C, const N: usize> Module<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Writes the bytes to the module as is and waits until they have been transmitted.
+    pub fn write_raw(&mut self, bytes: &[u8]) -> crate::Result<()> {
+        self.writer.write_all(bytes)?;
+        self.writer.flush()
+    }
+
+    /// Reads the bytes from the module as is, including the bytes that have already been
+    /// received by the driver but not handled yet.
+    ///
+    /// Waits until the buffer is full or the given time in microseconds elapses, returns
+    /// the number of bytes read. Returns the [`Error::Timeout`] error if no bytes have been
+    /// received at all.
+    pub fn read_raw(&mut self, buf: &mut [u8], timeout_us: u64) -> crate::Result<usize> {
+        let buffered = core::cmp::min(buf.len(), self.reader.buf().len());
+        buf[..buffered].copy_from_slice(&self.reader.buf()[..buffered]);
+        truncate_buf(self.reader.buf_mut(), buffered);
+
+        let started_at = self.clock.now_us();
+        let mut filled = buffered;
+        while filled < buf.len() {
+            match self.reader.read_byte() {
+                Ok(byte) => {
+                    buf[filled] = byte;
+                    filled += 1;
+                }
+                Err(nb::Error::WouldBlock) => {
+                    if self.clock.now_us().saturating_sub(started_at) >= timeout_us {
+                        break;
+                    }
+                }
+                Err(nb::Error::Other(err)) => return Err(err),
+            }
+        }
+
+        if filled == 0 && !buf.is_empty() {
+            return Err(Error::Timeout);
+        }
+        Ok(filled)
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Writes the bytes to the module as is.
+    ///
+    /// See [`Module::write_raw`].
+    pub fn write_raw(&mut self, bytes: &[u8]) -> crate::Result<()> {
+        self.module.write_raw(bytes)
+    }
+
+    /// Reads the bytes from the module as is.
+    ///
+    /// See [`Module::read_raw`].
+    pub fn read_raw(&mut self, buf: &mut [u8], timeout_us: u64) -> crate::Result<usize> {
+        self.module.read_raw(buf, timeout_us)
+    }
+}
@@ -3,20 +3,35 @@
 use core::{
     fmt::{self, Write},
     ops::Deref,
+    str::Utf8Error,
 };
 
 use embedded_hal::serial;
 use heapless::Vec;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer};
 
-use crate::Error;
+use crate::{error::SerialErrorKind, net::SocketAddr, trace::TraceRing, Error, Lines};
+
+/// Function that recognizes the kind of the serial port error.
+pub(crate) type ClassifyFn<Rx> = fn(&<Rx as serial::Read<u8>>::Error) -> Option<SerialErrorKind>;
 
 #[derive(Debug)]
-pub(crate) struct ReaderPart<Rx, const N: usize> {
+pub(crate) struct ReaderPart<Rx, const N: usize>
+where
+    Rx: serial::Read<u8> + 'static,
+{
     rx: Rx,
     buf: Vec<u8, N>,
+    pub classify: ClassifyFn<Rx>,
+    /// Last received bytes.
+    pub trace: TraceRing,
 }
 
-impl<Rx, const N: usize> ReaderPart<Rx, N> {
+impl<Rx, const N: usize> ReaderPart<Rx, N>
+where
+    Rx: serial::Read<u8> + 'static,
+{
     pub fn buf(&self) -> &Vec<u8, N> {
         &self.buf
     }
@@ -34,11 +49,18 @@ where
         Self {
             rx,
             buf: Vec::new(),
+            classify: |_| None,
+            trace: TraceRing::new(),
         }
     }
 
     pub fn read_byte(&mut self) -> nb::Result<u8, crate::Error> {
-        self.rx.read().map_err(|x| x.map(|_| Error::ReadBuffer))
+        let classify = self.classify;
+        let byte = self.rx.read().map_err(|err| {
+            err.map(|err| classify(&err).map_or(Error::ReadBuffer, Error::Serial))
+        })?;
+        self.trace.write(byte);
+        Ok(byte)
     }
 
     pub fn read_bytes(&mut self) -> nb::Result<(), crate::Error> {
@@ -57,13 +79,38 @@ where
     }
 }
 
+/// Unconsumed part of the received data that should be returned by the next poll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PendingData {
+    pub link_id: u16,
+    pub remote: Option<SocketAddr>,
+    pub len: usize,
+}
+
+/// Place to store the unconsumed part of the received data.
+struct Remainder<'a> {
+    link_id: u16,
+    remote: Option<SocketAddr>,
+    slot: &'a mut Option<PendingData>,
+    /// Keep the unconsumed data even if it has not been consumed at all.
+    retain: bool,
+}
+
 /// Buffer with the incoming data received from the module over the serial port.
 ///
-/// A user should handle this data, otherwise, it will be discarded.
+/// A user should handle this data, otherwise, it will be discarded. If the received data
+/// has been partially consumed by the [`take`](Self::take) or [`read`](Self::read) methods,
+/// the remaining bytes will be returned by the next poll. See also
+/// [`NetworkSession::set_retain_unconsumed`](crate::NetworkSession::set_retain_unconsumed).
 pub struct ReadData<'a, const N: usize> {
     inner: &'a mut Vec<u8, N>,
     from: usize,
     to: usize,
+    pos: usize,
+    /// End of the bytes that will be removed from the buffer after dropping the command
+    /// response, the bytes after it are kept.
+    end: usize,
+    remainder: Option<Remainder<'a>>,
 }
 
 struct PrintAscii<'a>(&'a [u8]);
@@ -85,32 +132,178 @@ impl<'a, const N: usize> fmt::Debug for ReadData<'a, N> {
         f.debug_struct("ReadData")
             .field("from", &self.from)
             .field("to", &self.to)
+            .field("pos", &self.pos)
             .field("data", &PrintAscii(self.as_ref()))
             .finish()
     }
 }
 
 impl<'a, const N: usize> ReadData<'a, N> {
-    pub(crate) fn new(inner: &'a mut Vec<u8, N>) -> Self {
+    /// Creates a command response that occupies the buffer from the given position,
+    /// the response will be removed from the buffer after dropping.
+    pub(crate) fn new(inner: &'a mut Vec<u8, N>, from: usize) -> Self {
         let to = inner.len();
-        Self { inner, from: 0, to }
+        Self {
+            inner,
+            from,
+            to,
+            pos: from,
+            end: to,
+            remainder: None,
+        }
+    }
+
+    /// Creates a data received from the given link, the data occupies the first `len` bytes
+    /// of the buffer.
+    pub(crate) fn received(
+        inner: &'a mut Vec<u8, N>,
+        len: usize,
+        link_id: u16,
+        remote: Option<SocketAddr>,
+        slot: &'a mut Option<PendingData>,
+        retain: bool,
+    ) -> Self {
+        Self {
+            inner,
+            from: 0,
+            to: len,
+            pos: 0,
+            end: len,
+            remainder: Some(Remainder {
+                link_id,
+                remote,
+                slot,
+                retain,
+            }),
+        }
+    }
+
+    /// Creates a view of the first `len` bytes of the buffer, the bytes are kept in the buffer
+    /// after dropping.
+    pub(crate) fn peek(inner: &'a mut Vec<u8, N>, len: usize) -> Self {
+        Self {
+            inner,
+            from: 0,
+            to: len,
+            pos: 0,
+            end: 0,
+            remainder: None,
+        }
+    }
+
+    /// Narrows the data to the first `len` unconsumed bytes followed by the marker
+    /// of the given length, e.g. the `OK` terminator.
+    ///
+    /// Both the data and the marker will be removed from the buffer after dropping, but
+    /// the bytes after the marker are kept.
+    pub(crate) fn cut(&mut self, len: usize, marker_len: usize) {
+        self.from = self.pos;
+        self.to = self.pos + len;
+        self.end = self.to + marker_len;
+    }
+
+    /// Returns the number of the unconsumed bytes.
+    pub fn len(&self) -> usize {
+        self.to - self.pos
+    }
+
+    /// Returns `true` if there are no unconsumed bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Copies the unconsumed bytes into the given buffer without consuming them.
+    ///
+    /// Returns the number of bytes copied, which is less than [`len`](Self::len) if the
+    /// buffer is too small.
+    pub fn copy_to(&self, buf: &mut [u8]) -> usize {
+        let len = core::cmp::min(self.len(), buf.len());
+        buf[..len].copy_from_slice(&self.inner[self.pos..self.pos + len]);
+        len
     }
 
-    pub(crate) fn subslice(&mut self, from: usize, to: usize) {
-        self.from = from;
-        self.to = to;
+    /// Consumes up to `n` bytes of the data and returns them.
+    pub fn take(&mut self, n: usize) -> &[u8] {
+        let from = self.pos;
+        self.pos = core::cmp::min(from + n, self.to);
+        &self.inner[from..self.pos]
+    }
+
+    /// Consumes bytes of the data by copying them into the given buffer.
+    ///
+    /// Returns the number of bytes read.
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        let bytes = self.take(buf.len());
+        buf[..bytes.len()].copy_from_slice(bytes);
+        bytes.len()
+    }
+
+    /// Keeps the unconsumed bytes for the next poll even if none of them has been consumed.
+    pub(crate) fn retain(&mut self) {
+        if let Some(remainder) = self.remainder.as_mut() {
+            remainder.retain = true;
+        }
+    }
+
+    /// Discards the unconsumed bytes, they are not returned by the next poll even if the
+    /// retention of the unconsumed data is enabled.
+    pub fn discard(mut self) {
+        self.pos = self.to;
+    }
+
+    /// Returns the unconsumed bytes as a string, if they are a valid UTF-8.
+    pub fn as_str(&self) -> Result<&str, Utf8Error> {
+        core::str::from_utf8(self.as_ref())
+    }
+
+    /// Returns an iterator over the non-empty lines of the unconsumed bytes without
+    /// the line terminators.
+    pub fn lines(&self) -> Lines<'_> {
+        Lines::new(self.as_ref())
+    }
+
+    /// Returns the unconsumed bytes without the leading and trailing line terminators.
+    pub fn trim_crlf(&self) -> &[u8] {
+        let is_crlf = |byte: &u8| *byte == b'\r' || *byte == b'\n';
+
+        let bytes = self.as_ref();
+        let from = bytes.iter().position(|byte| !is_crlf(byte));
+        let to = bytes.iter().rposition(|byte| !is_crlf(byte));
+        match (from, to) {
+            (Some(from), Some(to)) => &bytes[from..=to],
+            _ => &[],
+        }
     }
 }
 
 impl<'a, const N: usize> AsRef<[u8]> for ReadData<'a, N> {
     fn as_ref(&self) -> &[u8] {
-        &self.inner[self.from..self.to]
+        &self.inner[self.pos..self.to]
     }
 }
 
 impl<'a, const N: usize> Drop for ReadData<'a, N> {
     fn drop(&mut self) {
-        self.inner.clear()
+        match self.remainder.take() {
+            // Keep the rest of the partially consumed data for the next poll.
+            Some(remainder) if self.pos < self.to && (self.pos > self.from || remainder.retain) => {
+                truncate_buf(self.inner, self.pos);
+                *remainder.slot = Some(PendingData {
+                    link_id: remainder.link_id,
+                    remote: remainder.remote,
+                    len: self.to - self.pos,
+                });
+            }
+            Some(_) => truncate_buf(self.inner, self.to),
+            None => remove_range(self.inner, self.from, self.end),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'a, const N: usize> Serialize for ReadData<'a, N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.as_ref())
     }
 }
 
@@ -118,6 +311,37 @@ impl<'a, const N: usize> Deref for ReadData<'a, N> {
     type Target = [u8];
 
     fn deref(&self) -> &Self::Target {
-        self.inner.as_ref()
+        self.as_ref()
+    }
+}
+
+/// Removes the given range of bytes from the buffer.
+///
+/// The range is clamped to the buffer length.
+pub(crate) fn remove_range<const N: usize>(buf: &mut Vec<u8, N>, from: usize, to: usize) {
+    let buf_len = buf.len();
+    let to = core::cmp::min(to, buf_len);
+    let from = core::cmp::min(from, to);
+
+    buf.copy_within(to..buf_len, from);
+    buf.truncate(buf_len - (to - from));
+}
+
+/// Removes the first `at` bytes from the buffer.
+// FIXME: Reduce complexity of this operation.
+pub(crate) fn truncate_buf<const N: usize>(buf: &mut Vec<u8, N>, at: usize) {
+    let buf_len = buf.len();
+    // The whole buffer is removed if it is shorter.
+    let at = core::cmp::min(at, buf_len);
+
+    for from in at..buf_len {
+        let to = from - at;
+        buf[to] = buf[from];
+    }
+
+    // Safety: `u8` is aprimitive type and doesn't have drop implementation so we can just
+    // modify the buffer length.
+    unsafe {
+        buf.set_len(buf_len - at);
     }
 }
@@ -0,0 +1,121 @@
+//! Automatic reconnection to the access point after the connection loss.
+
+use embedded_hal::serial;
+use simple_clock::SimpleClock;
+
+use crate::{parser::CommandResponse, Backoff, NetworkSession, OwnedJoinApConfig};
+
+/// Policy of the automatic reconnection to the access point after the connection loss.
+///
+/// See [`NetworkSession::set_reconnect_policy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    join_ap: OwnedJoinApConfig,
+    backoff: Backoff,
+    /// Time of the next reconnection attempt, if the connection has been lost.
+    next_attempt_at: Option<u64>,
+}
+
+impl ReconnectPolicy {
+    /// Creates a policy that joins the given access point again after the delays of the
+    /// given backoff.
+    ///
+    /// Use the [`Backoff::with_max_attempts`] to limit the number of the attempts.
+    pub fn new(join_ap: OwnedJoinApConfig, backoff: Backoff) -> Self {
+        Self {
+            join_ap,
+            backoff,
+            next_attempt_at: None,
+        }
+    }
+
+    /// Returns the access point to be joined again.
+    pub fn join_ap(&self) -> &OwnedJoinApConfig {
+        &self.join_ap
+    }
+
+    /// Schedules the next attempt, or gives up if the attempts are exhausted.
+    fn schedule<C: SimpleClock>(&mut self, clock: &C) {
+        let now = clock.now_us();
+        self.next_attempt_at = self
+            .backoff
+            .next_delay(clock)
+            .map(|delay| now.saturating_add(delay));
+    }
+
+    fn cancel(&mut self) {
+        self.next_attempt_at = None;
+        self.backoff.reset();
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Sets the policy of the automatic reconnection to the access point, or disables
+    /// the reconnection if `None`.
+    ///
+    /// Once the `WIFI DISCONNECT` notification is received by the network event polling,
+    /// the [`service_reconnect`](Self::service_reconnect) method joins the access point
+    /// again after the backoff delays until it succeeds or the attempts are exhausted.
+    /// The `WIFI CONNECTED` notification cancels the reconnection, e.g. if the module has
+    /// reconnected by itself.
+    pub fn set_reconnect_policy(&mut self, policy: Option<ReconnectPolicy>) {
+        self.reconnect_policy = policy;
+    }
+
+    /// Returns `true` if the connection has been lost and the next reconnection attempt
+    /// is scheduled.
+    pub fn is_reconnecting(&self) -> bool {
+        matches!(&self.reconnect_policy, Some(policy) if policy.next_attempt_at.is_some())
+    }
+
+    /// Updates the reconnection state according to the WiFi notification.
+    pub(crate) fn track_wifi_state(&mut self, response: &CommandResponse) {
+        let policy = match self.reconnect_policy.as_mut() {
+            Some(policy) => policy,
+            None => return,
+        };
+
+        match response {
+            // The notification may be prepared again after it has been kept for the next poll.
+            CommandResponse::WifiDisconnect if policy.next_attempt_at.is_none() => {
+                policy.schedule(&self.module.clock)
+            }
+            CommandResponse::WifiConnected | CommandResponse::WifiGotIp => policy.cancel(),
+            _ => {}
+        }
+    }
+
+    /// Joins the access point again if the reconnection attempt is due, it should be
+    /// called periodically from the application loop.
+    ///
+    /// Returns `true` if the access point has been joined by this call. The failed attempt
+    /// schedules the next one according to the [policy](Self::set_reconnect_policy).
+    ///
+    /// # Notes
+    ///
+    /// The attempt blocks until the module responds, which may take several seconds.
+    pub fn service_reconnect(&mut self) -> bool {
+        let now = self.clock().now_us();
+        let mut policy = match self.reconnect_policy.take() {
+            Some(policy) if matches!(policy.next_attempt_at, Some(at) if at <= now) => policy,
+            other => {
+                self.reconnect_policy = other;
+                return false;
+            }
+        };
+
+        let joined = policy.join_ap.as_config().join_ap(&mut self.module).is_ok();
+        if joined {
+            policy.cancel();
+        } else {
+            policy.schedule(&self.module.clock);
+        }
+        self.reconnect_policy = Some(policy);
+        joined
+    }
+}
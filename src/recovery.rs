@@ -0,0 +1,90 @@
+//! Recovery of the session after an unexpected module reboot.
+
+use embedded_hal::serial;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use simple_clock::SimpleClock;
+
+use crate::{NetworkSession, OwnedJoinApConfig, OwnedSoftApConfig};
+
+/// Configuration that is restored after an unexpected module reboot, e.g. due to
+/// a brownout.
+///
+/// See [`NetworkSession::set_reboot_recovery`].
+#[derive(Debug, Default, PartialEq, Clone, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RebootRecovery {
+    /// Software access point to be started again.
+    pub softap: Option<OwnedSoftApConfig>,
+    /// Access point to be joined again.
+    pub join_ap: Option<OwnedJoinApConfig>,
+    /// Maximum number of the simultaneous incoming connections.
+    pub max_connections: Option<u8>,
+    /// Port of the TCP server to be started again.
+    pub listen_port: Option<u16>,
+}
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Sets the configuration to be restored after an unexpected module reboot, or disables
+    /// the restoring if `None`.
+    ///
+    /// The reboot is detected by the network event polling, which reports it as the
+    /// [`NetworkEvent::ModuleRebooted`](crate::NetworkEvent::ModuleRebooted) event. The
+    /// configuration is restored by the [`restore_after_reboot`](Self::restore_after_reboot)
+    /// call after this event.
+    pub fn set_reboot_recovery(&mut self, recovery: Option<RebootRecovery>) {
+        self.reboot_recovery = recovery;
+    }
+
+    /// Restores the module state after the reboot: disables the command echo and replays
+    /// the [recovery](Self::set_reboot_recovery) configuration.
+    ///
+    /// Returns `false` if there is no recovery configuration.
+    ///
+    /// # Notes
+    ///
+    /// This method blocks until the access point is joined again, which may take several
+    /// seconds, so it is not called by the network event polling automatically. The failed
+    /// restoring can be retried by the next call.
+    pub fn restore_after_reboot(&mut self) -> crate::Result<bool> {
+        self.module.disable_echo()?;
+
+        let recovery = match self.reboot_recovery.take() {
+            Some(recovery) => recovery,
+            None => return Ok(false),
+        };
+        let res = self.replay_recovery(&recovery);
+        self.reboot_recovery = Some(recovery);
+        res.map(|_| true)
+    }
+
+    fn replay_recovery(&mut self, recovery: &RebootRecovery) -> crate::Result<()> {
+        match (&recovery.softap, &recovery.join_ap) {
+            (Some(softap), join_ap) => {
+                softap.as_config().init(&mut self.module)?;
+                if let Some(join_ap) = join_ap {
+                    join_ap.as_config().join_ap(&mut self.module)?;
+                }
+            }
+            (None, Some(join_ap)) => {
+                let config = join_ap.as_config();
+                config.init(&mut self.module)?;
+                config.join_ap(&mut self.module)?;
+            }
+            (None, None) => {}
+        }
+
+        if let Some(count) = recovery.max_connections {
+            self.set_max_connections(count)?;
+        }
+        if let Some(port) = recovery.listen_port {
+            self.listen(port)?;
+        }
+        Ok(())
+    }
+}
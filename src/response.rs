@@ -0,0 +1,114 @@
+//! Response to the sent AT command.
+
+use core::{fmt, ops::Deref, str::Utf8Error};
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer};
+
+use crate::{parser::find_line_value, ReadData};
+
+/// Response to an AT command, i.e. the bytes received before the terminator like `OK`
+/// or `ERROR`.
+///
+/// The response is removed from the reader buffer after dropping.
+pub struct Response<'a, const N: usize> {
+    data: ReadData<'a, N>,
+}
+
+impl<'a, const N: usize> Response<'a, N> {
+    pub(crate) fn new(data: ReadData<'a, N>) -> Self {
+        Self { data }
+    }
+
+    /// Returns the raw response bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+
+    /// Returns the response as a string, if it's a valid UTF-8.
+    pub fn as_str(&self) -> Result<&str, Utf8Error> {
+        self.data.as_str()
+    }
+
+    /// Returns an iterator over the non-empty response lines without the line terminators.
+    pub fn lines(&self) -> Lines<'_> {
+        self.data.lines()
+    }
+
+    /// Returns the response without the leading and trailing line terminators.
+    pub fn trim_crlf(&self) -> &[u8] {
+        self.data.trim_crlf()
+    }
+
+    /// Returns the rest of the first line that starts with the given prefix, e.g. the
+    /// `"ssid","bssid",1,-60` value of the `+CWJAP:` line.
+    pub fn find_prefix(&self, prefix: &str) -> Option<&[u8]> {
+        find_line_value(self.as_bytes(), prefix)
+    }
+
+    /// Converts the response into the underlying data.
+    pub fn into_data(self) -> ReadData<'a, N> {
+        self.data
+    }
+}
+
+impl<'a, const N: usize> AsRef<[u8]> for Response<'a, N> {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl<'a, const N: usize> Deref for Response<'a, N> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        self.as_bytes()
+    }
+}
+
+impl<'a, const N: usize> fmt::Debug for Response<'a, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Response").field(&self.data).finish()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'a, const N: usize> Serialize for Response<'a, N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.data.serialize(serializer)
+    }
+}
+
+/// An iterator over the non-empty lines of the module output.
+///
+/// This struct is created by the [`Response::lines`] and [`ReadData::lines`] methods.
+#[derive(Debug, Clone)]
+pub struct Lines<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Lines<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { rest: bytes }
+    }
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.rest.is_empty() {
+            let (line, rest) = match self.rest.iter().position(|&byte| byte == b'\n') {
+                Some(pos) => (&self.rest[..pos], &self.rest[pos + 1..]),
+                None => (self.rest, &[][..]),
+            };
+            self.rest = rest;
+
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if !line.is_empty() {
+                return Some(line);
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,54 @@
+//! RF TX power configuration.
+
+use embedded_hal::serial;
+use simple_clock::SimpleClock;
+
+use crate::{Dialect, Error, Module, NetworkSession};
+
+/// Minimum RF TX power in 0.25 dBm units accepted by the ESP-AT firmware.
+const ESP_AT_MIN_RF_POWER: u8 = 40;
+
+impl<Rx, Tx, C, const N: usize> Module<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Sets the maximum WiFi RF TX power in dBm by the `AT+RFPOWER` command.
+    ///
+    /// # Notes
+    ///
+    /// The allowed range depends on the firmware and the [chip](Self::chip): from 0 to 20 dBm
+    /// for the NonOS firmware, and from 10 dBm to 20 dBm for the esp8266 chip or to 21 dBm
+    /// for the ESP32-series chips for the ESP-AT firmware. Other values are rejected with
+    /// [`Error::InvalidArgument`].
+    pub fn set_tx_power(&mut self, dbm: u8) -> crate::Result<()> {
+        let min_power = match self.dialect() {
+            Dialect::NonOs => 0,
+            Dialect::EspAt => ESP_AT_MIN_RF_POWER,
+        };
+        let power = dbm
+            .checked_mul(4)
+            .filter(|power| (min_power..=self.chip.max_rf_power()).contains(power))
+            .ok_or(Error::InvalidArgument)?;
+
+        self.send_at_command(format_args!("AT+RFPOWER={}", power))?
+            .map_err(|_| Error::CommandFailed)?;
+
+        Ok(())
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Sets the maximum WiFi RF TX power in dBm.
+    ///
+    /// See [`Module::set_tx_power`] for details.
+    pub fn set_tx_power(&mut self, dbm: u8) -> crate::Result<()> {
+        self.module.set_tx_power(dbm)
+    }
+}
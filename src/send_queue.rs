@@ -0,0 +1,68 @@
+//! Queue of the outgoing data segments.
+
+use heapless::Vec;
+
+use crate::Error;
+
+/// Maximum number of the queued segments.
+pub(crate) const MAX_QUEUED_SEGMENTS: usize = 4;
+/// Maximum length of the queued segment in bytes.
+pub const QUEUED_SEGMENT_MAX_LEN: usize = 128;
+
+/// Data segment to be sent via the link.
+#[derive(Debug)]
+pub(crate) struct Segment {
+    pub link_id: usize,
+    pub data: Vec<u8, QUEUED_SEGMENT_MAX_LEN>,
+}
+
+/// Outgoing data that is sent when the line is free, the segments of the high-priority link
+/// are sent first.
+#[derive(Debug, Default)]
+pub(crate) struct SendQueue {
+    segments: Vec<Segment, MAX_QUEUED_SEGMENTS>,
+    priority_link: Option<usize>,
+}
+
+impl SendQueue {
+    /// Splits the bytes into segments and enqueues them, either all the bytes are
+    /// enqueued or none of them.
+    pub fn push(&mut self, link_id: usize, bytes: &[u8]) -> crate::Result<()> {
+        let count = bytes.chunks(QUEUED_SEGMENT_MAX_LEN).len();
+        if self.segments.len() + count > self.segments.capacity() {
+            return Err(Error::BufferFull);
+        }
+
+        for chunk in bytes.chunks(QUEUED_SEGMENT_MAX_LEN) {
+            let segment = Segment {
+                link_id,
+                data: Vec::from_slice(chunk).map_err(|_| Error::BufferFull)?,
+            };
+            self.segments.push(segment).ok();
+        }
+        Ok(())
+    }
+
+    /// Removes the next segment to be sent.
+    pub fn pop(&mut self) -> Option<Segment> {
+        let index = self
+            .segments
+            .iter()
+            .position(|segment| Some(segment.link_id) == self.priority_link)
+            .unwrap_or(0);
+        if index < self.segments.len() {
+            Some(self.segments.remove(index))
+        } else {
+            None
+        }
+    }
+
+    /// Discards the queued segments of the given link.
+    pub fn remove_link(&mut self, link_id: usize) {
+        self.segments.retain(|segment| segment.link_id != link_id);
+    }
+
+    pub fn set_priority_link(&mut self, link_id: Option<usize>) {
+        self.priority_link = link_id;
+    }
+}
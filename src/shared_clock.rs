@@ -0,0 +1,37 @@
+//! Clock that is shared by reference.
+
+use simple_clock::SimpleClock;
+
+/// Handle to a clock owned elsewhere, so a single hardware timer can be used by the several
+/// modules and by the rest of the firmware at the same time.
+///
+/// Clocks that implement [`Clone`] can be shared by cloning them instead.
+#[derive(Debug)]
+pub struct SharedClock<'a, C: ?Sized>(pub &'a C);
+
+impl<'a, C: ?Sized> SharedClock<'a, C> {
+    /// Creates a new handle to the given clock.
+    pub fn new(clock: &'a C) -> Self {
+        Self(clock)
+    }
+}
+
+impl<C: ?Sized> Clone for SharedClock<'_, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C: ?Sized> Copy for SharedClock<'_, C> {}
+
+impl<'a, C: ?Sized> From<&'a C> for SharedClock<'a, C> {
+    fn from(clock: &'a C) -> Self {
+        Self(clock)
+    }
+}
+
+impl<C: SimpleClock + ?Sized> SimpleClock for SharedClock<'_, C> {
+    fn now_us(&self) -> u64 {
+        self.0.now_us()
+    }
+}
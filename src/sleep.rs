@@ -0,0 +1,137 @@
+//! Power saving configuration.
+
+use embedded_hal::serial;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use simple_clock::SimpleClock;
+
+use crate::{error::ensure, Capabilities, Error, Module, NetworkSession};
+
+/// Sleep mode of the module.
+///
+/// The module keeps the access point association in all of the sleep modes.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SleepMode {
+    /// The module never sleeps.
+    Disabled,
+    /// The RF module is turned off between the DTIM beacon intervals.
+    ModemSleep,
+    /// The CPU is suspended in addition to the RF module until the wakeup event.
+    LightSleep,
+}
+
+impl From<SleepMode> for u8 {
+    fn from(mode: SleepMode) -> Self {
+        match mode {
+            SleepMode::Disabled => 0,
+            SleepMode::ModemSleep => 1,
+            SleepMode::LightSleep => 2,
+        }
+    }
+}
+
+/// Configuration of the light-sleep wakeup by the GPIO pin.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GpioWakeup {
+    /// Number of the GPIO pin that wakes up the module.
+    pub gpio: u8,
+    /// Whether the module wakes up on the high level of the pin instead of the low one.
+    pub active_high: bool,
+}
+
+impl<Rx, Tx, C, const N: usize> Module<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Sets the sleep mode of the module.
+    ///
+    /// # Notes
+    ///
+    /// In the [light-sleep](SleepMode::LightSleep) mode the module doesn't respond to the
+    /// commands until it is woken up, so the wakeup source should be configured in advance
+    /// by the [`set_gpio_wakeup`](Self::set_gpio_wakeup).
+    pub fn set_sleep_mode(&mut self, mode: SleepMode) -> crate::Result<()> {
+        self.send_at_command(format_args!("AT+SLEEP={}", u8::from(mode)))?
+            .map_err(|_| Error::CommandFailed)?;
+
+        Ok(())
+    }
+
+    /// Configures the module to wake up from the light-sleep by the GPIO pin.
+    ///
+    /// # Notes
+    ///
+    /// The allowed GPIO numbers depend on the [chip](Self::chip), e.g. the range from 0 to 15
+    /// for the esp8266 chip, other numbers are rejected with [`Error::InvalidArgument`].
+    /// Returns [`Error::Unsupported`] if the module firmware doesn't support the wakeup
+    /// configuration.
+    pub fn set_gpio_wakeup(&mut self, wakeup: GpioWakeup) -> crate::Result<()> {
+        self.require(Capabilities::SLEEP_WAKEUP)?;
+        if wakeup.gpio > self.chip.max_wakeup_gpio() {
+            return Err(Error::InvalidArgument);
+        }
+
+        self.send_at_command(format_args!(
+            "AT+SLEEPWKCFG=2,{},{}",
+            wakeup.gpio, wakeup.active_high as u8
+        ))?
+        .map_err(|_| Error::CommandFailed)?;
+
+        Ok(())
+    }
+
+    /// Puts the module into the deep-sleep for the given duration in microseconds by
+    /// the `AT+GSLP` command.
+    ///
+    /// # Notes
+    ///
+    /// The duration is rounded down to milliseconds, the zero duration means sleeping until
+    /// the external reset. The module wakes up only if its `XPD_DCDC` pin is connected to
+    /// the `EXT_RSTB` one, and it wakes up by the reset, so all the links and the current
+    /// configuration are lost. The session reports the wakeup by the
+    /// [`ModuleRebooted`](crate::NetworkEvent::ModuleRebooted) event.
+    pub fn deep_sleep(&mut self, duration_us: u64) -> crate::Result<()> {
+        let duration_ms = duration_us / 1_000;
+        ensure!(
+            duration_ms <= u64::from(u32::MAX),
+            "Deep-sleep duration should fit into 32 bits in milliseconds"
+        );
+
+        self.send_at_command(format_args!("AT+GSLP={}", duration_ms))?
+            .map_err(|_| Error::CommandFailed)?;
+
+        Ok(())
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Sets the sleep mode of the module.
+    ///
+    /// See [`Module::set_sleep_mode`] for details.
+    pub fn set_sleep_mode(&mut self, mode: SleepMode) -> crate::Result<()> {
+        self.module.set_sleep_mode(mode)
+    }
+
+    /// Configures the module to wake up from the light-sleep by the GPIO pin.
+    ///
+    /// See [`Module::set_gpio_wakeup`] for details.
+    pub fn set_gpio_wakeup(&mut self, wakeup: GpioWakeup) -> crate::Result<()> {
+        self.module.set_gpio_wakeup(wakeup)
+    }
+
+    /// Puts the module into the deep-sleep for the given duration in microseconds.
+    ///
+    /// See [`Module::deep_sleep`] for details.
+    pub fn deep_sleep(&mut self, duration_us: u64) -> crate::Result<()> {
+        self.module.deep_sleep(duration_us)
+    }
+}
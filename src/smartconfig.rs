@@ -0,0 +1,129 @@
+//! WiFi credentials provisioning by the SmartConfig protocols.
+
+use core::convert::TryFrom;
+
+use embedded_hal::serial;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use simple_clock::SimpleClock;
+
+use crate::{
+    module::{find_line, Condition},
+    parser::find_line_value,
+    reader_part::ReadData,
+    softap::init_station,
+    CredentialStore, Error, JoinApConfig, Module, NetworkSession, OwnedJoinApConfig,
+};
+
+/// Protocol used by the phone application to send the credentials.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SmartConfigProtocol {
+    /// Espressif ESP-TOUCH protocol.
+    EspTouch = 1,
+    /// WeChat AirKiss protocol.
+    AirKiss = 2,
+    /// Both ESP-TOUCH and AirKiss protocols.
+    EspTouchAirKiss = 3,
+}
+
+/// Notification that marks the end of the SmartConfig, the credentials are printed before it.
+#[derive(Clone, Copy)]
+struct SmartConfigDone;
+
+impl SmartConfigDone {
+    const MSG: &'static [u8] = b"smartconfig connected wifi\r\n";
+}
+
+impl<'a, const N: usize> Condition<'a, N> for SmartConfigDone {
+    type Output = Option<OwnedJoinApConfig>;
+
+    fn is_performed(self, buf: &[u8]) -> bool {
+        find_line(buf, Self::MSG).is_some()
+    }
+
+    fn output(self, buf: ReadData<'a, N>) -> Self::Output {
+        let ssid = core::str::from_utf8(find_line_value(&buf, "ssid:")?).ok()?;
+        let password = core::str::from_utf8(find_line_value(&buf, "password:")?).ok()?;
+        OwnedJoinApConfig::try_from(JoinApConfig {
+            ssid,
+            password: Some(password).filter(|password| !password.is_empty()),
+            options: None,
+        })
+        .ok()
+    }
+}
+
+/// Provisioning flow that receives the WiFi credentials from a phone application by
+/// the SmartConfig protocol.
+///
+/// The module listens to the specially encoded packets of the phone connected to the target
+/// access point, so the device needs neither its own access point nor any input. Once
+/// the module has joined the access point, the credentials are saved to the store and
+/// the joined session is returned.
+#[derive(Debug, Clone, Copy)]
+pub struct SmartConfig {
+    protocol: SmartConfigProtocol,
+    timeout_us: Option<u64>,
+}
+
+impl SmartConfig {
+    /// Creates a new SmartConfig flow with the given protocol, which waits for the
+    /// credentials infinitely.
+    pub fn new(protocol: SmartConfigProtocol) -> Self {
+        Self {
+            protocol,
+            timeout_us: None,
+        }
+    }
+
+    /// Sets the maximum time in microseconds to wait for the credentials, the
+    /// [`Error::Timeout`] error is returned after it.
+    pub fn timeout(mut self, us: Option<u64>) -> Self {
+        self.timeout_us = us;
+        self
+    }
+
+    /// Runs the SmartConfig until the module joins the access point.
+    ///
+    /// # Notes
+    ///
+    /// The module operation timeout is not applied while waiting for the credentials.
+    pub fn run<Rx, Tx, C, S, const N: usize>(
+        &self,
+        mut module: Module<Rx, Tx, C, N>,
+        store: &mut S,
+    ) -> crate::Result<NetworkSession<Rx, Tx, C, N>>
+    where
+        Rx: serial::Read<u8> + 'static,
+        Tx: serial::Write<u8> + 'static,
+        C: SimpleClock,
+        S: CredentialStore,
+    {
+        // The SmartConfig is available only in the station mode.
+        init_station(&mut module)?;
+
+        module
+            .send_at_command(format_args!("AT+CWSTARTSMART={}", self.protocol as u8))?
+            .map_err(|_| Error::CommandFailed)?;
+
+        let timeout = module.timeout();
+        module.set_timeout(self.timeout_us);
+        // The notifications may have been received together with the command response.
+        let mut kept = 0;
+        let received = module
+            .wait_until(SmartConfigDone, &mut kept)
+            .map(|_| SmartConfigDone.output(ReadData::new(module.reader.buf_mut(), kept)));
+        module.set_timeout(timeout);
+
+        // The SmartConfig should be stopped in any case to release the module memory.
+        let stopped = module
+            .send_at_command("AT+CWSTOPSMART")
+            .and_then(|resp| resp.map(drop).map_err(|_| Error::CommandFailed));
+        let config = received?.ok_or(Error::CommandFailed)?;
+        stopped?;
+
+        store.save(&config.as_config())?;
+        Ok(NetworkSession::new(module))
+    }
+}
@@ -0,0 +1,110 @@
+//! Network time synchronization by the SNTP client of the module.
+
+use embedded_hal::serial;
+use simple_clock::SimpleClock;
+
+use crate::{
+    error::ensure, module::Escaped, parser::find_line_value, Capabilities, Error, Module,
+    NetworkSession,
+};
+
+/// Maximum number of the SNTP servers supported by the module.
+const MAX_SNTP_SERVERS: usize = 3;
+
+impl<Rx, Tx, C, const N: usize> Module<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Enables the SNTP client of the module with the given time zone offset in hours
+    /// and up to three servers, e.g. `pool.ntp.org`.
+    ///
+    /// The time zone should be zero to get the UTC time from the [`sntp_time`](Self::sntp_time).
+    pub fn set_sntp_config(&mut self, timezone: i8, servers: &[&str]) -> crate::Result<()> {
+        ensure!(
+            servers.len() <= MAX_SNTP_SERVERS,
+            "The module supports no more than 3 SNTP servers"
+        );
+        self.require(Capabilities::SNTP)?;
+
+        let mut servers = servers.iter().map(|server| Escaped(server));
+        let mut next = || servers.next().unwrap_or(Escaped(""));
+        let (first, second, third) = (next(), next(), next());
+        self.send_at_command(format_args!(
+            "AT+CIPSNTPCFG=1,{},\"{}\",\"{}\",\"{}\"",
+            timezone, first, second, third
+        ))?
+        .map_err(|_| Error::CommandFailed)?;
+        Ok(())
+    }
+
+    /// Returns the time obtained by the SNTP client as the number of seconds since the Unix
+    /// epoch, or `None` if the time has not been synchronized yet.
+    pub fn sntp_time(&mut self) -> crate::Result<Option<u64>> {
+        self.require(Capabilities::SNTP)?;
+
+        let time = self.query("AT+CIPSNTPTIME?", |resp| {
+            let time = find_line_value(resp, "+CIPSNTPTIME:")?;
+            parse_asctime(core::str::from_utf8(time).ok()?)
+        })?;
+        // The module reports the epoch start until the time is synchronized.
+        Ok(time.filter(|&time| time > 0))
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Enables the SNTP client of the module.
+    ///
+    /// See [`Module::set_sntp_config`].
+    pub fn set_sntp_config(&mut self, timezone: i8, servers: &[&str]) -> crate::Result<()> {
+        self.module.set_sntp_config(timezone, servers)
+    }
+
+    /// Returns the time obtained by the SNTP client.
+    ///
+    /// See [`Module::sntp_time`].
+    pub fn sntp_time(&mut self) -> crate::Result<Option<u64>> {
+        self.module.sntp_time()
+    }
+}
+
+/// Parses the time in the `Thu Jan 01 00:00:00 1970` form into the number of seconds since
+/// the Unix epoch.
+pub(crate) fn parse_asctime(s: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let mut parts = s.split_whitespace().skip(1);
+    let month = parts.next()?;
+    let month = MONTHS.iter().position(|name| *name == month)? as u64 + 1;
+    let day: u64 = parts.next()?.parse().ok()?;
+    let mut time = parts.next()?.split(':');
+    let year: u64 = parts.next()?.parse().ok()?;
+
+    let mut next = || -> Option<u64> { time.next()?.parse().ok() };
+    let (hours, minutes, seconds) = (next()?, next()?, next()?);
+    if year < 1970 || !(1..=31).contains(&day) || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+
+    Some(days_from_civil(year, month, day) * 86_400 + hours * 3_600 + minutes * 60 + seconds)
+}
+
+/// Returns the number of days since the Unix epoch for the given date.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    // The year starts in March, so the leap day is the last one.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    // The number of days between 0000-03-01 and 1970-01-01.
+    era * 146_097 + day_of_era - 719_468
+}
@@ -0,0 +1,177 @@
+//! Blocking I/O traits of the `embedded-io` crate over the network session links.
+
+use embedded_hal::serial;
+use embedded_io::{ErrorKind, ErrorType, Read, Write};
+use simple_clock::SimpleClock;
+
+use crate::{buffered::SEND_MAX_LEN, Error, NetworkEvent, NetworkSession};
+
+impl embedded_io::Error for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Timeout => ErrorKind::TimedOut,
+            Error::ConnectionClosed => ErrorKind::BrokenPipe,
+            Error::BufferFull => ErrorKind::OutOfMemory,
+            Error::InvalidArgument | Error::InvalidConfig(_) => ErrorKind::InvalidInput,
+            Error::MalformedMessage => ErrorKind::InvalidData,
+            Error::Unsupported => ErrorKind::Unsupported,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// Handle of the connected link that implements the [`Read`] and [`Write`] traits, so
+/// the protocol crates built on top of the `embedded-io` can use the link directly.
+///
+/// The reads block until data is received, the link is closed or the operation
+/// [timeout](NetworkSession::timeout) is reached. The end of the stream is reported
+/// once the link has been closed. The writes send up to 2048 bytes at a time by the
+/// [`send`](NetworkSession::send) method.
+///
+/// The handle polls the network events by itself and discards the events of the other
+/// links, so it's intended for the sessions with a single active link.
+///
+/// # Example
+///
+/// ```ignore
+/// session.connect(0, server_address)?;
+/// let mut socket = session.socket_handle(0);
+/// socket.write_all(b"GET / HTTP/1.0\r\n\r\n")?;
+/// let len = socket.read(&mut response)?;
+/// ```
+pub struct SocketHandle<'a, Rx, Tx, C, const N: usize>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    session: &'a mut NetworkSession<Rx, Tx, C, N>,
+    link_id: usize,
+    /// The link has been closed.
+    eof: bool,
+}
+
+impl<'a, Rx, Tx, C, const N: usize> SocketHandle<'a, Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Creates a new handle of the link with the given identifier.
+    pub fn new(session: &'a mut NetworkSession<Rx, Tx, C, N>, link_id: usize) -> Self {
+        Self {
+            session,
+            link_id,
+            eof: false,
+        }
+    }
+
+    /// Returns the link identifier.
+    pub fn link_id(&self) -> usize {
+        self.link_id
+    }
+
+    /// Returns `true` if the link has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.eof
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> ErrorType for SocketHandle<'_, Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    type Error = Error;
+}
+
+impl<Rx, Tx, C, const N: usize> Read for SocketHandle<'_, Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Reads the received data into the given buffer, the rest of the received segment
+    /// is returned by the next reads.
+    ///
+    /// Returns zero if the link has been closed.
+    fn read(&mut self, buf: &mut [u8]) -> crate::Result<usize> {
+        if buf.is_empty() || self.eof {
+            return Ok(0);
+        }
+
+        let timeout = self.session.timeout();
+        let started_at = self.session.clock().now_us();
+        loop {
+            match self.session.poll_network_event() {
+                Ok(NetworkEvent::DataAvailable {
+                    link_id, mut data, ..
+                }) if usize::from(link_id) == self.link_id => match data.read(buf) {
+                    0 => {}
+                    len => return Ok(len),
+                },
+                Ok(NetworkEvent::Closed { link_id, .. })
+                    if usize::from(link_id) == self.link_id =>
+                {
+                    self.eof = true;
+                    return Ok(0);
+                }
+                Ok(NetworkEvent::ModuleRebooted) => {
+                    self.eof = true;
+                    return Ok(0);
+                }
+                // The data of the other links is discarded, otherwise the retained data would
+                // block the link forever.
+                Ok(NetworkEvent::DataAvailable { data, .. }) => data.discard(),
+                Ok(_) | Err(nb::Error::WouldBlock) => {}
+                Err(nb::Error::Other(err)) => return Err(err),
+            }
+
+            let elapsed = self.session.clock().now_us().saturating_sub(started_at);
+            if matches!(timeout, Some(timeout) if elapsed >= timeout) {
+                return Err(Error::Timeout);
+            }
+        }
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> Write for SocketHandle<'_, Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Sends up to 2048 bytes of the given buffer by a single command.
+    ///
+    /// Returns the [`Error::ConnectionClosed`] error if the link has been closed.
+    fn write(&mut self, buf: &[u8]) -> crate::Result<usize> {
+        if self.eof {
+            return Err(Error::ConnectionClosed);
+        }
+
+        let len = core::cmp::min(buf.len(), SEND_MAX_LEN);
+        if len > 0 {
+            self.session
+                .send(self.link_id, buf[..len].iter().copied())?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> crate::Result<()> {
+        self.session.flush()
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Creates a handle of the link with the given identifier that implements the
+    /// `embedded-io` traits.
+    pub fn socket_handle(&mut self, link_id: usize) -> SocketHandle<'_, Rx, Tx, C, N> {
+        SocketHandle::new(self, link_id)
+    }
+}
@@ -1,27 +1,92 @@
-use core::{fmt::Debug, format_args};
+use core::{convert::TryFrom, fmt::Debug, format_args};
 
 use embedded_hal::serial;
+use heapless::String;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use simple_clock::SimpleClock;
 
-use crate::{Error, Module, NetworkSession};
+use crate::{
+    ipv4::{format_ipv4, IPV4_MAX_LEN},
+    mac::MacParam,
+    module::{Escaped, ExpectOk},
+    net::Ipv4Addr,
+    Backoff, ConfigError, Dialect, Error, Module, NetworkSession,
+};
+
+/// Maximum length of the access point SSID in bytes.
+pub const SSID_MAX_LEN: usize = 32;
+/// Maximum length of the access point password in bytes.
+pub const PASSWORD_MAX_LEN: usize = 64;
+/// Minimum length of the password in WPA modes.
+const MIN_WPA_PASSWORD_LEN: usize = 8;
 
 /// WiFi modes that supported by this module.
-#[repr(u8)]
-#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, Eq)]
+///
+/// Encryption codes that are unknown to this crate are represented by the
+/// [`Unknown`](WifiMode::Unknown) variant.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum WifiMode {
     /// Open network mode without any encryption.
-    Open = 0,
+    Open,
+    /// WEP encryption mode.
+    Wep,
     /// WPA PSK encryption mode.
-    WpaPsk = 2,
+    WpaPsk,
     /// WPA2 PSK encryption mode.
-    Wpa2Psk = 3,
+    Wpa2Psk,
     /// Both WPA PSK and WPA2 PSK encryption modes.
-    WpaWpa2Psk = 4,
+    WpaWpa2Psk,
+    /// WPA2 Enterprise encryption mode.
+    Wpa2Enterprise,
+    /// WPA3 PSK encryption mode.
+    Wpa3Psk,
+    /// Both WPA2 PSK and WPA3 PSK encryption modes.
+    Wpa2Wpa3Psk,
+    /// WAPI PSK encryption mode.
+    WapiPsk,
+    /// Encryption mode that is unknown to this crate.
+    Unknown(u8),
+}
+
+impl From<u8> for WifiMode {
+    fn from(code: u8) -> Self {
+        match code {
+            0 => Self::Open,
+            1 => Self::Wep,
+            2 => Self::WpaPsk,
+            3 => Self::Wpa2Psk,
+            4 => Self::WpaWpa2Psk,
+            5 => Self::Wpa2Enterprise,
+            6 => Self::Wpa3Psk,
+            7 => Self::Wpa2Wpa3Psk,
+            8 => Self::WapiPsk,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+impl From<WifiMode> for u8 {
+    fn from(mode: WifiMode) -> Self {
+        match mode {
+            WifiMode::Open => 0,
+            WifiMode::Wep => 1,
+            WifiMode::WpaPsk => 2,
+            WifiMode::Wpa2Psk => 3,
+            WifiMode::WpaWpa2Psk => 4,
+            WifiMode::Wpa2Enterprise => 5,
+            WifiMode::Wpa3Psk => 6,
+            WifiMode::Wpa2Wpa3Psk => 7,
+            WifiMode::WapiPsk => 8,
+            WifiMode::Unknown(other) => other,
+        }
+    }
 }
 
 /// Software access point configuration parameters.
-#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, Eq)]
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SoftApConfig<'a> {
     /// Access point SSID.
     pub ssid: &'a str,
@@ -33,9 +98,66 @@ pub struct SoftApConfig<'a> {
     pub channel: u8,
     /// WiFi mode.
     pub mode: WifiMode,
+    /// Address of the access point, the module default `192.168.4.1` is used if it is `None`.
+    pub address: Option<SoftApAddress>,
+}
+
+/// Address of the software access point in its own network.
+///
+/// It should be changed if the default `192.168.4.0/24` network collides with the network
+/// of the joined access point.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SoftApAddress {
+    /// IP address of the access point.
+    #[cfg_attr(feature = "serde", serde(with = "crate::ipv4::serde_ipv4"))]
+    pub ip: Ipv4Addr,
+    /// Gateway address of the access point network.
+    #[cfg_attr(feature = "serde", serde(with = "crate::ipv4::serde_ipv4"))]
+    pub gateway: Ipv4Addr,
+    /// Network mask of the access point network.
+    #[cfg_attr(feature = "serde", serde(with = "crate::ipv4::serde_ipv4"))]
+    pub netmask: Ipv4Addr,
 }
 
 impl<'a> SoftApConfig<'a> {
+    /// Default channel number of the software access point.
+    pub const DEFAULT_CHANNEL: u8 = 1;
+
+    /// Creates a builder of the software access point configuration.
+    ///
+    /// By default, the access point uses the [`DEFAULT_CHANNEL`](Self::DEFAULT_CHANNEL)
+    /// and the open WiFi mode.
+    pub fn builder() -> SoftApConfigBuilder<'a, ()> {
+        SoftApConfigBuilder {
+            ssid: (),
+            password: "",
+            channel: Self::DEFAULT_CHANNEL,
+            mode: WifiMode::Open,
+            address: None,
+        }
+    }
+
+    /// Checks that the configuration parameters are acceptable by the module.
+    pub fn validate(&self) -> crate::Result<()> {
+        validate_ssid(self.ssid)?;
+        if matches!(
+            self.mode,
+            WifiMode::Wep | WifiMode::Wpa2Enterprise | WifiMode::Unknown(_)
+        ) {
+            return Err(Error::InvalidConfig(ConfigError::UnsupportedMode));
+        }
+        if self.mode != WifiMode::Open
+            && !(MIN_WPA_PASSWORD_LEN..=PASSWORD_MAX_LEN).contains(&self.password.len())
+        {
+            return Err(Error::InvalidConfig(ConfigError::InvalidPassword));
+        }
+        if !(1..=14).contains(&self.channel) {
+            return Err(Error::InvalidConfig(ConfigError::InvalidChannel));
+        }
+        Ok(())
+    }
+
     /// Creates a software access point with the configuration parameters and establishes
     /// a new WiFi session.
     pub fn start<Rx, Tx, C, const N: usize>(
@@ -51,7 +173,7 @@ impl<'a> SoftApConfig<'a> {
         Ok(NetworkSession::new(module))
     }
 
-    fn init<Rx, Tx, C, const N: usize>(
+    pub(crate) fn init<Rx, Tx, C, const N: usize>(
         &self,
         module: &mut Module<Rx, Tx, C, N>,
     ) -> crate::Result<()>
@@ -60,38 +182,120 @@ impl<'a> SoftApConfig<'a> {
         Tx: serial::Write<u8> + 'static,
         C: SimpleClock,
     {
+        self.validate()?;
+
+        let suffix = module.dialect().current_suffix();
         // Enable SoftAP+Station mode.
         module
-            .send_at_command("AT+CWMODE=3")?
-            .expect("Malformed command");
+            .send_at_command(format_args!("AT+CWMODE{}=3", suffix))?
+            .expect_ok()?;
 
         // Enable multiple connections.
-        module
-            .send_at_command("AT+CIPMUX=1")?
-            .expect("Malformed command");
+        module.send_at_command("AT+CIPMUX=1")?.expect_ok()?;
+        enable_remote_info(module)?;
 
         // Start SoftAP.
         module
             .send_at_command(format_args!(
-                "AT+CWSAP=\"{}\",\"{}\",{},{}",
-                self.ssid, self.password, self.channel, self.mode as u8,
+                "AT+CWSAP{}=\"{}\",\"{}\",{},{}",
+                suffix,
+                Escaped(self.ssid),
+                Escaped(self.password),
+                self.channel,
+                u8::from(self.mode),
             ))?
-            .expect("Malformed command");
+            .expect_ok()?;
+
+        if let Some(address) = self.address {
+            let mut ip_buf = [0_u8; IPV4_MAX_LEN];
+            let mut gateway_buf = [0_u8; IPV4_MAX_LEN];
+            let mut netmask_buf = [0_u8; IPV4_MAX_LEN];
+            module
+                .send_at_command(format_args!(
+                    "AT+CIPAP{}=\"{}\",\"{}\",\"{}\"",
+                    suffix,
+                    format_ipv4(address.ip, &mut ip_buf),
+                    format_ipv4(address.gateway, &mut gateway_buf),
+                    format_ipv4(address.netmask, &mut netmask_buf),
+                ))?
+                .expect_ok()?;
+        }
 
         Ok(())
     }
 }
 
 /// Configuration parameters describe a connection to the existing access point.
-#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct JoinApConfig<'a> {
     /// Access point SSID.
     pub ssid: &'a str,
-    /// Access point password.
-    pub password: &'a str,
+    /// Access point password, should be `None` for the open networks.
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    pub password: Option<&'a str>,
+    /// Extra joining parameters, the firmwares older than ESP-AT 2.x support only
+    /// the [`bssid`](JoinApOptions::bssid) one.
+    pub options: Option<JoinApOptions>,
+}
+
+/// Access points scanning mode used while joining.
+#[repr(u8)]
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ScanMode {
+    /// Join the first found access point.
+    Fast = 0,
+    /// Scan all channels and join the access point with the strongest signal.
+    ///
+    /// This mode is recommended to reliably join networks with the hidden SSID.
+    AllChannels = 1,
+}
+
+/// Extra parameters of joining the access point supported by the ESP-AT 2.x firmware.
+///
+/// The NonOS firmware supports only the BSSID, the other parameters should be left default.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct JoinApOptions {
+    /// Forbid joining the open and WEP access points.
+    pub pci_auth: bool,
+    /// Access points scanning mode.
+    pub scan_mode: ScanMode,
+    /// Join only the access point with the given MAC address, e.g. to pin one of the several
+    /// access points with the same SSID.
+    pub bssid: Option<[u8; 6]>,
+}
+
+impl Default for JoinApOptions {
+    fn default() -> Self {
+        Self {
+            pci_auth: false,
+            scan_mode: ScanMode::Fast,
+            bssid: None,
+        }
+    }
 }
 
 impl<'a> JoinApConfig<'a> {
+    /// Creates a builder of the access point connection configuration.
+    pub fn builder() -> JoinApConfigBuilder<'a, ()> {
+        JoinApConfigBuilder {
+            ssid: (),
+            password: None,
+            options: None,
+        }
+    }
+
+    /// Checks that the configuration parameters are acceptable by the module.
+    pub fn validate(&self) -> crate::Result<()> {
+        validate_ssid(self.ssid)?;
+        if matches!(self.password, Some(password) if password.len() > PASSWORD_MAX_LEN) {
+            return Err(Error::InvalidConfig(ConfigError::InvalidPassword));
+        }
+        Ok(())
+    }
+
     /// Joins to the existing access point and establishing a new WiFi session.
     pub fn join<Rx, Tx, C, const N: usize>(
         self,
@@ -103,10 +307,28 @@ impl<'a> JoinApConfig<'a> {
         C: SimpleClock,
     {
         self.init(&mut module)?;
+        self.join_ap(&mut module)?;
         Ok(NetworkSession::new(module))
     }
 
-    fn init<Rx, Tx, C, const N: usize>(
+    /// Joins to the existing access point like the [`join`](Self::join) method,
+    /// but repeats the failed attempts according to the given backoff.
+    pub fn join_with_backoff<Rx, Tx, C, const N: usize>(
+        self,
+        mut module: Module<Rx, Tx, C, N>,
+        backoff: &mut Backoff,
+    ) -> crate::Result<NetworkSession<Rx, Tx, C, N>>
+    where
+        Rx: serial::Read<u8> + 'static,
+        Tx: serial::Write<u8> + 'static,
+        C: SimpleClock,
+    {
+        self.init(&mut module)?;
+        self.join_ap_with_backoff(&mut module, backoff)?;
+        Ok(NetworkSession::new(module))
+    }
+
+    pub(crate) fn init<Rx, Tx, C, const N: usize>(
         &self,
         module: &mut Module<Rx, Tx, C, N>,
     ) -> crate::Result<()>
@@ -115,24 +337,340 @@ impl<'a> JoinApConfig<'a> {
         Tx: serial::Write<u8> + 'static,
         C: SimpleClock,
     {
-        // Enable Station mode.
-        module
-            .send_at_command("AT+CWMODE=1")?
-            .expect("Malformed command");
+        self.validate()?;
+        init_station(module)
+    }
 
-        // Enable multiple connections.
-        module
-            .send_at_command("AT+CIPMUX=1")?
-            .expect("Malformed command");
+    /// Repeats joining the access point until it succeeds or the backoff attempts
+    /// are exhausted.
+    pub(crate) fn join_ap_with_backoff<Rx, Tx, C, const N: usize>(
+        &self,
+        module: &mut Module<Rx, Tx, C, N>,
+        backoff: &mut Backoff,
+    ) -> crate::Result<()>
+    where
+        Rx: serial::Read<u8> + 'static,
+        Tx: serial::Write<u8> + 'static,
+        C: SimpleClock,
+    {
+        self.validate()?;
 
+        loop {
+            match self.join_ap(module) {
+                Err(Error::JoinApError) => {
+                    let delay = backoff
+                        .next_delay(&module.clock)
+                        .ok_or(Error::JoinApError)?;
+                    module.delay(delay);
+                }
+                res => {
+                    backoff.reset();
+                    return res;
+                }
+            }
+        }
+    }
+
+    pub(crate) fn join_ap<Rx, Tx, C, const N: usize>(
+        &self,
+        module: &mut Module<Rx, Tx, C, N>,
+    ) -> crate::Result<()>
+    where
+        Rx: serial::Read<u8> + 'static,
+        Tx: serial::Write<u8> + 'static,
+        C: SimpleClock,
+    {
         // Join the given access point.
-        module
-            .send_at_command(format_args!(
-                "AT+CWJAP=\"{}\",\"{}\"",
-                self.ssid, self.password,
-            ))?
-            .map_err(|_| Error::JoinApError)?;
+        let suffix = module.dialect().current_suffix();
+        let ssid = Escaped(self.ssid);
+        let password = Escaped(self.password.unwrap_or_default());
+        let resp = match self.options {
+            // Only the BSSID is supported by the NonOS firmware.
+            Some(options) if module.dialect() == Dialect::NonOs => {
+                if options.pci_auth || options.scan_mode != ScanMode::Fast {
+                    return Err(Error::Unsupported);
+                }
+                let separator = if options.bssid.is_some() { "," } else { "" };
+                module.send_at_command(format_args!(
+                    "AT+CWJAP{}=\"{}\",\"{}\"{}{}",
+                    suffix,
+                    ssid,
+                    password,
+                    separator,
+                    MacParam(options.bssid),
+                ))?
+            }
+            // Omitted parameters are left empty.
+            Some(options) => module.send_at_command(format_args!(
+                "AT+CWJAP=\"{}\",\"{}\",{},{},,,{}",
+                ssid,
+                password,
+                MacParam(options.bssid),
+                options.pci_auth as u8,
+                options.scan_mode as u8,
+            ))?,
+            None => module.send_at_command(format_args!(
+                "AT+CWJAP{}=\"{}\",\"{}\"",
+                suffix, ssid, password
+            ))?,
+        };
+        resp.map_err(|_| Error::JoinApError)?;
 
         Ok(())
     }
 }
+
+/// Builder of the [`SoftApConfig`].
+///
+/// The SSID is required, so the [`build`](SoftApConfigBuilder::build) method becomes
+/// available only after it has been set.
+#[derive(Debug, Clone, Copy)]
+pub struct SoftApConfigBuilder<'a, S> {
+    ssid: S,
+    password: &'a str,
+    channel: u8,
+    mode: WifiMode,
+    address: Option<SoftApAddress>,
+}
+
+impl<'a, S> SoftApConfigBuilder<'a, S> {
+    /// Sets the access point SSID.
+    pub fn ssid(self, ssid: &'a str) -> SoftApConfigBuilder<'a, &'a str> {
+        SoftApConfigBuilder {
+            ssid,
+            password: self.password,
+            channel: self.channel,
+            mode: self.mode,
+            address: self.address,
+        }
+    }
+
+    /// Sets the access point password.
+    ///
+    /// If the WiFi mode is open, it will be switched to the WPA2 PSK mode.
+    pub fn password(mut self, password: &'a str) -> Self {
+        self.password = password;
+        if self.mode == WifiMode::Open {
+            self.mode = WifiMode::Wpa2Psk;
+        }
+        self
+    }
+
+    /// Sets the channel number.
+    pub fn channel(mut self, channel: u8) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    /// Sets the WiFi mode.
+    pub fn mode(mut self, mode: WifiMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the address of the access point instead of the module default.
+    pub fn address(mut self, address: SoftApAddress) -> Self {
+        self.address = Some(address);
+        self
+    }
+}
+
+impl<'a> SoftApConfigBuilder<'a, &'a str> {
+    /// Creates the software access point configuration.
+    pub fn build(self) -> SoftApConfig<'a> {
+        SoftApConfig {
+            ssid: self.ssid,
+            password: self.password,
+            channel: self.channel,
+            mode: self.mode,
+            address: self.address,
+        }
+    }
+}
+
+/// Builder of the [`JoinApConfig`].
+///
+/// The SSID is required, so the [`build`](JoinApConfigBuilder::build) method becomes
+/// available only after it has been set. Without a password the open network will be joined.
+#[derive(Debug, Clone, Copy)]
+pub struct JoinApConfigBuilder<'a, S> {
+    ssid: S,
+    password: Option<&'a str>,
+    options: Option<JoinApOptions>,
+}
+
+impl<'a, S> JoinApConfigBuilder<'a, S> {
+    /// Sets the access point SSID.
+    pub fn ssid(self, ssid: &'a str) -> JoinApConfigBuilder<'a, &'a str> {
+        JoinApConfigBuilder {
+            ssid,
+            password: self.password,
+            options: self.options,
+        }
+    }
+
+    /// Sets the access point password.
+    pub fn password(mut self, password: &'a str) -> Self {
+        self.password = Some(password);
+        self
+    }
+
+    /// Sets the extra joining parameters.
+    pub fn options(mut self, options: JoinApOptions) -> Self {
+        self.options = Some(options);
+        self
+    }
+}
+
+impl<'a> JoinApConfigBuilder<'a, &'a str> {
+    /// Creates the access point connection configuration.
+    pub fn build(self) -> JoinApConfig<'a> {
+        JoinApConfig {
+            ssid: self.ssid,
+            password: self.password,
+            options: self.options,
+        }
+    }
+}
+
+/// Owned variant of the [`SoftApConfig`] suitable for storing.
+#[derive(Debug, PartialEq, Clone, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OwnedSoftApConfig {
+    /// Access point SSID.
+    pub ssid: String<SSID_MAX_LEN>,
+    /// Access point password.
+    ///
+    /// This field will be ignored if WiFi mode is open.
+    pub password: String<PASSWORD_MAX_LEN>,
+    /// Channel number.
+    pub channel: u8,
+    /// WiFi mode.
+    pub mode: WifiMode,
+    /// Address of the access point.
+    pub address: Option<SoftApAddress>,
+}
+
+impl OwnedSoftApConfig {
+    /// Borrows this configuration as a [`SoftApConfig`].
+    pub fn as_config(&self) -> SoftApConfig<'_> {
+        SoftApConfig {
+            ssid: &self.ssid,
+            password: &self.password,
+            channel: self.channel,
+            mode: self.mode,
+            address: self.address,
+        }
+    }
+}
+
+impl<'a> TryFrom<SoftApConfig<'a>> for OwnedSoftApConfig {
+    type Error = Error;
+
+    fn try_from(config: SoftApConfig<'a>) -> crate::Result<Self> {
+        Ok(Self {
+            ssid: to_owned_str(config.ssid, ConfigError::InvalidSsid)?,
+            password: to_owned_str(config.password, ConfigError::InvalidPassword)?,
+            channel: config.channel,
+            mode: config.mode,
+            address: config.address,
+        })
+    }
+}
+
+impl<'a> From<&'a OwnedSoftApConfig> for SoftApConfig<'a> {
+    fn from(config: &'a OwnedSoftApConfig) -> Self {
+        config.as_config()
+    }
+}
+
+/// Owned variant of the [`JoinApConfig`] suitable for storing.
+#[derive(Debug, PartialEq, Clone, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OwnedJoinApConfig {
+    /// Access point SSID.
+    pub ssid: String<SSID_MAX_LEN>,
+    /// Access point password, should be `None` for the open networks.
+    pub password: Option<String<PASSWORD_MAX_LEN>>,
+    /// Extra joining parameters.
+    pub options: Option<JoinApOptions>,
+}
+
+impl OwnedJoinApConfig {
+    /// Borrows this configuration as a [`JoinApConfig`].
+    pub fn as_config(&self) -> JoinApConfig<'_> {
+        JoinApConfig {
+            ssid: &self.ssid,
+            password: self.password.as_deref(),
+            options: self.options,
+        }
+    }
+}
+
+impl<'a> TryFrom<JoinApConfig<'a>> for OwnedJoinApConfig {
+    type Error = Error;
+
+    fn try_from(config: JoinApConfig<'a>) -> crate::Result<Self> {
+        Ok(Self {
+            ssid: to_owned_str(config.ssid, ConfigError::InvalidSsid)?,
+            password: config
+                .password
+                .map(|password| to_owned_str(password, ConfigError::InvalidPassword))
+                .transpose()?,
+            options: config.options,
+        })
+    }
+}
+
+impl<'a> From<&'a OwnedJoinApConfig> for JoinApConfig<'a> {
+    fn from(config: &'a OwnedJoinApConfig) -> Self {
+        config.as_config()
+    }
+}
+
+/// Switches the module to the station mode with multiple connections.
+pub(crate) fn init_station<Rx, Tx, C, const N: usize>(
+    module: &mut Module<Rx, Tx, C, N>,
+) -> crate::Result<()>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    // Enable Station mode.
+    let suffix = module.dialect().current_suffix();
+    module
+        .send_at_command(format_args!("AT+CWMODE{}=1", suffix))?
+        .expect_ok()?;
+
+    // Enable multiple connections.
+    module.send_at_command("AT+CIPMUX=1")?.expect_ok()?;
+    enable_remote_info(module)
+}
+
+/// Enables the remote address in the received data headers, the firmwares that don't
+/// support the `AT+CIPDINFO` command report the data without it.
+fn enable_remote_info<Rx, Tx, C, const N: usize>(
+    module: &mut Module<Rx, Tx, C, N>,
+) -> crate::Result<()>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    module.send_at_command("AT+CIPDINFO=1")?.ok();
+    Ok(())
+}
+
+fn validate_ssid(ssid: &str) -> crate::Result<()> {
+    if ssid.is_empty() || ssid.len() > SSID_MAX_LEN {
+        return Err(Error::InvalidConfig(ConfigError::InvalidSsid));
+    }
+    Ok(())
+}
+
+fn to_owned_str<const N: usize>(s: &str, err: ConfigError) -> crate::Result<String<N>> {
+    let mut owned = String::new();
+    owned.push_str(s).map_err(|_| Error::InvalidConfig(err))?;
+    Ok(owned)
+}
@@ -0,0 +1,177 @@
+//! Authentication of the SSL links.
+
+use embedded_hal::serial;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use simple_clock::SimpleClock;
+
+use crate::{
+    error::ensure, module::Escaped, net::SocketAddr, network_session::MAX_LINKS, Capabilities,
+    Dialect, Error, NetworkSession,
+};
+
+/// Maximum length of the SSL pre-shared key identity and hint.
+pub const SSL_PSK_MAX_LEN: usize = 32;
+
+/// Range of the SSL buffer sizes accepted by the module.
+pub const SSL_BUFFER_SIZE_RANGE: core::ops::RangeInclusive<u16> = 2048..=4096;
+
+/// Authentication mode of the SSL link.
+///
+/// The certificates and keys are referred by their indices in the certificate partitions
+/// of the module flash. The AT firmware has no command to check the fingerprint of the server
+/// certificate, so the server is authenticated by the selected CA certificate only. To accept
+/// a single endpoint, the device should be provisioned with the certificate of this endpoint
+/// as the CA certificate, or the [PSK](NetworkSession::set_ssl_psk) authentication should be
+/// used instead.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SslAuth {
+    /// The peers are not authenticated.
+    None,
+    /// The client presents the certificate with the given index.
+    Client {
+        /// Index of the client certificate and the private key.
+        pki: u8,
+    },
+    /// The server certificate is verified by the CA with the given index.
+    Server {
+        /// Index of the CA certificate.
+        ca: u8,
+    },
+    /// Both peers are authenticated.
+    Mutual {
+        /// Index of the client certificate and the private key.
+        pki: u8,
+        /// Index of the CA certificate.
+        ca: u8,
+    },
+}
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Establishes an SSL connection with the specified IP address.
+    ///
+    /// The authentication of the link should be configured in advance by
+    /// the [`set_ssl_auth`](Self::set_ssl_auth) or [`set_ssl_psk`](Self::set_ssl_psk) methods.
+    /// The server name is not sent unless it is set by the [`set_ssl_sni`](Self::set_ssl_sni)
+    /// method.
+    #[doc(alias = "connect_tls")]
+    pub fn connect_ssl(&mut self, link_id: usize, address: SocketAddr) -> crate::Result<()> {
+        self.connect_with(link_id, "SSL", address)
+    }
+
+    /// Establishes an SSL connection with the specified host name.
+    ///
+    /// The module resolves the name and sends it as the SNI hostname, so this method
+    /// should be used for the servers behind the shared load balancers.
+    #[doc(alias = "connect_tls")]
+    pub fn connect_ssl_host(&mut self, link_id: usize, host: &str, port: u16) -> crate::Result<()> {
+        self.start_connection(link_id, "SSL", &Escaped(host), port)
+    }
+
+    /// Sets the size of the SSL buffer of the module, it should be set before the SSL
+    /// connection is established.
+    ///
+    /// The larger buffer is required by the servers that send the long certificate chains,
+    /// the size should be in the [`SSL_BUFFER_SIZE_RANGE`]. Returns [`Error::Unsupported`]
+    /// if the module uses the ESP-AT firmware, which allocates the buffer itself.
+    pub fn set_ssl_buffer_size(&mut self, size: u16) -> crate::Result<()> {
+        if !SSL_BUFFER_SIZE_RANGE.contains(&size) {
+            return Err(Error::InvalidArgument);
+        }
+        // The buffer size is configured by the NonOS firmware only.
+        if self.module.dialect() != Dialect::NonOs {
+            return Err(Error::Unsupported);
+        }
+
+        self.module
+            .send_at_command(format_args!("AT+CIPSSLSIZE={}", size))?
+            .map_err(|_| Error::CommandFailed)?;
+
+        Ok(())
+    }
+
+    /// Sets the SNI hostname of the SSL link with the given identifier explicitly, e.g. when
+    /// the link is [connected](Self::connect_ssl) by the IP address.
+    ///
+    /// Returns [`Error::Unsupported`] if the module firmware doesn't support the SSL
+    /// configuration.
+    pub fn set_ssl_sni(&mut self, link_id: usize, host: &str) -> crate::Result<()> {
+        ensure!(
+            link_id < MAX_LINKS,
+            "Link identifier should be less than the maximum number of connections"
+        );
+        self.module.require(Capabilities::SSL_CONFIG)?;
+
+        self.module
+            .send_at_command(format_args!(
+                "AT+CIPSSLCSNI={},\"{}\"",
+                link_id,
+                Escaped(host)
+            ))?
+            .map_err(|_| Error::CommandFailed)?;
+
+        Ok(())
+    }
+
+    /// Sets the authentication mode of the SSL link with the given identifier.
+    ///
+    /// Returns [`Error::Unsupported`] if the module firmware doesn't support the SSL
+    /// configuration.
+    pub fn set_ssl_auth(&mut self, link_id: usize, auth: SslAuth) -> crate::Result<()> {
+        ensure!(
+            link_id < MAX_LINKS,
+            "Link identifier should be less than the maximum number of connections"
+        );
+        self.module.require(Capabilities::SSL_CONFIG)?;
+
+        let (mode, pki, ca) = match auth {
+            SslAuth::None => (0, 0, 0),
+            SslAuth::Client { pki } => (1, pki, 0),
+            SslAuth::Server { ca } => (2, 0, ca),
+            SslAuth::Mutual { pki, ca } => (3, pki, ca),
+        };
+        self.module
+            .send_at_command(format_args!(
+                "AT+CIPSSLCCONF={},{},{},{}",
+                link_id, mode, pki, ca
+            ))?
+            .map_err(|_| Error::CommandFailed)?;
+
+        Ok(())
+    }
+
+    /// Sets the pre-shared key parameters of the SSL link with the given identifier, so the
+    /// peers are authenticated by the shared secret instead of the certificates.
+    ///
+    /// Both the `psk` and the `hint` strings should be no longer than [`SSL_PSK_MAX_LEN`] bytes.
+    /// Returns [`Error::Unsupported`] if the module firmware doesn't support the SSL
+    /// configuration.
+    pub fn set_ssl_psk(&mut self, link_id: usize, psk: &str, hint: &str) -> crate::Result<()> {
+        ensure!(
+            link_id < MAX_LINKS,
+            "Link identifier should be less than the maximum number of connections"
+        );
+        ensure!(
+            psk.len() <= SSL_PSK_MAX_LEN && hint.len() <= SSL_PSK_MAX_LEN,
+            "The pre-shared key identity and hint should be no longer than 32 bytes"
+        );
+        self.module.require(Capabilities::SSL_CONFIG)?;
+
+        self.module
+            .send_at_command(format_args!(
+                "AT+CIPSSLCPSK={},\"{}\",\"{}\"",
+                link_id,
+                Escaped(psk),
+                Escaped(hint)
+            ))?
+            .map_err(|_| Error::CommandFailed)?;
+
+        Ok(())
+    }
+}
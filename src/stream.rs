@@ -0,0 +1,103 @@
+//! Bulk transfer of the large amounts of data.
+
+use embedded_hal::serial;
+use simple_clock::SimpleClock;
+
+use crate::{
+    buffered::SEND_MAX_LEN,
+    error::ensure,
+    module::{CarretCondition, OkCondition},
+    network_session::MAX_LINKS,
+    Error, NetworkSession,
+};
+
+/// Size of the segments sent by the [`send_stream`](NetworkSession::send_stream) method,
+/// it is the largest one accepted by the module.
+pub const STREAM_SEGMENT_LEN: usize = SEND_MAX_LEN;
+
+/// Source of the bytes sent by the [`send_stream`](NetworkSession::send_stream) method,
+/// e.g. a firmware image in the external flash.
+pub trait StreamSource {
+    /// Reads bytes into the given buffer and returns the number of bytes read,
+    /// zero means that the source is exhausted.
+    fn read(&mut self, buf: &mut [u8]) -> crate::Result<usize>;
+}
+
+impl StreamSource for &[u8] {
+    fn read(&mut self, buf: &mut [u8]) -> crate::Result<usize> {
+        let len = core::cmp::min(buf.len(), self.len());
+        let (head, tail) = self.split_at(len);
+        buf[..len].copy_from_slice(head);
+        *self = tail;
+        Ok(len)
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Sends `len` bytes read from the source via the link with the given identifier.
+    ///
+    /// Unlike the [`send`](Self::send) method, it is intended for the large transfers, e.g.
+    /// the firmware download: the data is sent by the segments of the
+    /// [`STREAM_SEGMENT_LEN`] bytes, each segment is read from the source before the command
+    /// so the module doesn't wait for the data, and the segment is written to the serial port
+    /// in a tight loop. The segments are pipelined if it is
+    /// [enabled](Self::set_send_pipeline_depth).
+    ///
+    /// Returns the [`Error::InvalidArgument`] error if the source is exhausted before `len`
+    /// bytes have been read.
+    pub fn send_stream<R>(
+        &mut self,
+        link_id: usize,
+        source: &mut R,
+        len: usize,
+    ) -> crate::Result<()>
+    where
+        R: StreamSource,
+    {
+        ensure!(
+            link_id < MAX_LINKS,
+            "Link identifier should be less than the maximum number of connections"
+        );
+
+        let mut buf = [0_u8; STREAM_SEGMENT_LEN];
+        let mut remaining = len;
+        while remaining > 0 {
+            let segment = &mut buf[..core::cmp::min(remaining, STREAM_SEGMENT_LEN)];
+            let mut filled = 0;
+            while filled < segment.len() {
+                match source.read(&mut segment[filled..])? {
+                    0 => return Err(Error::InvalidArgument),
+                    read => filled += read,
+                }
+            }
+
+            if self.module.in_flight.depth > 0 {
+                self.module
+                    .send_pipelined(link_id, segment.iter().copied())?;
+            } else {
+                self.send_segment(link_id, segment)?;
+            }
+            remaining -= segment.len();
+        }
+        Ok(())
+    }
+
+    fn send_segment(&mut self, link_id: usize, segment: &[u8]) -> crate::Result<()> {
+        self.module
+            .write_command_fmt(format_args!("AT+CIPSEND={},{}", link_id, segment.len()))?;
+        self.module.read_until(CarretCondition)?;
+
+        self.module.writer.write_all(segment)?;
+        self.module.writer.flush()?;
+
+        self.module
+            .read_until(OkCondition)?
+            .map_err(|_| Error::CommandFailed)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+//! Wall clock synchronized over the network.
+
+use core::cell::Cell;
+
+use embedded_hal::serial;
+use simple_clock::SimpleClock;
+
+use crate::NetworkSession;
+
+/// Default interval between the time synchronizations, one hour in microseconds.
+pub const DEFAULT_RESYNC_INTERVAL_US: u64 = 3_600_000_000;
+
+/// Moment when the clock has been synchronized.
+#[derive(Debug, Clone, Copy)]
+struct SyncPoint {
+    /// Monotonic time of the synchronization in microseconds.
+    now_us: u64,
+    /// Unix time of the synchronization in microseconds.
+    unix_us: u64,
+}
+
+/// Clock that wraps a monotonic clock and tracks the wall clock time obtained by the SNTP
+/// client of the module.
+///
+/// The clock itself is a [`SimpleClock`] that passes the monotonic time through, so it can be
+/// given to a module while the rest of the firmware reads the wall clock time from it.
+/// The synchronization methods take `&self`, so the clock can be shared by reference,
+/// see [`SharedClock`](crate::SharedClock).
+///
+/// The synchronization point is kept in a [`Cell`], so the clock is not [`Sync`] and cannot
+/// be shared with the interrupt handlers directly, e.g. as a `static` item. Wrap it into
+/// a mutex for that.
+///
+/// # Example
+///
+/// ```no_run
+/// # use embedded_hal::serial;
+/// # use esp8266_wifi_serial::{clock::SimpleClock, JoinApConfig, Module, SharedClock, SyncedClock};
+/// # fn run<Rx, Tx, C>(rx: Rx, tx: Tx, timer: C) -> esp8266_wifi_serial::Result<()>
+/// # where
+/// #     Rx: serial::Read<u8> + 'static,
+/// #     Tx: serial::Write<u8> + 'static,
+/// #     C: SimpleClock,
+/// # {
+/// let clock = SyncedClock::new(timer);
+/// let module: Module<_, _, _> = Module::new(rx, tx, SharedClock::new(&clock))?;
+/// let mut session = JoinApConfig {
+///     ssid: "network",
+///     password: Some("password"),
+///     options: None,
+/// }
+/// .join(module)?;
+/// session.set_sntp_config(0, &["pool.ntp.org"])?;
+/// loop {
+///     clock.sync_if_needed(&mut session)?;
+///     if let Some(time) = clock.unix_time() {
+///         // Use the wall clock time.
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SyncedClock<C> {
+    clock: C,
+    resync_interval_us: u64,
+    sync_point: Cell<Option<SyncPoint>>,
+}
+
+impl<C: SimpleClock> SyncedClock<C> {
+    /// Creates a new unsynchronized clock with the
+    /// [default](DEFAULT_RESYNC_INTERVAL_US) resync interval.
+    pub fn new(clock: C) -> Self {
+        Self {
+            clock,
+            resync_interval_us: DEFAULT_RESYNC_INTERVAL_US,
+            sync_point: Cell::new(None),
+        }
+    }
+
+    /// Sets the interval between the time synchronizations in microseconds.
+    pub fn with_resync_interval(mut self, interval_us: u64) -> Self {
+        self.resync_interval_us = interval_us;
+        self
+    }
+
+    /// Returns a reference to the wrapped monotonic clock.
+    pub fn inner(&self) -> &C {
+        &self.clock
+    }
+
+    /// Returns `true` if the clock has been synchronized at least once.
+    pub fn is_synced(&self) -> bool {
+        self.sync_point.get().is_some()
+    }
+
+    /// Returns `true` if the clock has never been synchronized or the resync interval has
+    /// elapsed since the last synchronization.
+    pub fn needs_sync(&self) -> bool {
+        match self.sync_point.get() {
+            Some(point) => {
+                self.clock.now_us().saturating_sub(point.now_us) >= self.resync_interval_us
+            }
+            None => true,
+        }
+    }
+
+    /// Sets the current wall clock time as the number of seconds since the Unix epoch.
+    pub fn set_unix_time(&self, secs: u64) {
+        self.sync_point.set(Some(SyncPoint {
+            now_us: self.clock.now_us(),
+            unix_us: secs * 1_000_000,
+        }));
+    }
+
+    /// Returns the number of microseconds since the Unix epoch, or `None` if the clock has
+    /// not been synchronized yet.
+    pub fn unix_time_us(&self) -> Option<u64> {
+        let point = self.sync_point.get()?;
+        let elapsed = self.clock.now_us().saturating_sub(point.now_us);
+        Some(point.unix_us + elapsed)
+    }
+
+    /// Returns the number of seconds since the Unix epoch, or `None` if the clock has not
+    /// been synchronized yet.
+    pub fn unix_time(&self) -> Option<u64> {
+        self.unix_time_us().map(|us| us / 1_000_000)
+    }
+
+    /// Reads the time obtained by the SNTP client of the module and synchronizes the clock.
+    ///
+    /// Returns `false` if the module has not obtained the time yet, the clock keeps the
+    /// previous time in this case. The SNTP client should be enabled by the
+    /// [`set_sntp_config`](NetworkSession::set_sntp_config) method.
+    pub fn sync<Rx, Tx, C2, const N: usize>(
+        &self,
+        session: &mut NetworkSession<Rx, Tx, C2, N>,
+    ) -> crate::Result<bool>
+    where
+        Rx: serial::Read<u8> + 'static,
+        Tx: serial::Write<u8> + 'static,
+        C2: SimpleClock,
+    {
+        match session.sntp_time()? {
+            Some(secs) => {
+                self.set_unix_time(secs);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Synchronizes the clock if [needed](Self::needs_sync).
+    ///
+    /// Returns `true` if the clock is synchronized after the call.
+    pub fn sync_if_needed<Rx, Tx, C2, const N: usize>(
+        &self,
+        session: &mut NetworkSession<Rx, Tx, C2, N>,
+    ) -> crate::Result<bool>
+    where
+        Rx: serial::Read<u8> + 'static,
+        Tx: serial::Write<u8> + 'static,
+        C2: SimpleClock,
+    {
+        if self.needs_sync() {
+            Ok(self.sync(session)? || self.is_synced())
+        } else {
+            Ok(true)
+        }
+    }
+}
+
+impl<C: SimpleClock> SimpleClock for SyncedClock<C> {
+    fn now_us(&self) -> u64 {
+        self.clock.now_us()
+    }
+}
@@ -0,0 +1,262 @@
+//! Deterministic utilities to write host unit tests for the code that uses this driver.
+//!
+//! # Example
+//!
+//! ```
+//! use esp8266_wifi_serial::{
+//!     test_utils::{MockClock, MockSerial},
+//!     Module,
+//! };
+//!
+//! let serial = MockSerial::new().with_default_reply(b"ERROR\r\n");
+//! serial.expect(b"ATE0\r\n", b"OK\r\n");
+//!
+//! let module = Module::<_, _, _, 256>::new(serial.clone(), serial.clone(), MockClock::new())
+//!     .unwrap();
+//! assert!(serial.written().starts_with(b"ATE0\r\n"));
+//! # drop(module);
+//! ```
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    rc::Rc,
+    string::String,
+    vec::Vec,
+};
+
+use embedded_hal::serial;
+use simple_clock::SimpleClock;
+
+/// Manually advanced clock.
+///
+/// The clones of the clock share the same time.
+#[derive(Debug, Clone, Default)]
+pub struct MockClock {
+    now: Rc<Cell<u64>>,
+    step: u64,
+}
+
+impl MockClock {
+    /// Creates a new clock that starts from zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the clock advance by the given number of microseconds on every reading,
+    /// so the operation timeouts are reached even if the test doesn't advance the clock.
+    pub fn with_step(mut self, step: u64) -> Self {
+        self.step = step;
+        self
+    }
+
+    /// Advances the clock by the given number of microseconds.
+    pub fn advance(&self, us: u64) {
+        self.now.set(self.now.get() + us);
+    }
+
+    /// Sets the current time in microseconds.
+    pub fn set(&self, us: u64) {
+        self.now.set(us);
+    }
+}
+
+impl SimpleClock for MockClock {
+    fn now_us(&self) -> u64 {
+        let now = self.now.get();
+        self.now.set(now + self.step);
+        now
+    }
+}
+
+#[derive(Debug, Default)]
+struct SerialState {
+    /// Bytes to be read by the driver.
+    rx: VecDeque<u8>,
+    /// All the bytes written by the driver.
+    written: Vec<u8>,
+    /// Written bytes since the last matched expectation or command.
+    pending: Vec<u8>,
+    /// Expected writes and the corresponding replies.
+    script: VecDeque<(Vec<u8>, Vec<u8>)>,
+    /// Reply to the written command lines that don't match the script.
+    default_reply: Option<Vec<u8>>,
+    /// Bytes have been written since the last flush.
+    unflushed: bool,
+}
+
+impl SerialState {
+    fn on_write(&mut self, byte: u8) {
+        self.written.push(byte);
+        self.pending.push(byte);
+        self.unflushed = true;
+
+        if let Some((expected, _)) = self.script.front() {
+            if self.pending.ends_with(expected) {
+                let (_, reply) = self.script.pop_front().unwrap();
+                self.rx.extend(reply);
+                self.pending.clear();
+                return;
+            }
+        }
+
+        // The payload bytes may contain line breaks too, so only the command lines
+        // are answered by default.
+        if self.pending.starts_with(b"AT") && self.pending.ends_with(b"\r\n") {
+            if let Some(reply) = self.default_reply.as_ref() {
+                self.rx.extend(reply);
+            }
+            self.pending.clear();
+        }
+    }
+}
+
+/// Scripted loopback serial port that can be used as both the receiving and transmitting
+/// halves of the module connection.
+///
+/// The clones of the serial port share the same state, so a test can keep one of them
+/// to script the replies and to check the written bytes.
+#[derive(Debug, Clone, Default)]
+pub struct MockSerial {
+    state: Rc<RefCell<SerialState>>,
+}
+
+impl MockSerial {
+    /// Creates a new serial port without any scripted replies.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the reply to the written command lines that don't match the script,
+    /// e.g. `ERROR\r\n` to reject all the unexpected commands.
+    pub fn with_default_reply(self, reply: &[u8]) -> Self {
+        self.state.borrow_mut().default_reply = Some(reply.to_vec());
+        self
+    }
+
+    /// Scripts the reply that will be received after the driver writes the given bytes.
+    ///
+    /// The expectations are matched in order.
+    pub fn expect(&self, written: &[u8], reply: &[u8]) {
+        self.state
+            .borrow_mut()
+            .script
+            .push_back((written.to_vec(), reply.to_vec()));
+    }
+
+    /// Queues the bytes to be received by the driver, e.g. the unsolicited frames.
+    pub fn receive(&self, bytes: &[u8]) {
+        self.state.borrow_mut().rx.extend(bytes);
+    }
+
+    /// Returns all the bytes written by the driver.
+    pub fn written(&self) -> Vec<u8> {
+        self.state.borrow().written.clone()
+    }
+
+    /// Returns the bytes written by the driver since the previous call and forgets them.
+    pub fn take_written(&self) -> Vec<u8> {
+        core::mem::take(&mut self.state.borrow_mut().written)
+    }
+
+    /// Asserts that the driver has written exactly the given bytes since the previous
+    /// [`take_written`](Self::take_written) or `assert_written` call.
+    #[track_caller]
+    pub fn assert_written(&self, expected: &[u8]) {
+        let written = self.take_written();
+        assert_eq!(
+            String::from_utf8_lossy(&written),
+            String::from_utf8_lossy(expected),
+            "Unexpected bytes have been written"
+        );
+    }
+
+    /// Returns `true` if the driver has flushed all the written bytes.
+    pub fn is_flushed(&self) -> bool {
+        !self.state.borrow().unflushed
+    }
+
+    /// Returns `true` if all the scripted replies have been sent.
+    pub fn is_script_done(&self) -> bool {
+        self.state.borrow().script.is_empty()
+    }
+}
+
+impl serial::Read<u8> for MockSerial {
+    type Error = core::convert::Infallible;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.state
+            .borrow_mut()
+            .rx
+            .pop_front()
+            .ok_or(nb::Error::WouldBlock)
+    }
+}
+
+impl serial::Write<u8> for MockSerial {
+    type Error = core::convert::Infallible;
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        self.state.borrow_mut().on_write(word);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.state.borrow_mut().unflushed = false;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_io_async::ErrorType for MockSerial {
+    type Error = core::convert::Infallible;
+}
+
+/// Waits until there are bytes to be read, the waker is not notified, so the executor
+/// should poll the future repeatedly.
+#[cfg(feature = "async")]
+#[clippy::msrv = "1.75"]
+impl embedded_io_async::Read for MockSerial {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        core::future::poll_fn(|_| {
+            let mut state = self.state.borrow_mut();
+            if state.rx.is_empty() && !buf.is_empty() {
+                return core::task::Poll::Pending;
+            }
+
+            let len = core::cmp::min(buf.len(), state.rx.len());
+            for (dst, src) in buf.iter_mut().zip(state.rx.drain(..len)) {
+                *dst = src;
+            }
+            core::task::Poll::Ready(Ok(len))
+        })
+        .await
+    }
+}
+
+#[cfg(feature = "async")]
+#[clippy::msrv = "1.75"]
+impl embedded_io_async::Write for MockSerial {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let mut state = self.state.borrow_mut();
+        for byte in buf {
+            state.on_write(*byte);
+        }
+        Ok(buf.len())
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.state.borrow_mut().unflushed = false;
+        Ok(())
+    }
+}
+
+/// Advances the clock by the delay and completes immediately.
+#[cfg(feature = "async")]
+#[clippy::msrv = "1.75"]
+impl embedded_hal_async::delay::DelayNs for MockClock {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.advance(u64::from(ns) / 1_000);
+    }
+}
@@ -1,4 +1,42 @@
-use crate::parser::CommandResponse;
+use core::{
+    cell::Cell,
+    convert::TryFrom,
+    fmt::Write,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+use std::{boxed::Box, format};
+
+use heapless::{String, Vec};
+use simple_clock::SimpleClock;
+
+use crate::{
+    captive_portal::dns_answer,
+    format_ipv4,
+    module::{
+        skip_unsolicited_frames, CarretCondition, Condition, Escaped, OkCondition, ReadyCondition,
+    },
+    net::{IpAddr, Ipv4Addr},
+    parse_ipv4,
+    parser::{
+        find_line_value, parse_mac, parse_mac_response, parse_numeric, CifsrResponse,
+        CommandResponse, CwjapResponse, IpConfigResponse,
+    },
+    pipeline::find_confirmation,
+    provisioning::{http_request_body, parse_credentials},
+    reader_part::{PendingData, ReadData},
+    sntp::parse_asctime,
+    test_utils::{MockClock, MockSerial},
+    AtVersion, Backoff, BlockingSession, Capabilities, CaptivePortal, Chip, ConfigError,
+    ConnectedAp, CredentialStore, DhcpLeaseRange, Dialect, Dispatcher, DynModule,
+    DynNetworkSession, Error, GpioWakeup, JoinApConfig, JoinApOptions, KeepAlive, LinkEvent,
+    LinkProtocol, LinkQueues, LinkRole, LinkStatus, MdnsResponder, MdnsService, Module,
+    NetworkEvent, NetworkSession, OwnedJoinApConfig, OwnedNetworkEvent, PingTime, Provisioning,
+    ProvisioningPages, RebootRecovery, ReconnectPolicy, ResetConfig, ResetKind, RetryPolicy,
+    ScanMode, SharedClock, SleepMode, SmartConfig, SmartConfigProtocol, SoftApAddress,
+    SoftApConfig, SslAuth, SyncedClock, WifiInterface, WifiMode, DEFAULT_BUFFER_LEN,
+    MAX_IN_FLIGHT_SENDS, QUEUED_SEGMENT_MAX_LEN, STREAM_SEGMENT_LEN, TRACE_BUFFER_LEN,
+};
 
 #[test]
 fn test_parse_connect() {
@@ -25,7 +63,3223 @@ fn test_parse_data_available() {
         event,
         CommandResponse::DataAvailable {
             link_id: 12,
-            size: 6
+            size: 6,
+            remote: None,
         }
     )
 }
+
+#[test]
+fn test_parse_ip_config() {
+    let raw = b"+CIPSTA:ip:\"192.168.1.5\"\r\n+CIPSTA:gateway:\"192.168.1.1\"\r\n+CIPSTA:netmask:\"255.255.255.0\"\r\n\r\n";
+    let resp = IpConfigResponse::parse(raw.as_ref(), "CIPSTA").unwrap().1;
+
+    assert_eq!(
+        resp,
+        IpConfigResponse {
+            ip: Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 5))),
+            gateway: Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1))),
+            netmask: Some(IpAddr::V4(Ipv4Addr::new(255, 255, 255, 0))),
+        }
+    );
+
+    // The ESP32 chips report the IPv6 addresses as well.
+    let raw = b"+CIPSTA:ip:\"192.168.1.5\"\r\n+CIPSTA:ip6ll:\"fe80::260a:c4ff:fe09:a5e8\"\r\n\
+        +CIPSTA:gateway:\"192.168.1.1\"\r\n+CIPSTA:netmask:\"255.255.255.0\"\r\n";
+    let esp32_resp = IpConfigResponse::parse(raw.as_ref(), "CIPSTA").unwrap().1;
+    assert_eq!(esp32_resp, resp);
+
+    let raw = b"+CIPAP_CUR:ip:\"192.168.4.1\"\r\n";
+    let resp = IpConfigResponse::parse(raw.as_ref(), "CIPAP").unwrap().1;
+    assert_eq!(resp.ip, Some(IpAddr::V4(Ipv4Addr::new(192, 168, 4, 1))));
+}
+
+#[test]
+fn test_parse_cifsr() {
+    let raw = b"+CIFSR:APIP,\"192.168.4.1\"\r\n+CIFSR:APMAC,\"1a:fe:34:a5:8d:c6\"\r\n+CIFSR:STAIP,\"192.168.1.5\"\r\n";
+    assert_eq!(
+        CifsrResponse::parse(raw),
+        CifsrResponse {
+            ap_ip: Some(IpAddr::V4(Ipv4Addr::new(192, 168, 4, 1))),
+            sta_ip: Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 5))),
+        }
+    );
+
+    // The station address line is missing, e.g. in the SoftAP mode.
+    let raw = b"+CIFSR:APIP,\"192.168.4.1\"";
+    let resp = CifsrResponse::parse(raw);
+    assert_eq!(resp.ap_ip, Some(IpAddr::V4(Ipv4Addr::new(192, 168, 4, 1))));
+    assert_eq!(resp.sta_ip, None);
+}
+
+#[test]
+fn test_parse_cwjap() {
+    let raw = b"+CWJAP:\"my network\",\"aa:bb:cc:dd:ee:ff\",6,-56\r\n";
+    let resp = CwjapResponse::parse(raw.as_ref()).unwrap().1;
+
+    assert_eq!(
+        resp,
+        CwjapResponse {
+            ssid: b"my network",
+            bssid: b"aa:bb:cc:dd:ee:ff",
+            channel: 6,
+            rssi: -56,
+        }
+    );
+    assert!(CwjapResponse::parse(b"No AP\r\n").is_none());
+
+    let raw = b"+CWJAP_CUR:\"my network\",\"aa:bb:cc:dd:ee:ff\",6,-56\r\n";
+    assert_eq!(CwjapResponse::parse(raw.as_ref()).unwrap().1, resp);
+}
+
+#[test]
+fn test_skip_unsolicited_frames() {
+    let buf = b"0,CONNECT\r\n+IPD,0,5:hello\r\n+CWLAP:(3,\"net\",-60)\r\n";
+    assert_eq!(skip_unsolicited_frames(buf, 0), 25);
+
+    let incomplete = b"0,CONNECT\r\n+IPD,0,5:hel";
+    assert_eq!(skip_unsolicited_frames(incomplete, 0), 11);
+}
+
+#[test]
+fn test_command_response_keeps_diverted_frames() {
+    let mut buf: Vec<u8, 32> = Vec::from_slice(b"+IPD,0,2:hiOK\r\n").unwrap();
+
+    let mut data = ReadData::new(&mut buf, 11);
+    data.cut(0, 4);
+    assert!(data.is_empty());
+    drop(data);
+
+    assert_eq!(&buf[..], b"+IPD,0,2:hi");
+}
+
+#[test]
+fn test_ready_condition_split_and_noise() {
+    let mut buf: Vec<u8, 64> = Vec::new();
+    buf.extend_from_slice(b"\r\nrea").unwrap();
+    assert!(!Condition::<64>::is_performed(ReadyCondition, &buf));
+
+    buf.extend_from_slice(b"dy\r\n\x00\xfeboot noise").unwrap();
+    assert!(Condition::<64>::is_performed(ReadyCondition, &buf));
+
+    let data = ReadyCondition.output(ReadData::new(&mut buf, 0));
+    assert_eq!(data.as_ref(), b"\r\n".as_ref());
+    drop(data);
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn test_ok_condition_keeps_trailing_frames() {
+    let mut buf: Vec<u8, 64> = Vec::new();
+    buf.extend_from_slice(b"+CWMODE:1\r\n\r\nO").unwrap();
+    assert!(!Condition::<64>::is_performed(OkCondition, &buf));
+
+    buf.extend_from_slice(b"K\r\n0,CONNECT\r\n").unwrap();
+    assert!(Condition::<64>::is_performed(OkCondition, &buf));
+
+    let data = OkCondition.output(ReadData::new(&mut buf, 0)).unwrap();
+    assert_eq!(data.as_ref(), b"+CWMODE:1\r\n\r\n".as_ref());
+    drop(data);
+    assert_eq!(&buf[..], b"0,CONNECT\r\n");
+
+    let mut buf: Vec<u8, 64> = Vec::from_slice(b"busy p...\r\nERROR\r\n").unwrap();
+    assert!(OkCondition::is_busy(&buf));
+    assert!(OkCondition.output(ReadData::new(&mut buf, 0)).is_err());
+}
+
+#[test]
+fn test_ok_condition_is_line_anchored() {
+    let mut buf: Vec<u8, 64> = Vec::new();
+    buf.extend_from_slice(b"+CWJAP:\"NOK\r\n\",\"aa\",1,-50\r\n")
+        .unwrap();
+    assert!(!Condition::<64>::is_performed(OkCondition, &buf));
+
+    buf.extend_from_slice(b"\r\nOK\r\n").unwrap();
+    let data = OkCondition.output(ReadData::new(&mut buf, 0)).unwrap();
+    assert_eq!(
+        data.as_ref(),
+        b"+CWJAP:\"NOK\r\n\",\"aa\",1,-50\r\n\r\n".as_ref()
+    );
+    drop(data);
+
+    let mut buf: Vec<u8, 64> = Vec::from_slice(b"\r\nRecv 5 bytes\r\n\r\nSEND OK\r\n").unwrap();
+    assert!(OkCondition.output(ReadData::new(&mut buf, 0)).is_ok());
+}
+
+#[test]
+fn test_carret_condition_with_trailing_bytes() {
+    let mut buf: Vec<u8, 64> = Vec::from_slice(b"\r\nOK\r\n> \r\n").unwrap();
+    assert!(Condition::<64>::is_performed(CarretCondition, &buf));
+
+    let data = CarretCondition.output(ReadData::new(&mut buf, 0));
+    assert_eq!(data.as_ref(), b"\r\nOK\r\n".as_ref());
+    drop(data);
+    assert_eq!(&buf[..], b"\r\n");
+}
+
+#[test]
+fn test_read_data_partial_consumption() {
+    let mut buf: Vec<u8, 32> = Vec::from_slice(b"hello1,CONNECT\r\n").unwrap();
+    let mut pending = None;
+
+    let mut data = ReadData::received(&mut buf, 5, 0, None, &mut pending, false);
+    assert_eq!(data.take(2), b"he".as_ref());
+    let mut rest = [0; 2];
+    assert_eq!(data.read(&mut rest), 2);
+    assert_eq!(&rest, b"ll");
+    assert_eq!(data.as_ref(), b"o".as_ref());
+    drop(data);
+
+    assert_eq!(
+        pending,
+        Some(PendingData {
+            link_id: 0,
+            remote: None,
+            len: 1
+        })
+    );
+    assert_eq!(&buf[..], b"o1,CONNECT\r\n");
+
+    let data = ReadData::received(&mut buf, 1, 0, None, &mut pending, false);
+    drop(data);
+    assert_eq!(&buf[..], b"1,CONNECT\r\n");
+}
+
+#[test]
+fn test_read_data_retain_unconsumed() {
+    let mut buf: Vec<u8, 32> = Vec::from_slice(b"hello").unwrap();
+    let mut pending = None;
+
+    let data = ReadData::received(&mut buf, 5, 3, None, &mut pending, true);
+    drop(data);
+
+    assert_eq!(
+        pending,
+        Some(PendingData {
+            link_id: 3,
+            remote: None,
+            len: 5
+        })
+    );
+    assert_eq!(&buf[..], b"hello");
+}
+
+#[test]
+fn test_read_data_copy_to() {
+    let mut buf: Vec<u8, 32> = Vec::from_slice(b"hello").unwrap();
+    let mut pending = None;
+
+    let data = ReadData::received(&mut buf, 5, 0, None, &mut pending, false);
+    let mut short = [0; 3];
+    assert_eq!(data.copy_to(&mut short), 3);
+    assert_eq!(&short, b"hel");
+    let mut long = [0; 8];
+    assert_eq!(data.copy_to(&mut long), 5);
+    assert_eq!(&long[..5], b"hello");
+    assert_eq!(data.len(), 5);
+}
+
+#[test]
+fn test_owned_join_ap_config() {
+    let config = JoinApConfig {
+        ssid: "my network",
+        password: Some("12345678"),
+        options: None,
+    };
+    let owned = OwnedJoinApConfig::try_from(config).unwrap();
+    assert_eq!(owned.as_config(), config);
+
+    let long_ssid = core::str::from_utf8(&[b'a'; 33]).unwrap();
+    let err = OwnedJoinApConfig::try_from(JoinApConfig {
+        ssid: long_ssid,
+        password: None,
+        options: None,
+    })
+    .unwrap_err();
+    assert_eq!(err, Error::InvalidConfig(ConfigError::InvalidSsid));
+}
+
+#[test]
+fn test_validate_softap_config() {
+    let config = SoftApConfig {
+        ssid: "test_network",
+        password: "12345678",
+        channel: 4,
+        mode: WifiMode::Wpa2Psk,
+        address: None,
+    };
+    assert_eq!(config.validate(), Ok(()));
+
+    let short_password = SoftApConfig {
+        password: "1234",
+        ..config
+    };
+    assert_eq!(
+        short_password.validate(),
+        Err(Error::InvalidConfig(ConfigError::InvalidPassword))
+    );
+    let open = SoftApConfig {
+        mode: WifiMode::Open,
+        ..short_password
+    };
+    assert_eq!(open.validate(), Ok(()));
+
+    let wrong_channel = SoftApConfig {
+        channel: 15,
+        ..config
+    };
+    assert_eq!(
+        wrong_channel.validate(),
+        Err(Error::InvalidConfig(ConfigError::InvalidChannel))
+    );
+
+    let unknown_mode = SoftApConfig {
+        mode: WifiMode::Unknown(42),
+        ..config
+    };
+    assert_eq!(
+        unknown_mode.validate(),
+        Err(Error::InvalidConfig(ConfigError::UnsupportedMode))
+    );
+}
+
+#[test]
+fn test_wifi_mode_codes() {
+    for code in 0..=u8::MAX {
+        assert_eq!(u8::from(WifiMode::from(code)), code);
+    }
+    assert_eq!(WifiMode::from(7), WifiMode::Wpa2Wpa3Psk);
+    assert_eq!(WifiMode::from(42), WifiMode::Unknown(42));
+}
+
+#[test]
+fn test_escape_at_string() {
+    let mut s: String<32> = String::new();
+    write!(s, "\"{}\"", Escaped(r#"my "net",\wifi"#)).unwrap();
+
+    assert_eq!(s, r#""my \"net\"\,\\wifi""#);
+}
+
+#[test]
+fn test_config_builders() {
+    let config = SoftApConfig::builder()
+        .ssid("test_network")
+        .password("12345678")
+        .build();
+    assert_eq!(
+        config,
+        SoftApConfig {
+            ssid: "test_network",
+            password: "12345678",
+            channel: SoftApConfig::DEFAULT_CHANNEL,
+            mode: WifiMode::Wpa2Psk,
+            address: None,
+        }
+    );
+
+    let config = JoinApConfig::builder()
+        .password("12345678")
+        .ssid("test_network")
+        .build();
+    assert_eq!(
+        config,
+        JoinApConfig {
+            ssid: "test_network",
+            password: Some("12345678"),
+            options: None,
+        }
+    );
+
+    let config = JoinApConfig::builder().ssid("open_network").build();
+    assert_eq!(config.password, None);
+}
+
+#[test]
+fn test_softap_address() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let module: Module<_, _, _, 256> =
+        Module::new(serial.clone(), serial.clone(), MockClock::new()).unwrap();
+    serial.take_written();
+
+    SoftApConfig::builder()
+        .ssid("setup")
+        .address(SoftApAddress {
+            ip: Ipv4Addr::new(10, 10, 0, 1),
+            gateway: Ipv4Addr::new(10, 10, 0, 1),
+            netmask: Ipv4Addr::new(255, 255, 255, 0),
+        })
+        .build()
+        .start(module)
+        .unwrap();
+    serial.assert_written(
+        b"AT+CWMODE=3\r\n\
+        AT+CIPMUX=1\r\n\
+        AT+CIPDINFO=1\r\n\
+        AT+CWSAP=\"setup\",\"\",1,0\r\n\
+        AT+CIPAP=\"10.10.0.1\",\"10.10.0.1\",\"255.255.255.0\"\r\n",
+    );
+}
+
+#[test]
+fn test_parse_diagnostics_responses() {
+    let raw = b"AT version:1.7.4.0(May 11 2020 19:13:04)\r\nSDK version:3.0.4(9532ceb)\r\n\r\n";
+    assert_eq!(
+        find_line_value(raw, "AT version:"),
+        Some(b"1.7.4.0(May 11 2020 19:13:04)".as_ref())
+    );
+    assert_eq!(find_line_value(raw, "Bin version:"), None);
+
+    assert_eq!(
+        parse_numeric(b"+SYSRAM:53432\r\n", "+SYSRAM:"),
+        Some(53432_u32)
+    );
+    assert_eq!(
+        parse_numeric::<u8>(b"+CWSTATE:2,\"ssid\"\r\n", "+CWSTATE:"),
+        Some(2)
+    );
+}
+
+struct FixedClock(u64);
+
+impl SimpleClock for FixedClock {
+    fn now_us(&self) -> u64 {
+        self.0
+    }
+}
+
+#[test]
+fn test_backoff_delays() {
+    let clock = FixedClock(12345);
+    let mut backoff = Backoff::new(1_000, 10_000).with_max_attempts(6);
+
+    let mut delays = [0; 6];
+    for delay in delays.iter_mut() {
+        *delay = backoff.next_delay(&clock).unwrap();
+    }
+    assert_eq!(backoff.next_delay(&clock), None);
+
+    for (delay, interval) in delays
+        .iter()
+        .zip([1_000, 2_000, 4_000, 8_000, 10_000, 10_000])
+    {
+        assert!((interval / 2..=interval).contains(delay));
+    }
+
+    backoff.reset();
+    assert!(backoff.next_delay(&clock).unwrap() <= 1_000);
+}
+
+#[test]
+fn test_keepalive_probes() {
+    let mut keepalive = KeepAlive::new(b"ping", 1_000, 500).unwrap();
+    keepalive.watch(1, 0, true);
+
+    assert_eq!(keepalive.poll(999), None);
+    assert_eq!(keepalive.poll(1_000), Some(1));
+    // The probe has been sent, wait for the response.
+    assert_eq!(keepalive.poll(1_200), None);
+    keepalive.reset(1, 1_300);
+    assert!(!keepalive.is_dead(1));
+
+    assert_eq!(keepalive.poll(2_300), Some(1));
+    assert_eq!(keepalive.poll(2_800), None);
+    assert!(keepalive.is_dead(1));
+
+    keepalive.watch(1, 3_000, false);
+    assert_eq!(keepalive.poll(10_000), None);
+}
+
+#[test]
+fn test_parse_at_version() {
+    let raw = b"AT version:1.7.4.0(May 11 2020 19:13:04)\r\nSDK version:3.0.4(9532ceb)\r\n";
+    assert_eq!(
+        AtVersion::parse(raw),
+        Some(AtVersion {
+            major: 1,
+            minor: 7,
+            patch: 4
+        })
+    );
+    assert!(
+        AtVersion::parse(b"AT version:2.2.0.0(s-b097cdf - ESP8266 - Jun 17 2021)").unwrap()
+            > AtVersion::parse(raw).unwrap()
+    );
+    assert_eq!(AtVersion::parse(b"SDK version:3.0.4\r\n"), None);
+}
+
+#[test]
+fn test_capabilities_set() {
+    let mut caps = Capabilities::empty();
+    assert!(!caps.contains(Capabilities::SNTP));
+
+    caps |= Capabilities::SNTP | Capabilities::DOMAIN_RESOLVE;
+    assert!(caps.contains(Capabilities::SNTP));
+    assert!(caps.contains(Capabilities::SNTP | Capabilities::DOMAIN_RESOLVE));
+    assert!(!caps.contains(Capabilities::SNTP | Capabilities::SYSTEM_RAM));
+    assert!(caps.contains(Capabilities::empty()));
+}
+
+#[test]
+fn test_parse_chip() {
+    assert_eq!(
+        Chip::parse(b"AT version:2.2.0.0(s-b097cdf - ESP32C3 - Jun 17 2021)\r\n"),
+        Some(Chip::Esp32C3)
+    );
+    assert_eq!(
+        Chip::parse(b"AT version:2.1.0.0(883f7f2 - ESP32 - Jul 28 2020)\r\n"),
+        Some(Chip::Esp32)
+    );
+    assert_eq!(
+        Chip::parse(b"AT version:1.7.4.0(May 11 2020 19:13:04)\r\n"),
+        None
+    );
+}
+
+#[test]
+fn test_ipv4_conversion() {
+    let mut buf = [0; crate::IPV4_MAX_LEN];
+    for addr in [
+        Ipv4Addr::new(0, 0, 0, 0),
+        Ipv4Addr::new(192, 168, 4, 1),
+        Ipv4Addr::new(10, 99, 100, 255),
+        Ipv4Addr::new(255, 255, 255, 255),
+    ] {
+        let s = format_ipv4(addr, &mut buf);
+        let mut expected: String<15> = String::new();
+        write!(expected, "{}", addr).unwrap();
+        assert_eq!(s, expected.as_str());
+        assert_eq!(parse_ipv4(s.as_bytes()), Some(addr));
+    }
+
+    assert_eq!(parse_ipv4(b"192.168.4"), None);
+    assert_eq!(parse_ipv4(b"192.168.4.1.1"), None);
+    assert_eq!(parse_ipv4(b"192.168.4.256"), None);
+    assert_eq!(parse_ipv4(b"192..4.1"), None);
+    assert_eq!(parse_ipv4(b"192.168.4.a"), None);
+}
+
+#[test]
+fn test_mock_module_init() {
+    let serial = MockSerial::new().with_default_reply(b"ERROR\r\n");
+    serial.expect(b"ATE0\r\n", b"ATE0\r\r\nOK\r\n");
+
+    let module =
+        Module::<_, _, _, 256>::new(serial.clone(), serial.clone(), MockClock::new()).unwrap();
+
+    assert!(serial.is_script_done());
+    assert!(serial.is_flushed());
+    serial.assert_written(b"ATE0\r\nAT+GMR\r\n");
+
+    // The optional commands are probed on demand.
+    let mut module = module;
+    serial.expect(b"AT+SYSRAM?\r\n", b"+SYSRAM:1024\r\nOK\r\n");
+    assert_eq!(module.probe_capabilities(), Ok(Capabilities::SYSTEM_RAM));
+    assert_eq!(module.probe_capabilities(), Ok(Capabilities::SYSTEM_RAM));
+    serial.assert_written(
+        b"AT+CIPDOMAIN=?\r\n\
+        AT+CIPRECVMODE?\r\n\
+        AT+CIPSNTPCFG?\r\n\
+        AT+SLEEPWKCFG=?\r\n\
+        AT+SYSRAM?\r\n\
+        AT+CWSTATE?\r\n\
+        AT+RFPOWER?\r\n\
+        AT+CIPSSLCCONF?\r\n",
+    );
+}
+
+#[test]
+fn test_gpio_wakeup() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    // The GPIO number is checked before sending the command.
+    assert_eq!(
+        session.set_gpio_wakeup(GpioWakeup {
+            gpio: 16,
+            active_high: true,
+        }),
+        Err(Error::InvalidArgument)
+    );
+    serial.assert_written(b"");
+
+    session
+        .set_gpio_wakeup(GpioWakeup {
+            gpio: 5,
+            active_high: true,
+        })
+        .unwrap();
+    serial.assert_written(b"AT+SLEEPWKCFG=2,5,1\r\n");
+}
+
+#[test]
+fn test_deep_sleep() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    session.set_sleep_mode(SleepMode::ModemSleep).unwrap();
+    session.deep_sleep(60_000_999).unwrap();
+    serial.assert_written(b"AT+SLEEP=1\r\nAT+GSLP=60000\r\n");
+
+    serial.expect(b"AT+GSLP=0\r\n", b"ERROR\r\n");
+    assert_eq!(session.deep_sleep(0), Err(Error::CommandFailed));
+}
+
+/// Creates a network session that accepts all the commands.
+fn mock_session(serial: &MockSerial) -> NetworkSession<MockSerial, MockSerial, MockClock, 256> {
+    mock_session_with_clock(serial, MockClock::new())
+}
+
+fn mock_session_with_clock(
+    serial: &MockSerial,
+    clock: MockClock,
+) -> NetworkSession<MockSerial, MockSerial, MockClock, 256> {
+    let mut module = Module::new(serial.clone(), serial.clone(), clock).unwrap();
+    module.probe_capabilities().unwrap();
+    let session = JoinApConfig {
+        ssid: "ssid",
+        password: Some("password"),
+        options: None,
+    }
+    .join(module)
+    .unwrap();
+    serial.take_written();
+    session
+}
+
+#[test]
+fn test_mock_session_events() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    serial.receive(b"0,CONNECT\r\n+IPD,0,5:hello");
+    let event = session.wait_for(|_| true).unwrap();
+    assert!(matches!(event, NetworkEvent::Connected { link_id: 0 }));
+    drop(event);
+    let len = match session.wait_for(|_| true).unwrap() {
+        NetworkEvent::DataAvailable {
+            link_id, len, data, ..
+        } => {
+            assert_eq!(link_id, 0);
+            assert_eq!(data.as_ref(), b"hello");
+            len
+        }
+        other => panic!("Unexpected event: {:?}", other),
+    };
+    // The size is still known after the session borrow has ended.
+    assert_eq!(len, 5);
+
+    serial.expect(b"AT+CIPSEND=0,5\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"world", b"\r\nRecv 5 bytes\r\n\r\nSEND OK\r\n");
+    session.send(0, b"world".iter().copied()).unwrap();
+    serial.assert_written(b"AT+CIPSEND=0,5\r\nworld");
+    assert!(serial.is_script_done());
+    assert!(serial.is_flushed());
+}
+
+#[test]
+fn test_unknown_line_handler() {
+    static UNKNOWN_LINES: AtomicUsize = AtomicUsize::new(0);
+
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+    session.set_unknown_line_handler(Some(|line| {
+        assert_eq!(line, b"+TIME_UPDATED");
+        UNKNOWN_LINES.fetch_add(1, Ordering::SeqCst);
+    }));
+
+    serial.receive(b"\r\n+TIME_UPDATED\r\n\r\n0,CONNECT\r\n");
+    match session.wait_for(|_| true).unwrap() {
+        NetworkEvent::Unknown(line) => assert_eq!(line.as_ref(), b"+TIME_UPDATED"),
+        other => panic!("Unexpected event: {:?}", other),
+    }
+    assert_eq!(UNKNOWN_LINES.load(Ordering::SeqCst), 1);
+
+    let event = session.wait_for(|_| true).unwrap();
+    assert!(matches!(event, NetworkEvent::Connected { link_id: 0 }));
+}
+
+#[test]
+fn test_wait_for_skips_unknown_lines() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    serial.receive(b"WIFI CONNECTED\r\n0,CONNECT\r\n+TIME_UPDATED\r\n+IPD,0,2:hi");
+    match session.wait_for(|event| matches!(event, NetworkEvent::DataAvailable { .. })) {
+        Ok(NetworkEvent::DataAvailable { data, .. }) => assert_eq!(data.as_ref(), b"hi"),
+        other => panic!("Unexpected event: {:?}", other),
+    }
+    // The connection events are kept, but the unknown lines are discarded.
+    let event = session.wait_for(|_| true).unwrap();
+    assert!(matches!(event, NetworkEvent::WifiConnected));
+    drop(event);
+    let event = session.wait_for(|_| true).unwrap();
+    assert!(matches!(event, NetworkEvent::Connected { link_id: 0 }));
+    drop(event);
+    assert!(session.poll_network_event().is_err());
+}
+
+#[test]
+fn test_shared_clock() {
+    let clock = MockClock::new();
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let first =
+        Module::<_, _, _, 64>::new(serial.clone(), serial.clone(), SharedClock(&clock)).unwrap();
+    let second =
+        Module::<_, _, _, 64>::new(serial.clone(), serial, SharedClock::new(&clock)).unwrap();
+
+    clock.advance(1_000);
+    assert_eq!(first.uptime(), 1_000);
+    assert_eq!(second.uptime(), 1_000);
+}
+
+#[test]
+fn test_command_response_helpers() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut module =
+        Module::<_, _, _, 256>::new(serial.clone(), serial.clone(), MockClock::new()).unwrap();
+
+    serial.expect(
+        b"AT+CWJAP?\r\n",
+        b"+CWJAP:\"ssid\",\"aa:bb:cc:dd:ee:ff\",6,-60\r\n\r\nOK\r\n",
+    );
+    let resp = module.send_at_command("AT+CWJAP?").unwrap().unwrap();
+    assert_eq!(
+        resp.as_str().unwrap(),
+        "+CWJAP:\"ssid\",\"aa:bb:cc:dd:ee:ff\",6,-60\r\n\r\n"
+    );
+    assert_eq!(
+        resp.find_prefix("+CWJAP:"),
+        Some(b"\"ssid\",\"aa:bb:cc:dd:ee:ff\",6,-60".as_ref())
+    );
+    assert_eq!(resp.find_prefix("+CIFSR:"), None);
+
+    let mut lines = resp.lines();
+    assert_eq!(
+        lines.next(),
+        Some(b"+CWJAP:\"ssid\",\"aa:bb:cc:dd:ee:ff\",6,-60".as_ref())
+    );
+    assert_eq!(lines.next(), None);
+}
+
+#[test]
+fn test_read_data_text_helpers() {
+    let mut buf: Vec<u8, 64> = Vec::from_slice(b"\r\nHTTP/1.1 200 OK\r\n\r\nbody\r\n").unwrap();
+    let data = ReadData::new(&mut buf, 0);
+
+    assert_eq!(
+        data.as_str().unwrap(),
+        "\r\nHTTP/1.1 200 OK\r\n\r\nbody\r\n"
+    );
+    assert_eq!(data.trim_crlf(), b"HTTP/1.1 200 OK\r\n\r\nbody");
+    assert_eq!(
+        data.lines().collect::<Vec<_, 4>>(),
+        [b"HTTP/1.1 200 OK".as_ref(), b"body".as_ref()]
+    );
+    drop(data);
+    assert!(buf.is_empty());
+
+    let mut buf: Vec<u8, 64> = Vec::from_slice(b"\r\n\r\n\xff").unwrap();
+    let data = ReadData::new(&mut buf, 2);
+    assert!(data.as_str().is_err());
+    assert_eq!(data.trim_crlf(), b"\xff");
+
+    let mut buf: Vec<u8, 64> = Vec::from_slice(b"\r\n\r\n").unwrap();
+    let data = ReadData::new(&mut buf, 0);
+    assert_eq!(data.trim_crlf(), b"");
+    assert_eq!(data.lines().next(), None);
+}
+
+#[test]
+fn test_dns_answer() {
+    let address = Ipv4Addr::new(192, 168, 4, 1);
+    // Query of the `A` record of the `example.com` with the recursion desired.
+    let query = b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\
+        \x07example\x03com\x00\x00\x01\x00\x01";
+
+    let mut buf = [0_u8; 512];
+    buf[..query.len()].copy_from_slice(query);
+    let len = dns_answer(&mut buf, query.len(), address).unwrap();
+    assert_eq!(len, query.len() + 16);
+    assert_eq!(&buf[..4], b"\x12\x34\x85\x80");
+    assert_eq!(&buf[4..12], b"\x00\x01\x00\x01\x00\x00\x00\x00");
+    assert_eq!(&buf[12..query.len()], &query[12..]);
+    assert_eq!(
+        &buf[query.len()..len],
+        b"\xc0\x0c\x00\x01\x00\x01\x00\x00\x00\x3c\x00\x04\xc0\xa8\x04\x01"
+    );
+
+    // The `AAAA` query gets an empty answer.
+    let mut buf = [0_u8; 512];
+    buf[..query.len()].copy_from_slice(query);
+    buf[query.len() - 3] = 28;
+    let len = dns_answer(&mut buf, query.len(), address).unwrap();
+    assert_eq!(len, query.len());
+    assert_eq!(&buf[6..8], b"\x00\x00");
+
+    // Truncated query and response are ignored.
+    let mut buf = [0_u8; 512];
+    buf[..query.len()].copy_from_slice(query);
+    assert_eq!(dns_answer(&mut buf, 20, address), None);
+    buf[2] |= 0x80;
+    assert_eq!(dns_answer(&mut buf, query.len(), address), None);
+}
+
+#[test]
+fn test_captive_portal_page() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+    let mut portal = CaptivePortal::new(Ipv4Addr::new(192, 168, 4, 1), "<h1>Hi</h1>");
+    portal.start(&mut session).unwrap();
+    serial.assert_written(
+        b"AT+CIPSERVERMAXCONN=4\r\n\
+        AT+CIPSERVER=1,80\r\n\
+        AT+CIPSTART=4,\"UDP\",\"0.0.0.0\",53,53,2\r\n",
+    );
+
+    let header = "HTTP/1.1 200 OK\r\n\
+        Content-Type: text/html; charset=utf-8\r\n\
+        Content-Length: 11\r\n\
+        Connection: close\r\n\r\n";
+    let mut send_header = String::<32>::new();
+    write!(send_header, "AT+CIPSEND=0,{}\r\n", header.len()).unwrap();
+
+    serial.receive(b"0,CONNECT\r\n+IPD,0,16:GET / HTTP/1.1\r\n");
+    serial.expect(send_header.as_bytes(), b"\r\nOK\r\n> ");
+    serial.expect(header.as_bytes(), b"\r\nSEND OK\r\n");
+    serial.expect(b"AT+CIPSEND=0,11\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"<h1>Hi</h1>", b"\r\nSEND OK\r\n");
+    serial.expect(b"AT+CIPCLOSE=0\r\n", b"0,CLOSED\r\n\r\nOK\r\n");
+    // Connection event.
+    nb::block!(portal.poll(&mut session)).unwrap();
+    // Request.
+    nb::block!(portal.poll(&mut session)).unwrap();
+    assert!(serial.is_script_done());
+}
+
+#[test]
+fn test_captive_portal_long_request() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    // The session buffer fits the whole request.
+    let module: Module<_, _, _, 1024> =
+        Module::new(serial.clone(), serial.clone(), MockClock::new()).unwrap();
+    let mut session = JoinApConfig {
+        ssid: "ssid",
+        password: Some("password"),
+        options: None,
+    }
+    .join(module)
+    .unwrap();
+    let mut portal = CaptivePortal::new(Ipv4Addr::new(192, 168, 4, 1), "<h1>Hi</h1>");
+    portal.start(&mut session).unwrap();
+    serial.take_written();
+
+    let header = "HTTP/1.1 200 OK\r\n\
+        Content-Type: text/html; charset=utf-8\r\n\
+        Content-Length: 11\r\n\
+        Connection: close\r\n\r\n";
+    let mut send_header = String::<32>::new();
+    write!(send_header, "AT+CIPSEND=0,{}\r\n", header.len()).unwrap();
+    let expect_page = || {
+        serial.expect(send_header.as_bytes(), b"\r\nOK\r\n> ");
+        serial.expect(header.as_bytes(), b"\r\nSEND OK\r\n");
+        serial.expect(b"AT+CIPSEND=0,11\r\n", b"\r\nOK\r\n> ");
+        serial.expect(b"<h1>Hi</h1>", b"\r\nSEND OK\r\n");
+        serial.expect(b"AT+CIPCLOSE=0\r\n", b"0,CLOSED\r\n\r\nOK\r\n");
+    };
+
+    // The request is longer than the read buffer and is followed by one more frame,
+    // the connection is answered only once.
+    let request = format!("GET / HTTP/1.1\r\nCookie: {}\r\n", "a".repeat(600));
+    serial.receive(b"0,CONNECT\r\n");
+    serial.receive(format!("+IPD,0,{}:", request.len()).as_bytes());
+    serial.receive(request.as_bytes());
+    serial.receive(b"+IPD,0,2:\r\n");
+    expect_page();
+    // Connection, request, the ignored frame and the closed connection events.
+    for _ in 0..4 {
+        nb::block!(portal.poll(&mut session)).unwrap();
+    }
+    assert_eq!(portal.poll(&mut session), Err(nb::Error::WouldBlock));
+    assert!(serial.is_script_done());
+
+    // The next connection with the same link identifier is answered again.
+    serial.receive(b"0,CONNECT\r\n+IPD,0,16:GET / HTTP/1.1\r\n");
+    expect_page();
+    nb::block!(portal.poll(&mut session)).unwrap();
+    nb::block!(portal.poll(&mut session)).unwrap();
+    assert!(serial.is_script_done());
+}
+
+#[derive(Default)]
+struct MockStore(Option<OwnedJoinApConfig>);
+
+impl CredentialStore for MockStore {
+    fn save(&mut self, config: &JoinApConfig<'_>) -> crate::Result<()> {
+        self.0 = Some(OwnedJoinApConfig::try_from(*config).unwrap());
+        Ok(())
+    }
+}
+
+#[test]
+fn test_provisioning() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let module: Module<_, _, _, 256> =
+        Module::new(serial.clone(), serial.clone(), MockClock::new()).unwrap();
+    serial.take_written();
+    let provisioning = Provisioning::new(SoftApConfig {
+        ssid: "setup",
+        password: "password",
+        channel: 5,
+        mode: WifiMode::Wpa2Psk,
+        address: None,
+    })
+    .pages(ProvisioningPages {
+        form: "form",
+        joined: "done",
+        failed: "fail",
+    });
+
+    serial.expect(
+        b"AT+CIFSR\r\n",
+        b"+CIFSR:APIP,\"192.168.4.1\"\r\n\r\nOK\r\n",
+    );
+    let request = b"POST / HTTP/1.1\r\nContent-Length: 23\r\n\r\nssid=home&password=pass";
+    let mut ipd = String::<32>::new();
+    write!(ipd, "+IPD,0,{}:", request.len()).unwrap();
+    serial.expect(b"AT+CIPSTART=4,\"UDP\",\"0.0.0.0\",53,53,2\r\n", b"OK\r\n");
+    serial.receive(ipd.as_bytes());
+    serial.receive(request);
+    serial.expect(
+        b"AT+CWJAP=\"home\",\"pass\"\r\n",
+        b"WIFI CONNECTED\r\n\r\nOK\r\n",
+    );
+    let header = "HTTP/1.1 200 OK\r\n\
+        Content-Type: text/html; charset=utf-8\r\n\
+        Content-Length: 4\r\n\
+        Connection: close\r\n\r\n";
+    let mut send_header = String::<32>::new();
+    write!(send_header, "AT+CIPSEND=0,{}\r\n", header.len()).unwrap();
+    serial.expect(send_header.as_bytes(), b"\r\nOK\r\n> ");
+    serial.expect(header.as_bytes(), b"\r\nSEND OK\r\n");
+    serial.expect(b"AT+CIPSEND=0,4\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"done", b"\r\nSEND OK\r\n");
+    serial.expect(b"AT+CIPCLOSE=0\r\n", b"0,CLOSED\r\n\r\nOK\r\n");
+    serial.expect(b"AT+CIPCLOSE=4\r\n", b"4,CLOSED\r\n\r\nOK\r\n");
+
+    let mut store = MockStore::default();
+    provisioning.run(module, &mut store).unwrap();
+    let config = store.0.unwrap();
+    assert_eq!(config.ssid, "home");
+    assert_eq!(config.password.as_deref(), Some("pass"));
+    // The device goes back to the station mode after the access point has been joined.
+    assert!(serial
+        .take_written()
+        .ends_with(b"AT+CIPSERVER=0\r\nAT+CIPCLOSE=4\r\nAT+CWMODE=1\r\n"));
+    assert!(serial.is_script_done());
+}
+
+#[test]
+fn test_http_request_body() {
+    assert_eq!(http_request_body(b"GET / HTTP/1.1\r\nHost: a\r\n"), None);
+    assert_eq!(
+        http_request_body(b"GET / HTTP/1.1\r\nHost: a\r\n\r\n"),
+        Some(None)
+    );
+
+    let request = b"POST / HTTP/1.1\r\ncontent-length: 9\r\n\r\nssid=";
+    assert_eq!(http_request_body(request), None);
+    let request = b"POST / HTTP/1.1\r\nContent-Length: 9\r\n\r\nssid=home";
+    assert_eq!(
+        http_request_body(request),
+        Some(Some(b"ssid=home".as_ref()))
+    );
+}
+
+#[test]
+fn test_parse_credentials() {
+    let config = parse_credentials(b"ssid=My+Home%21&password=p%26ss%3Dword").unwrap();
+    assert_eq!(config.ssid, "My Home!");
+    assert_eq!(config.password.as_deref(), Some("p&ss=word"));
+
+    let config = parse_credentials(b"ssid=cafe&password=").unwrap();
+    assert_eq!(config.ssid, "cafe");
+    assert_eq!(config.password, None);
+
+    let config =
+        parse_credentials(b" { \"ssid\" : \"My \\\"Home\\\"\", \"password\": \"secret\\/1\" }")
+            .unwrap();
+    assert_eq!(config.ssid, "My \"Home\"");
+    assert_eq!(config.password.as_deref(), Some("secret/1"));
+
+    assert_eq!(parse_credentials(b"password=secret"), None);
+    assert_eq!(parse_credentials(b"ssid=%4"), None);
+    assert_eq!(parse_credentials(b"{\"ssid\": \"a\\u0041\"}"), None);
+}
+
+#[test]
+fn test_echo_server() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    serial.receive(b"+IPD,1,4:ping");
+    serial.expect(b"AT+CIPSEND=1,4\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"ping", b"\r\nSEND OK\r\n");
+    nb::block!(session.echo_once()).unwrap();
+    serial.assert_written(b"AT+CIPSEND=1,4\r\nping");
+}
+
+#[test]
+fn test_bridge() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+    let mut peer = None;
+    let mut received = Vec::<u8, 16>::new();
+    let local = Cell::new(b"pong".as_ref());
+
+    let mut read = |buf: &mut [u8]| {
+        let bytes = local.take();
+        buf[..bytes.len()].copy_from_slice(bytes);
+        bytes.len()
+    };
+    let mut write = |data: &[u8]| received.extend_from_slice(data).unwrap();
+
+    // The local data is dropped until a peer is connected.
+    serial.receive(b"2,CONNECT\r\n");
+    session
+        .bridge_once(&mut peer, &mut read, &mut write)
+        .unwrap();
+    assert_eq!(peer, None);
+    serial.assert_written(b"");
+
+    local.set(b"pong");
+    serial.expect(b"AT+CIPSEND=2,4\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"pong", b"\r\nSEND OK\r\n");
+    session
+        .bridge_once(&mut peer, &mut read, &mut write)
+        .unwrap();
+    assert_eq!(peer, Some(2));
+    serial.assert_written(b"AT+CIPSEND=2,4\r\npong");
+
+    serial.receive(b"+IPD,2,4:ping");
+    for _ in 0..2 {
+        session
+            .bridge_once(&mut peer, &mut read, &mut write)
+            .unwrap();
+    }
+    assert_eq!(received, b"ping");
+}
+
+#[test]
+fn test_send_queue_priority() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    session.enqueue_send(1, b"bulk").unwrap();
+    session.enqueue_send(0, b"ctl").unwrap();
+    session.set_priority_link(Some(0));
+    assert_eq!(
+        session.enqueue_send(2, &[0; QUEUED_SEGMENT_MAX_LEN * 3]),
+        Err(Error::BufferFull)
+    );
+
+    serial.expect(b"AT+CIPSEND=0,3\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"ctl", b"\r\nSEND OK\r\n");
+    serial.expect(b"AT+CIPSEND=1,4\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"bulk", b"\r\nSEND OK\r\n");
+    session.send_queued().unwrap();
+    serial.assert_written(b"AT+CIPSEND=0,3\r\nctlAT+CIPSEND=1,4\r\nbulk");
+}
+
+#[test]
+fn test_buf_writer() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    serial.expect(b"AT+CIPSEND=1,8\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"abcdefgh", b"\r\nSEND OK\r\n");
+    serial.expect(b"AT+CIPSEND=1,3\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"ij;", b"\r\nSEND OK\r\n");
+
+    let mut writer = session.buf_writer::<8>(1);
+    writer.write(b"abc").unwrap();
+    writer.write(b"defghij").unwrap();
+    assert_eq!(writer.buffer(), b"ij");
+    write!(writer, ";").unwrap();
+    drop(writer);
+    serial.assert_written(b"AT+CIPSEND=1,8\r\nabcdefghAT+CIPSEND=1,3\r\nij;");
+}
+
+#[test]
+fn test_buf_reader() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+    let mut reader = session.buf_reader::<4>(1);
+
+    // The message is split into segments, the data of the other links is discarded.
+    serial.receive(b"+IPD,1,3:hel+IPD,0,3:xyz+IPD,1,6:lo!end");
+    let mut buf = [0_u8; 6];
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"hello!");
+
+    // The rest of the segment that doesn't fit into the buffer is received by the next poll.
+    assert_eq!(nb::block!(reader.fill_buf()).unwrap(), b"e");
+    reader.consume(1);
+    assert_eq!(nb::block!(reader.fill_buf()).unwrap(), b"nd");
+    reader.consume(1);
+    assert_eq!(reader.buffer(), b"d");
+
+    serial.receive(b"1,CLOSED\r\n");
+    assert_eq!(reader.read_exact(&mut buf), Err(Error::ConnectionClosed));
+    assert_eq!(nb::block!(reader.read(&mut buf)).unwrap(), 0);
+}
+
+#[test]
+fn test_buf_reader_read_line() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+    let mut reader = session.buf_reader::<4>(2);
+
+    serial.receive(b"+IPD,2,7:HELO a\r+IPD,2,18:\nvery long line\nQU+IPD,2,2:IT");
+    let mut buf = [0_u8; 8];
+    assert_eq!(reader.read_line(&mut buf), Ok(Some(b"HELO a".as_ref())));
+    assert_eq!(reader.read_line(&mut buf), Err(Error::BufferFull));
+
+    serial.receive(b"2,CLOSED\r\n");
+    assert_eq!(reader.read_line(&mut buf), Ok(Some(b"QUIT".as_ref())));
+    assert_eq!(reader.read_line(&mut buf), Ok(None));
+}
+
+#[test]
+fn test_trace_buffer() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    serial.expect(b"AT+CIPSERVERMAXCONN=2\r\n", b"ERROR\r\n");
+    assert_eq!(session.set_max_connections(2), Err(Error::CommandFailed));
+
+    let trace = session.trace_buffer();
+    let sent: Vec<u8, TRACE_BUFFER_LEN> = trace.sent().collect();
+    let received: Vec<u8, TRACE_BUFFER_LEN> = trace.received().collect();
+    assert!(sent.ends_with(b"\r\nAT+CIPSERVERMAXCONN=2\r\n"));
+    assert!(received.ends_with(b"OK\r\nERROR\r\n"));
+
+    let mut dump = String::<1024>::new();
+    write!(dump, "{:?}", trace).unwrap();
+    assert!(dump.contains(r#"AT+CIPSERVERMAXCONN=2\r\n""#));
+}
+
+#[test]
+fn test_dyn_module() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let rx = Box::leak(Box::new(serial.clone()));
+    let tx = Box::leak(Box::new(serial.clone()));
+    let clock = Box::leak(Box::new(MockClock::new()));
+
+    let mut module = DynModule::<256>::new_dyn(rx, tx, clock).unwrap();
+    assert!(serial.take_written().starts_with(b"ATE0\r\n"));
+
+    serial.expect(b"AT+GMR\r\n", b"AT version:1.7.4.0\r\nOK\r\n");
+    let response = module.send_at_command("AT+GMR").unwrap().unwrap();
+    assert_eq!(
+        response.find_prefix("AT version:"),
+        Some(b"1.7.4.0".as_ref())
+    );
+
+    // The buffer length may be omitted like in the generic types.
+    let _: Option<DynModule> = None::<DynModule<DEFAULT_BUFFER_LEN>>;
+    let _: Option<DynNetworkSession> = None::<DynNetworkSession<DEFAULT_BUFFER_LEN>>;
+}
+
+#[test]
+fn test_default_buffer_len() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let module: Module<_, _, _> = Module::new(serial.clone(), serial, MockClock::new()).unwrap();
+    let module: Module<MockSerial, MockSerial, MockClock, DEFAULT_BUFFER_LEN> = module;
+
+    let session: NetworkSession<_, _, _> = JoinApConfig {
+        ssid: "ssid",
+        password: None,
+        options: None,
+    }
+    .join(module)
+    .unwrap();
+    let session: BlockingSession<_, _, _> = BlockingSession::new(session);
+    let _: BlockingSession<MockSerial, MockSerial, MockClock, DEFAULT_BUFFER_LEN> = session;
+}
+
+#[test]
+fn test_dispatcher() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    let mut http = Vec::<u16, 4>::new();
+    let mut mqtt = Vec::<u16, 4>::new();
+    let mut other = 0;
+    let mut on_http = |event: NetworkEvent<'_, 256>| http.push(event.link_id().unwrap()).unwrap();
+    let mut on_mqtt = |event: NetworkEvent<'_, 256>| mqtt.push(event.link_id().unwrap()).unwrap();
+    let mut on_other = |_: NetworkEvent<'_, 256>| other += 1;
+
+    let mut dispatcher = Dispatcher::<256, 2>::new();
+    dispatcher.register(&[0, 1], &mut on_http).unwrap();
+    dispatcher.register(&[4], &mut on_mqtt).unwrap();
+    dispatcher.set_fallback(Some(&mut on_other));
+
+    serial.receive(b"0,CONNECT\r\n4,CONNECT\r\n+IPD,1,2:hi+IPD,4,2:yo2,CONNECT\r\n+VENDOR\r\n");
+    for _ in 0..32 {
+        dispatcher.poll(&mut session).ok();
+    }
+    drop(dispatcher);
+
+    assert_eq!(http, [0, 1]);
+    assert_eq!(mqtt, [4, 4]);
+    assert_eq!(other, 2);
+}
+
+#[test]
+fn test_wait_any() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    serial.receive(b"+IPD,0,3:abc2,CONNECT\r\n+IPD,3,2:hi");
+    let event = session.wait_any(&[1, 3], 1_000).unwrap();
+    assert!(
+        matches!(event, NetworkEvent::DataAvailable { link_id: 3, ref data, .. } if data.as_ref() == b"hi")
+    );
+    drop(event);
+
+    // The connection events of the other links are kept.
+    let event = session.wait_for(|_| true).unwrap();
+    assert!(matches!(event, NetworkEvent::Connected { link_id: 2 }));
+    drop(event);
+
+    serial.receive(b"1,CLOSED\r\n");
+    let event = session.wait_any(&[1, 3], 1_000).unwrap();
+    assert!(matches!(event, NetworkEvent::Closed { link_id: 1, .. }));
+    drop(event);
+}
+
+#[test]
+fn test_wait_any_timeout() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session_with_clock(&serial, MockClock::new().with_step(100));
+
+    serial.receive(b"+IPD,0,3:abc");
+    assert_eq!(session.wait_any(&[1], 1_000).unwrap_err(), Error::Timeout);
+}
+
+#[test]
+fn test_wait_any_reboot() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session_with_clock(&serial, MockClock::new().with_step(100));
+
+    // The reboot is returned although it doesn't match the links.
+    serial.receive(b"1,CONNECT\r\n\r\nready\r\n+IPD,0,2:hi");
+    let event = session.wait_any(&[0], 1_000_000).unwrap();
+    assert!(matches!(event, NetworkEvent::ModuleRebooted));
+    drop(event);
+
+    // The events of the links lost by the reboot are not returned.
+    let event = session.wait_for(|_| true).unwrap();
+    assert!(
+        matches!(event, NetworkEvent::DataAvailable { link_id: 0, ref data, .. } if data.as_ref() == b"hi")
+    );
+    drop(event);
+    assert!(matches!(
+        session.poll_network_event(),
+        Err(nb::Error::WouldBlock)
+    ));
+}
+
+#[test]
+fn test_buf_reader_read_timeout() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session_with_clock(&serial, MockClock::new().with_step(100));
+
+    let mut reader = session.buf_reader::<16>(0);
+    reader.set_read_timeout(Some(10_000));
+    let mut buf = [0_u8; 8];
+    assert_eq!(reader.read_blocking(&mut buf), Err(Error::Timeout));
+
+    // The partial data is returned after the timeout.
+    serial.receive(b"+IPD,0,3:abc");
+    assert_eq!(reader.read_blocking(&mut buf), Ok(3));
+    assert_eq!(&buf[..3], b"abc");
+
+    serial.receive(b"+IPD,0,10:0123456789");
+    assert_eq!(reader.read_blocking(&mut buf), Ok(8));
+    assert_eq!(&buf, b"01234567");
+}
+
+#[test]
+fn test_mdns_answer() {
+    let responder =
+        MdnsResponder::new(3, "sensor", Ipv4Addr::new(192, 168, 4, 1)).with_service(MdnsService {
+            instance: "Kitchen",
+            service_type: "_http._tcp",
+            port: 80,
+            txt: &["path=/"],
+        });
+
+    let mut buf = [0_u8; 512];
+    let mut query = Vec::<u8, 64>::new();
+    query
+        .extend_from_slice(&[0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0])
+        .unwrap();
+    query
+        .extend_from_slice(b"\x05_http\x04_tcp\x05local\x00\x00\x0c\x00\x01")
+        .unwrap();
+    buf[..query.len()].copy_from_slice(&query);
+
+    // The service browsing is answered with the instance and its records.
+    let len = responder.answer(&mut buf, query.len()).unwrap();
+    assert_eq!(&buf[..12], &[0, 0, 0x84, 0, 0, 0, 0, 4, 0, 0, 0, 0]);
+    let response = &buf[..len];
+    let contains = |needle: &[u8]| {
+        response
+            .windows(needle.len())
+            .any(|window| window == needle)
+    };
+    assert!(contains(b"\x07Kitchen\x05_http\x04_tcp\x05local\x00"));
+    assert!(contains(b"\x00\x00\x00\x00\x00\x50\x06sensor\x05local\x00"));
+    assert!(contains(b"\x06path=/"));
+    assert!(response.ends_with(&[0x80, 0x01, 0, 0, 0, 120, 0, 4, 192, 168, 4, 1]));
+
+    // The host name is matched case-insensitively.
+    query.truncate(12);
+    query
+        .extend_from_slice(b"\x06SENSOR\x05local\x00\x00\x01\x00\x01")
+        .unwrap();
+    buf[..query.len()].copy_from_slice(&query);
+    let len = responder.answer(&mut buf, query.len()).unwrap();
+    assert_eq!(buf[7], 1);
+    assert_eq!(len, 12 + 14 + 10 + 4);
+
+    // The unrelated queries are ignored.
+    query.truncate(12);
+    query
+        .extend_from_slice(b"\x05other\x05local\x00\x00\x01\x00\x01")
+        .unwrap();
+    buf[..query.len()].copy_from_slice(&query);
+    assert_eq!(responder.answer(&mut buf, query.len()), None);
+}
+
+#[test]
+fn test_mdns_poll() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+    let responder = MdnsResponder::new(3, "sensor", Ipv4Addr::new(192, 168, 4, 1));
+
+    let mut query = Vec::<u8, 64>::new();
+    query
+        .extend_from_slice(&[0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0])
+        .unwrap();
+    query
+        .extend_from_slice(b"\x06sensor\x05local\x00\x00\x01\x00\x01")
+        .unwrap();
+    let mut ipd = String::<32>::new();
+    write!(ipd, "+IPD,3,{}:", query.len()).unwrap();
+
+    // The events of the other links are left for the main loop.
+    serial.receive(b"0,CONNECT\r\n+IPD,0,2:hi");
+    serial.receive(ipd.as_bytes());
+    serial.receive(&query);
+    assert_eq!(responder.poll(&mut session), Err(nb::Error::WouldBlock));
+    assert!(matches!(
+        session.poll_network_event().unwrap(),
+        NetworkEvent::Connected { link_id: 0 }
+    ));
+    assert_eq!(responder.poll(&mut session), Err(nb::Error::WouldBlock));
+    match session.poll_network_event().unwrap() {
+        NetworkEvent::DataAvailable { link_id, data, .. } => {
+            assert_eq!(link_id, 0);
+            assert_eq!(data.as_ref(), b"hi");
+        }
+        event => panic!("unexpected event: {:?}", event),
+    }
+
+    // The query is answered with the address record.
+    serial.expect(b"AT+CIPSEND=3,40\r\n", b"\r\nOK\r\n> ");
+    serial.expect(&[192, 168, 4, 1], b"\r\nSEND OK\r\n");
+    responder.poll(&mut session).unwrap();
+    assert!(serial.is_script_done());
+
+    // The routed queries are answered in the same way.
+    serial.expect(b"AT+CIPSEND=3,40\r\n", b"\r\nOK\r\n> ");
+    serial.expect(&[192, 168, 4, 1], b"\r\nSEND OK\r\n");
+    responder.handle(&mut session, &query).unwrap();
+    assert!(serial.is_script_done());
+    serial.take_written();
+    responder.handle(&mut session, b"\x00\x01").unwrap();
+    serial.assert_written(b"");
+}
+
+#[test]
+fn test_synced_clock() {
+    assert_eq!(parse_asctime("Thu Jan 01 00:00:00 1970"), Some(0));
+    assert_eq!(
+        parse_asctime("Mon Dec 12 02:33:32 2016"),
+        Some(1_481_510_012)
+    );
+    assert_eq!(
+        parse_asctime("Thu Feb 29 12:00:00 2024"),
+        Some(1_709_208_000)
+    );
+    assert_eq!(parse_asctime("Thu Foo 29 12:00:00 2024"), None);
+
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let clock = MockClock::new();
+    let mut session = mock_session_with_clock(&serial, clock.clone());
+    let synced = SyncedClock::new(clock.clone()).with_resync_interval(10_000_000);
+    assert!(synced.needs_sync());
+    assert_eq!(synced.unix_time(), None);
+
+    session.set_sntp_config(0, &["pool.ntp.org"]).unwrap();
+    serial.assert_written(b"AT+CIPSNTPCFG=1,0,\"pool.ntp.org\",\"\",\"\"\r\n");
+
+    // The epoch start means that the time has not been obtained yet.
+    serial.expect(
+        b"AT+CIPSNTPTIME?\r\n",
+        b"+CIPSNTPTIME:Thu Jan 01 00:00:00 1970\r\nOK\r\n",
+    );
+    assert!(!synced.sync_if_needed(&mut session).unwrap());
+    assert!(!synced.is_synced());
+
+    serial.expect(
+        b"AT+CIPSNTPTIME?\r\n",
+        b"+CIPSNTPTIME:Mon Dec 12 02:33:32 2016\r\nOK\r\n",
+    );
+    assert!(synced.sync_if_needed(&mut session).unwrap());
+    assert_eq!(synced.unix_time(), Some(1_481_510_012));
+    assert!(!synced.needs_sync());
+
+    clock.advance(10_500_000);
+    assert_eq!(synced.unix_time_us(), Some(1_481_510_022_500_000));
+    assert!(synced.needs_sync());
+}
+
+#[test]
+fn test_ssl_auth() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    session.set_ssl_auth(0, SslAuth::Server { ca: 0 }).unwrap();
+    session
+        .set_ssl_auth(1, SslAuth::Mutual { pki: 1, ca: 2 })
+        .unwrap();
+    session.set_ssl_psk(2, "device-1", "cloud").unwrap();
+    session
+        .connect_ssl(0, "10.0.0.1:8883".parse().unwrap())
+        .unwrap();
+    serial.assert_written(
+        b"AT+CIPSSLCCONF=0,2,0,0\r\n\
+        AT+CIPSSLCCONF=1,3,1,2\r\n\
+        AT+CIPSSLCPSK=2,\"device-1\",\"cloud\"\r\n\
+        AT+CIPSTART=0,\"SSL\",\"10.0.0.1\",8883\r\n",
+    );
+
+    session.set_ssl_sni(0, "api.example.com").unwrap();
+    session.connect_ssl_host(1, "api.example.com", 443).unwrap();
+    serial.assert_written(
+        b"AT+CIPSSLCSNI=0,\"api.example.com\"\r\n\
+        AT+CIPSTART=1,\"SSL\",\"api.example.com\",443\r\n",
+    );
+
+    serial.expect(b"AT+CIPSSLCCONF=0,0,0,0\r\n", b"ERROR\r\n");
+    assert_eq!(
+        session.set_ssl_auth(0, SslAuth::None),
+        Err(Error::CommandFailed)
+    );
+
+    // The failed handshake, e.g. due to the pinned certificate mismatch, is an error.
+    serial.expect(b"AT+CIPSTART=0,\"SSL\",\"10.0.0.1\",8883\r\n", b"ERROR\r\n");
+    assert_eq!(
+        session.connect_ssl(0, "10.0.0.1:8883".parse().unwrap()),
+        Err(Error::CommandFailed)
+    );
+    serial.expect(
+        b"AT+CIPSTART=1,\"SSL\",\"api.example.com\",443\r\n",
+        b"ERROR\r\n",
+    );
+    assert_eq!(
+        session.connect_ssl_host(1, "api.example.com", 443),
+        Err(Error::CommandFailed)
+    );
+
+    // The firmware without the SSL configuration is reported before sending the commands.
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    serial.expect(b"AT+CIPSSLCCONF?\r\n", b"ERROR\r\n");
+    let mut session = mock_session(&serial);
+    assert_eq!(
+        session.set_ssl_auth(0, SslAuth::Server { ca: 0 }),
+        Err(Error::Unsupported)
+    );
+    assert_eq!(
+        session.set_ssl_psk(0, "device-1", "cloud"),
+        Err(Error::Unsupported)
+    );
+    assert_eq!(
+        session.set_ssl_sni(0, "api.example.com"),
+        Err(Error::Unsupported)
+    );
+    serial.assert_written(b"");
+}
+
+#[test]
+fn test_ssl_buffer_size() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    // The buffer size is configured by the NonOS firmware only.
+    assert_eq!(session.set_ssl_buffer_size(4096), Err(Error::Unsupported));
+    session.module.set_dialect(Dialect::NonOs);
+    assert_eq!(
+        session.set_ssl_buffer_size(1024),
+        Err(Error::InvalidArgument)
+    );
+    assert_eq!(
+        session.set_ssl_buffer_size(8192),
+        Err(Error::InvalidArgument)
+    );
+    serial.assert_written(b"");
+
+    session.set_ssl_buffer_size(4096).unwrap();
+    session
+        .connect_ssl(0, "10.0.0.1:443".parse().unwrap())
+        .unwrap();
+    serial.assert_written(
+        b"AT+CIPSSLSIZE=4096\r\n\
+        AT+CIPSTART=0,\"SSL\",\"10.0.0.1\",443\r\n",
+    );
+
+    serial.expect(b"AT+CIPSSLSIZE=2048\r\n", b"ERROR\r\n");
+    assert_eq!(session.set_ssl_buffer_size(2048), Err(Error::CommandFailed));
+}
+
+#[test]
+fn test_pipelined_send() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+    session.set_send_pipeline_depth(2).unwrap();
+
+    // The next segment is sent before the previous one is confirmed.
+    serial.expect(b"AT+CIPSEND=0,5\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"hello", b"\r\nRecv 5 bytes\r\n");
+    session.send(0, b"hello".iter().copied()).unwrap();
+    assert_eq!(session.in_flight_sends(), 1);
+
+    serial.expect(b"AT+CIPSEND=1,5\r\n", b"\r\nSEND OK\r\n\r\nOK\r\n> ");
+    serial.expect(b"world", b"\r\nRecv 5 bytes\r\n");
+    session.send(1, b"world".iter().copied()).unwrap();
+    assert_eq!(session.in_flight_sends(), 1);
+    serial.assert_written(b"AT+CIPSEND=0,5\r\nhelloAT+CIPSEND=1,5\r\nworld");
+
+    // The confirmations are handled by the event polling.
+    serial.receive(b"\r\nSEND FAIL\r\n");
+    for _ in 0..8 {
+        assert!(matches!(
+            session.poll_network_event(),
+            Err(nb::Error::WouldBlock)
+        ));
+    }
+    assert_eq!(session.in_flight_sends(), 0);
+    assert_eq!(session.complete_sends(), Err(Error::CommandFailed));
+    assert_eq!(session.complete_sends(), Ok(()));
+
+    // The pending confirmations are awaited before the next command.
+    serial.expect(b"AT+CIPSEND=2,3\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"abc", b"\r\nRecv 3 bytes\r\n\r\nSEND OK\r\n");
+    session.send(2, b"abc".iter().copied()).unwrap();
+    session.set_max_connections(2).unwrap();
+    assert_eq!(session.in_flight_sends(), 0);
+    serial.assert_written(b"AT+CIPSEND=2,3\r\nabcAT+CIPSERVERMAXCONN=2\r\n");
+
+    assert_eq!(
+        session.set_send_pipeline_depth(MAX_IN_FLIGHT_SENDS + 1),
+        Err(Error::InvalidArgument)
+    );
+}
+
+#[test]
+fn test_send_confirmation_in_received_data() {
+    // The confirmation lines inside the received data are not taken into account.
+    assert_eq!(
+        find_confirmation(b"\r\n+IPD,0,11:\r\nSEND OK\r\n\r\nSEND FAIL\r\n"),
+        Some((25, 11, false))
+    );
+    assert_eq!(find_confirmation(b"+IPD,0,11:\r\nSEND OK\r\n"), None);
+    assert_eq!(find_confirmation(b"+IPD,0,20:SEND OK\r\n"), None);
+    assert_eq!(find_confirmation(b"+IPD,0"), None);
+    assert_eq!(
+        find_confirmation(b"Recv 5 bytes\r\nSEND OK\r\n"),
+        Some((14, 9, true))
+    );
+
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+    session.set_send_pipeline_depth(2).unwrap();
+    serial.expect(b"AT+CIPSEND=0,5\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"hello", b"\r\nRecv 5 bytes\r\n");
+    session.send(0, b"hello".iter().copied()).unwrap();
+    serial.expect(
+        b"AT+CIPSEND=1,5\r\n",
+        b"\r\n+IPD,2,11:\r\nSEND OK\r\n\r\nOK\r\n> ",
+    );
+    serial.expect(b"world", b"\r\nRecv 5 bytes\r\n");
+    session.send(1, b"world".iter().copied()).unwrap();
+    assert_eq!(session.in_flight_sends(), 2);
+}
+
+#[test]
+fn test_send_stream() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    let mut data = [b'a'; STREAM_SEGMENT_LEN + 10];
+    data[STREAM_SEGMENT_LEN - 1] = b'X';
+    data[STREAM_SEGMENT_LEN + 9] = b'Y';
+
+    let header = format!("AT+CIPSEND=0,{}\r\n", STREAM_SEGMENT_LEN);
+    serial.expect(header.as_bytes(), b"\r\nOK\r\n> ");
+    serial.expect(b"X", b"\r\nRecv 2048 bytes\r\n\r\nSEND OK\r\n");
+    serial.expect(b"AT+CIPSEND=0,10\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"Y", b"\r\nRecv 10 bytes\r\n\r\nSEND OK\r\n");
+    session
+        .send_stream(0, &mut data.as_ref(), data.len())
+        .unwrap();
+    assert!(serial.is_script_done());
+
+    let mut expected = header.into_bytes();
+    expected.extend_from_slice(&data[..STREAM_SEGMENT_LEN]);
+    expected.extend_from_slice(b"AT+CIPSEND=0,10\r\n");
+    expected.extend_from_slice(&data[STREAM_SEGMENT_LEN..]);
+    serial.assert_written(&expected);
+    assert!(session
+        .trace_buffer()
+        .sent()
+        .eq(expected[expected.len() - TRACE_BUFFER_LEN..]
+            .iter()
+            .copied()));
+
+    // The source is exhausted too early, the segment is read before sending.
+    assert_eq!(
+        session.send_stream(0, &mut b"abc".as_ref(), 5),
+        Err(Error::InvalidArgument)
+    );
+    serial.assert_written(b"");
+}
+
+#[test]
+fn test_pending_len() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    // The length is buffered by the module in the passive mode only.
+    assert_eq!(session.pending_len(0), Err(Error::CommandFailed));
+    session.set_passive_receive(true).unwrap();
+    assert!(session.is_passive_receive());
+    serial.assert_written(b"AT+CIPRECVMODE=1\r\n");
+
+    // The disconnected links are reported as empty or negative values.
+    for (link_id, len) in [(0, 100), (1, 0), (2, 0), (4, 2048)] {
+        serial.expect(
+            b"AT+CIPRECVLEN?\r\n",
+            b"+CIPRECVLEN:100,,-1,0,2048\r\n\r\nOK\r\n",
+        );
+        assert_eq!(session.pending_len(link_id), Ok(len));
+    }
+
+    session.set_passive_receive(false).unwrap();
+    assert!(!session.is_passive_receive());
+    assert_eq!(session.pending_len(0), Err(Error::CommandFailed));
+    let mut expected = b"AT+CIPRECVLEN?\r\n".repeat(4);
+    expected.extend_from_slice(b"AT+CIPRECVMODE=0\r\n");
+    serial.assert_written(&expected);
+}
+
+#[test]
+fn test_pull_data() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+    let mut buf = [0_u8; 16];
+
+    // The data is pulled in the passive mode only.
+    assert_eq!(session.pull_data(0, &mut buf), Err(Error::CommandFailed));
+    session.set_passive_receive(true).unwrap();
+    serial.take_written();
+
+    serial.receive(b"+IPD,0,10\r\n");
+    let event = session.wait_for(|_| true).unwrap();
+    assert!(matches!(
+        event,
+        NetworkEvent::DataBuffered {
+            link_id: 0,
+            len: 10
+        }
+    ));
+    drop(event);
+
+    // The data looks like the response terminator, the frames after the response are kept.
+    serial.expect(
+        b"AT+CIPRECVDATA=0,16\r\n",
+        b"+CIPRECVDATA:10,ab\r\nOK\r\ncd\r\nOK\r\n1,CONNECT\r\n",
+    );
+    assert_eq!(session.pull_data(0, &mut buf), Ok(10));
+    assert_eq!(&buf[..10], b"ab\r\nOK\r\ncd");
+    let event = session.wait_for(|_| true).unwrap();
+    assert!(matches!(event, NetworkEvent::Connected { link_id: 1 }));
+    drop(event);
+
+    // The header of the NonOS firmware.
+    serial.expect(b"AT+CIPRECVDATA=1,4\r\n", b"+CIPRECVDATA,4:wxyz\r\nOK\r\n");
+    assert_eq!(session.pull_data(1, &mut buf[..4]), Ok(4));
+    assert_eq!(&buf[..4], b"wxyz");
+
+    serial.expect(b"AT+CIPRECVDATA=0,16\r\n", b"ERROR\r\n");
+    assert_eq!(session.pull_data(0, &mut buf), Err(Error::CommandFailed));
+    assert!(serial.is_script_done());
+}
+
+#[test]
+fn test_link_queues() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+    let mut queues = LinkQueues::<8>::new();
+
+    serial.receive(b"0,CONNECT\r\n+IPD,0,5:first+IPD,1,4:ping+IPD,0,6:second");
+    assert_eq!(
+        nb::block!(queues.poll(&mut session)).unwrap(),
+        LinkEvent::Connected { link_id: 0 }
+    );
+    assert_eq!(
+        nb::block!(queues.poll(&mut session)).unwrap(),
+        LinkEvent::DataQueued {
+            link_id: 0,
+            len: 5,
+            pending: false
+        }
+    );
+    // The data of the other link is not blocked by the unread data.
+    assert_eq!(
+        nb::block!(queues.poll(&mut session)).unwrap(),
+        LinkEvent::DataQueued {
+            link_id: 1,
+            len: 4,
+            pending: false
+        }
+    );
+    assert_eq!(
+        nb::block!(queues.poll(&mut session)).unwrap(),
+        LinkEvent::DataQueued {
+            link_id: 0,
+            len: 3,
+            pending: true
+        }
+    );
+    let mut buf = [0_u8; 16];
+    assert_eq!(queues.read(1, &mut buf), 4);
+    assert_eq!(&buf[..4], b"ping");
+    assert_eq!(queues.len(0), 8);
+
+    // The rest of the data waits until the queue is read.
+    assert_eq!(queues.poll(&mut session), Err(nb::Error::WouldBlock));
+    assert_eq!(queues.poll(&mut session), Err(nb::Error::WouldBlock));
+    assert_eq!(queues.read(0, &mut buf[..6]), 6);
+    assert_eq!(&buf[..6], b"firsts");
+    assert_eq!(
+        nb::block!(queues.poll(&mut session)).unwrap(),
+        LinkEvent::DataQueued {
+            link_id: 0,
+            len: 3,
+            pending: false
+        }
+    );
+    assert_eq!(queues.read(0, &mut buf), 5);
+    assert_eq!(&buf[..5], b"econd");
+    assert!(queues.is_empty(0));
+
+    // The data that comes after the drained one is delivered intact.
+    serial.receive(b"+IPD,1,3:end");
+    assert_eq!(
+        nb::block!(queues.poll(&mut session)).unwrap(),
+        LinkEvent::DataQueued {
+            link_id: 1,
+            len: 3,
+            pending: false
+        }
+    );
+    assert_eq!(queues.read(1, &mut buf), 3);
+    assert_eq!(&buf[..3], b"end");
+}
+
+#[test]
+fn test_link_queues_passive() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+    let mut queues = LinkQueues::<8>::new();
+    session.set_passive_receive(true).unwrap();
+    serial.take_written();
+
+    serial.receive(b"+IPD,0,10\r\n");
+    assert_eq!(
+        nb::block!(queues.poll(&mut session)).unwrap(),
+        LinkEvent::DataBuffered {
+            link_id: 0,
+            len: 10
+        }
+    );
+    // Only the data that fits into the queue is pulled.
+    serial.expect(
+        b"AT+CIPRECVDATA=0,8\r\n",
+        b"+CIPRECVDATA:8,abcdefgh\r\nOK\r\n",
+    );
+    assert_eq!(
+        nb::block!(queues.poll(&mut session)).unwrap(),
+        LinkEvent::DataQueued {
+            link_id: 0,
+            len: 8,
+            pending: true
+        }
+    );
+    assert_eq!(queues.poll(&mut session), Err(nb::Error::WouldBlock));
+
+    // The full queue doesn't block the traffic of the other link.
+    serial.receive(b"+IPD,1,4\r\n");
+    assert_eq!(
+        nb::block!(queues.poll(&mut session)).unwrap(),
+        LinkEvent::DataBuffered { link_id: 1, len: 4 }
+    );
+    serial.expect(b"AT+CIPRECVDATA=1,4\r\n", b"+CIPRECVDATA:4,ping\r\nOK\r\n");
+    assert_eq!(
+        nb::block!(queues.poll(&mut session)).unwrap(),
+        LinkEvent::DataQueued {
+            link_id: 1,
+            len: 4,
+            pending: false
+        }
+    );
+    let mut buf = [0_u8; 16];
+    assert_eq!(queues.read(1, &mut buf), 4);
+    assert_eq!(&buf[..4], b"ping");
+    assert_eq!(queues.poll(&mut session), Err(nb::Error::WouldBlock));
+
+    // The rest of the data is pulled once the queue has been read.
+    assert_eq!(queues.read(0, &mut buf), 8);
+    serial.expect(b"AT+CIPRECVDATA=0,2\r\n", b"+CIPRECVDATA:2,ij\r\nOK\r\n");
+    assert_eq!(
+        nb::block!(queues.poll(&mut session)).unwrap(),
+        LinkEvent::DataQueued {
+            link_id: 0,
+            len: 2,
+            pending: false
+        }
+    );
+    assert_eq!(queues.read(0, &mut buf), 2);
+    assert_eq!(&buf[..2], b"ij");
+    assert!(serial.is_script_done());
+}
+
+#[test]
+fn test_timeout_units() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    session.set_timeout_ms(Some(1_500));
+    assert_eq!(session.timeout(), Some(1_500_000));
+    assert_eq!(session.timeout_ms(), Some(1_500));
+    assert_eq!(
+        session.timeout_duration(),
+        Some(Duration::from_millis(1_500))
+    );
+
+    session.set_timeout_duration(Some(Duration::from_micros(2_500)));
+    assert_eq!(session.timeout(), Some(2_500));
+    assert_eq!(session.timeout_ms(), Some(2));
+
+    session.set_timeout_duration(Some(Duration::MAX));
+    assert_eq!(session.timeout(), Some(u64::MAX));
+    session.set_timeout(None);
+    assert_eq!(session.timeout_duration(), None);
+}
+
+#[test]
+fn test_raw_access() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session_with_clock(&serial, MockClock::new().with_step(1));
+
+    serial.expect(b"\x01\x02upload", b"\x06ack");
+    session.write_raw(b"\x01\x02upload").unwrap();
+    serial.assert_written(b"\x01\x02upload");
+    assert!(serial.is_flushed());
+
+    // The bytes already received by the event polling come first.
+    assert!(session.poll_network_event().is_err());
+    let mut buf = [0_u8; 8];
+    assert_eq!(session.read_raw(&mut buf, 100), Ok(4));
+    assert_eq!(&buf[..4], b"\x06ack");
+    assert_eq!(session.read_raw(&mut buf, 100), Err(Error::Timeout));
+}
+
+#[test]
+fn test_transparent_mode() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let clock = MockClock::new().with_step(1_000);
+    let mut session = mock_session_with_clock(&serial, clock.clone());
+    serial.take_written();
+
+    serial.expect(b"AT+CIPSEND\r\n", b"\r\nOK\r\n\r\n>");
+    session.enter_transparent().unwrap();
+    serial.assert_written(b"AT+CIPMODE=1\r\nAT+CIPSEND\r\n");
+
+    // The passed through data doesn't contain the prompt.
+    serial.expect(b"ping", b"pong");
+    session.write_raw(b"ping").unwrap();
+    let mut buf = [0_u8; 8];
+    assert_eq!(session.read_raw(&mut buf, 10_000), Ok(4));
+    assert_eq!(&buf[..4], b"pong");
+
+    // The escape sequence is surrounded by the guard time and has no line break.
+    serial.expect(b"AT+CIPMODE=0\r\n", b"\r\nOK\r\n");
+    let started_at = clock.now_us();
+    session.exit_transparent().unwrap();
+    assert!(clock.now_us() - started_at >= 2_000_000);
+    serial.assert_written(b"ping+++AT+CIPMODE=0\r\n");
+
+    // The transmission cannot be started without the connection.
+    serial.expect(b"AT+CIPSEND\r\n", b"\r\nERROR\r\n");
+    assert_eq!(session.enter_transparent(), Err(Error::CommandFailed));
+
+    // The silent module doesn't block the exit forever.
+    serial.take_written();
+    assert_eq!(session.exit_transparent(), Err(Error::Timeout));
+    assert_eq!(session.timeout(), None);
+    serial.assert_written(b"+++AT+CIPMODE=0\r\n");
+}
+
+#[test]
+fn test_module_reboot() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let clock = MockClock::new();
+    let mut session = mock_session_with_clock(&serial, clock.clone());
+    session.set_reboot_recovery(Some(RebootRecovery {
+        join_ap: Some(
+            OwnedJoinApConfig::try_from(JoinApConfig {
+                ssid: "ssid",
+                password: Some("password"),
+                options: None,
+            })
+            .unwrap(),
+        ),
+        listen_port: Some(80),
+        ..RebootRecovery::default()
+    }));
+
+    serial.receive(b"0,CONNECT\r\n");
+    let event = session.wait_for(|_| true).unwrap();
+    assert!(matches!(event, NetworkEvent::Connected { link_id: 0 }));
+    drop(event);
+
+    clock.advance(5_000_000);
+    assert!(session.module.uptime() >= 5_000_000);
+
+    serial.receive(b"\r\n ets Jan  8 2013,rst cause:2, boot mode:(3,6)\r\n\r\nready\r\n");
+    let event = session
+        .wait_for(|event| matches!(event, NetworkEvent::ModuleRebooted))
+        .unwrap();
+    assert!(matches!(event, NetworkEvent::ModuleRebooted));
+    drop(event);
+    // The uptime is counted from the reboot, the configuration is not restored yet.
+    assert!(session.module.uptime() < 5_000_000);
+    serial.assert_written(b"");
+
+    assert_eq!(session.restore_after_reboot(), Ok(true));
+    serial.assert_written(
+        b"ATE0\r\n\
+        AT+CWMODE=1\r\n\
+        AT+CIPMUX=1\r\n\
+        AT+CIPDINFO=1\r\n\
+        AT+CWJAP=\"ssid\",\"password\"\r\n\
+        AT+CIPSERVER=1,80\r\n",
+    );
+
+    // The restoring failure is reported to the caller.
+    session.set_reboot_recovery(Some(RebootRecovery {
+        max_connections: Some(2),
+        ..RebootRecovery::default()
+    }));
+    serial.receive(b"ready\r\n");
+    let event = session.wait_for(|_| true).unwrap();
+    assert!(matches!(event, NetworkEvent::ModuleRebooted));
+    drop(event);
+    serial.expect(b"AT+CIPSERVERMAXCONN=2\r\n", b"ERROR\r\n");
+    assert_eq!(session.restore_after_reboot(), Err(Error::CommandFailed));
+}
+
+#[test]
+fn test_max_connections() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    assert_eq!(session.set_max_connections(0), Err(Error::InvalidArgument));
+    assert_eq!(session.set_max_connections(6), Err(Error::InvalidArgument));
+    serial.assert_written(b"");
+
+    session.set_max_connections(2).unwrap();
+    serial.assert_written(b"AT+CIPSERVERMAXCONN=2\r\n");
+}
+
+#[test]
+fn test_closed_locally() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    serial.expect(b"AT+CIPCLOSE=0\r\n", b"0,CLOSED\r\n\r\nOK\r\n");
+    session.close(0).unwrap();
+    serial.receive(b"1,CLOSED\r\n");
+
+    let event = session.wait_for(|_| true).unwrap();
+    assert!(matches!(
+        event,
+        NetworkEvent::Closed {
+            link_id: 0,
+            closed_locally: true
+        }
+    ));
+    drop(event);
+    let event = session.wait_for(|_| true).unwrap();
+    assert!(matches!(
+        event,
+        NetworkEvent::Closed {
+            link_id: 1,
+            closed_locally: false
+        }
+    ));
+}
+
+#[test]
+fn test_poll_network_event_for() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let clock = MockClock::new().with_step(10);
+    let mut session = mock_session_with_clock(&serial, clock.clone());
+
+    let started_at = clock.now_us();
+    assert!(matches!(
+        session.poll_network_event_for(1_000),
+        Err(nb::Error::WouldBlock)
+    ));
+    assert!(clock.now_us() - started_at >= 1_000);
+
+    serial.receive(b"2,CONNECT\r\n");
+    let event = session.poll_network_event_for(1_000).unwrap();
+    assert!(matches!(event, NetworkEvent::Connected { link_id: 2 }));
+}
+
+#[test]
+fn test_wait_for_limits() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let clock = MockClock::new().with_step(1);
+    let mut session = mock_session_with_clock(&serial, clock.clone());
+
+    // The non-matching events don't prolong the waiting.
+    for _ in 0..1_000 {
+        serial.receive(b"+TIME_UPDATED\r\n");
+    }
+    let started_at = clock.now_us();
+    assert_eq!(session.wait_any(&[0], 100).unwrap_err(), Error::Timeout);
+    assert!(clock.now_us() - started_at < 1_000);
+    while session.poll_network_event().is_ok() {}
+
+    // The skipped connection events are not lost silently.
+    serial.receive(b"0,CONNECT\r\n1,CONNECT\r\n2,CONNECT\r\n3,CONNECT\r\n4,CONNECT\r\n");
+    serial.receive(b"0,CLOSED\r\n");
+    assert_eq!(
+        session
+            .wait_for(|event| matches!(event, NetworkEvent::Closed { .. }))
+            .unwrap_err(),
+        Error::BufferFull
+    );
+    for expected in 0..5 {
+        let event = session.wait_for(|_| true).unwrap();
+        assert!(matches!(event, NetworkEvent::Connected { link_id } if link_id == expected));
+    }
+    let event = session.wait_for(|_| true).unwrap();
+    assert!(matches!(event, NetworkEvent::Closed { link_id: 0, .. }));
+}
+
+#[test]
+fn test_events_iterator() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    serial.receive(b"0,CONNECT\r\n+IPD,0,5:hello");
+    // The bytes are read by one poll and parsed by the next one.
+    let _ = session.poll_network_event();
+    let events = session
+        .events_owned::<2>()
+        .collect::<Result<std::vec::Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        events,
+        [
+            OwnedNetworkEvent::Connected { link_id: 0 },
+            OwnedNetworkEvent::DataAvailable {
+                link_id: 0,
+                remote: None,
+                data: Vec::from_slice(b"he").unwrap(),
+            },
+            OwnedNetworkEvent::DataAvailable {
+                link_id: 0,
+                remote: None,
+                data: Vec::from_slice(b"ll").unwrap(),
+            },
+            OwnedNetworkEvent::DataAvailable {
+                link_id: 0,
+                remote: None,
+                data: Vec::from_slice(b"o").unwrap(),
+            },
+        ]
+    );
+    assert_eq!(session.events().count(), 0);
+}
+
+#[test]
+fn test_retain_unconsumed_other_links() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session_with_clock(&serial, MockClock::new().with_step(1));
+    session.set_retain_unconsumed(true);
+
+    // The retained data of the other links doesn't block the waiting.
+    serial.receive(b"+IPD,1,3:abc+IPD,0,2:hi");
+    let event = session.wait_any(&[0], 10_000).unwrap();
+    assert!(
+        matches!(event, NetworkEvent::DataAvailable { link_id: 0, ref data, .. } if data.as_ref() == b"hi")
+    );
+    drop(event);
+
+    // The same for the buffered reader, the unconsumed data of its own link is kept.
+    serial.receive(b"+IPD,1,3:abc+IPD,0,2:ok");
+    let mut reader = session.buf_reader::<4>(0);
+    reader.set_read_timeout(Some(10_000));
+    let mut buf = [0_u8; 4];
+    assert_eq!(reader.read_blocking(&mut buf), Ok(4));
+    assert_eq!(&buf, b"hiok");
+}
+
+#[test]
+fn test_join_ap_bssid() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let module: Module<_, _, _, 256> =
+        Module::new(serial.clone(), serial.clone(), MockClock::new()).unwrap();
+    serial.take_written();
+
+    JoinApConfig {
+        ssid: "ssid",
+        password: Some("password"),
+        options: Some(JoinApOptions {
+            bssid: Some([0xca, 0xfe, 0x00, 0x01, 0xbe, 0xef]),
+            ..JoinApOptions::default()
+        }),
+    }
+    .join(module)
+    .unwrap();
+    let written = serial.take_written();
+    let written = std::string::String::from_utf8_lossy(&written);
+    assert!(
+        written.contains("AT+CWJAP=\"ssid\",\"password\",\"ca:fe:00:01:be:ef\",0,,,0\r\n"),
+        "{}",
+        written
+    );
+
+    // The NonOS firmware supports the BSSID only.
+    let mut module: Module<_, _, _, 256> =
+        Module::new(serial.clone(), serial.clone(), MockClock::new()).unwrap();
+    module.set_dialect(Dialect::NonOs);
+    serial.take_written();
+    let mut config = JoinApConfig {
+        ssid: "ssid",
+        password: None,
+        options: Some(JoinApOptions {
+            bssid: Some([0xca, 0xfe, 0x00, 0x01, 0xbe, 0xef]),
+            ..JoinApOptions::default()
+        }),
+    };
+    config.join_ap(&mut module).unwrap();
+    serial.assert_written(b"AT+CWJAP_CUR=\"ssid\",\"\",\"ca:fe:00:01:be:ef\"\r\n");
+
+    config.options = Some(JoinApOptions {
+        scan_mode: ScanMode::AllChannels,
+        ..JoinApOptions::default()
+    });
+    assert_eq!(config.join_ap(&mut module), Err(Error::Unsupported));
+    serial.assert_written(b"");
+}
+
+#[test]
+fn test_retry_policy() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let clock = MockClock::new().with_step(1);
+    let mut session = mock_session_with_clock(&serial, clock.clone());
+    session.set_retry_policy(RetryPolicy {
+        max_attempts: 3,
+        delay_us: 1_000,
+    });
+
+    // The busy module gets the command once again after the delay.
+    serial.expect(b"AT+CIPSERVER=1,80\r\n", b"busy p...\r\n");
+    serial.expect(b"AT+CIPSERVER=1,80\r\n", b"busy p...\r\n");
+    let started_at = clock.now_us();
+    session.listen(80).unwrap();
+    assert!(clock.now_us() - started_at >= 2_000);
+    serial.assert_written(b"AT+CIPSERVER=1,80\r\n".repeat(3).as_slice());
+
+    // The attempts are limited, and the rejected commands are not repeated.
+    for _ in 0..3 {
+        serial.expect(b"AT+CIPSERVERMAXCONN=1\r\n", b"busy p...\r\n");
+    }
+    assert_eq!(session.set_max_connections(1), Err(Error::CommandFailed));
+    serial.expect(b"AT+CIPSERVERMAXCONN=2\r\n", b"ERROR\r\n");
+    assert_eq!(session.set_max_connections(2), Err(Error::CommandFailed));
+    serial.assert_written(
+        b"AT+CIPSERVERMAXCONN=1\r\nAT+CIPSERVERMAXCONN=1\r\nAT+CIPSERVERMAXCONN=1\r\n\
+        AT+CIPSERVERMAXCONN=2\r\n",
+    );
+}
+
+#[test]
+fn test_deferred_command_waits_for_frame() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    // The command is not sent while the frame header is incomplete.
+    serial.receive(b"+IPD,0,1");
+    assert!(session.poll_network_event().is_err());
+    session.defer_close(1).unwrap();
+    session.service().unwrap();
+    serial.assert_written(b"");
+
+    serial.receive(b"0:0123456789");
+    let event = session.wait_for(|_| true).unwrap();
+    assert!(
+        matches!(event, NetworkEvent::DataAvailable { link_id: 0, ref data, .. } if data.as_ref() == b"0123456789")
+    );
+    drop(event);
+
+    // The polling itself never sends the commands.
+    assert!(session.poll_network_event().is_err());
+    serial.assert_written(b"");
+    session.service().unwrap();
+    serial.assert_written(b"AT+CIPCLOSE=1\r\n");
+}
+
+#[test]
+fn test_keepalive_and_idle_links() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let clock = MockClock::new();
+    let mut session = mock_session_with_clock(&serial, clock.clone());
+
+    serial.receive(b"0,CONNECT\r\n1,CONNECT\r\n");
+    for _ in 0..2 {
+        let event = session.wait_for(|_| true).unwrap();
+        assert!(matches!(event, NetworkEvent::Connected { .. }));
+    }
+    session.set_keepalive(Some(KeepAlive::new(b"ping", 1_000, 500).unwrap()));
+    session.watch_link(0, true);
+    session.set_idle_timeout(Some(5_000));
+
+    // The inactive watched link is probed.
+    clock.set(1_000);
+    serial.expect(b"AT+CIPSEND=0,4\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"ping", b"\r\nRecv 4 bytes\r\n\r\nSEND OK\r\n");
+    session.service().unwrap();
+    serial.assert_written(b"AT+CIPSEND=0,4\r\nping");
+
+    // The peer hasn't answered in time.
+    clock.set(1_600);
+    session.service().unwrap();
+    assert!(session.is_link_dead(0));
+    assert!(!session.is_link_dead(1));
+    assert!(!session.is_link_dead(8));
+    assert!(!session.is_link_dead(usize::MAX));
+
+    clock.set(3_000);
+    serial.receive(b"+IPD,1,2:hi");
+    let event = session.wait_for(|_| true).unwrap();
+    assert!(matches!(
+        event,
+        NetworkEvent::DataAvailable { link_id: 1, .. }
+    ));
+    drop(event);
+
+    // Only the link without any incoming data is closed.
+    clock.set(5_000);
+    session.service().unwrap();
+    serial.assert_written(b"AT+CIPCLOSE=0\r\n");
+    assert_eq!(session.link_idle_time(0), None);
+    assert_eq!(session.link_idle_time(1), Some(2_000));
+}
+
+#[test]
+fn test_rejected_commands() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    // The rejected commands are the runtime outcomes, they never panic.
+    serial.expect(b"AT+CIPSERVER=1,80\r\n", b"ERROR\r\n");
+    assert_eq!(session.listen(80), Err(Error::CommandFailed));
+    serial.expect(b"AT+CIPSEND=0,4\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"ping", b"\r\nRecv 4 bytes\r\n\r\nSEND FAIL\r\n");
+    assert_eq!(
+        session.send(0, b"ping".iter().copied()),
+        Err(Error::CommandFailed)
+    );
+    assert!(serial.is_script_done());
+}
+
+#[test]
+fn test_keepalive_failed_probe() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let clock = MockClock::new();
+    let mut session = mock_session_with_clock(&serial, clock.clone());
+    session.set_keepalive(Some(KeepAlive::new(b"ping", 1_000, 500).unwrap()));
+    session.watch_link(0, true);
+
+    // The half-open link fails to send the probe, it's not a service error.
+    clock.set(1_000);
+    serial.expect(b"AT+CIPSEND=0,4\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"ping", b"\r\nRecv 4 bytes\r\n\r\nSEND FAIL\r\n");
+    session.service().unwrap();
+    assert!(session.is_link_dead(0));
+    assert!(serial.is_script_done());
+}
+
+#[test]
+fn test_reset_waits_for_ready() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let clock = MockClock::new().with_step(10);
+    let mut module: Module<_, _, _, 64> =
+        Module::new(serial.clone(), serial.clone(), clock.clone()).unwrap();
+    serial.take_written();
+
+    // The boot noise is discarded and the reset finishes as soon as the module is ready.
+    let mut boot_output =
+        b"\r\n\x00\xfe\xff ets Jan  8 2013,rst cause:2, boot mode:(3,6)\r\n".repeat(4);
+    boot_output.extend_from_slice(b"\r\nready\r\n");
+    serial.expect(b"AT+RST\r\n", &boot_output);
+    let started_at = clock.now_us();
+    module.reset(ResetKind::Soft).unwrap();
+    assert!(clock.now_us() - started_at < 100_000);
+    assert!(module.uptime() < 100);
+    serial.assert_written(b"AT+RST\r\nATE0\r\n");
+
+    // The module that never boots is reported.
+    serial.expect(b"AT+RST\r\n", b"\x00\xff");
+    assert_eq!(module.reset(ResetKind::Soft), Err(Error::Timeout));
+}
+
+#[test]
+fn test_reset_config() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let clock = MockClock::new().with_step(10);
+    let mut module: Module<_, _, _, 64> =
+        Module::new(serial.clone(), serial.clone(), clock.clone()).unwrap();
+    serial.take_written();
+    module.set_reset_config(ResetConfig {
+        delay_us: 10_000,
+        recovery_attempts: 3,
+        wait_ready: false,
+    });
+
+    // The fixed delay is used instead of the banner, and the busy module gets the first
+    // command several times.
+    serial.expect(b"AT+RST\r\n", b"ready\r\n");
+    serial.expect(b"ATE0\r\n", b"busy p...\r\n");
+    serial.expect(b"ATE0\r\n", b"busy p...\r\n");
+    let started_at = clock.now_us();
+    module.reset(ResetKind::Soft).unwrap();
+    assert!(clock.now_us() - started_at >= 10_000);
+    serial.assert_written(b"AT+RST\r\nATE0\r\nATE0\r\nATE0\r\n");
+    assert!(serial.is_script_done());
+
+    // The recovery attempts are limited.
+    serial.expect(b"AT+RST\r\n", b"");
+    for _ in 0..3 {
+        serial.expect(b"ATE0\r\n", b"busy p...\r\n");
+    }
+    module.reset(ResetKind::Soft).unwrap();
+    serial.assert_written(b"AT+RST\r\nATE0\r\nATE0\r\nATE0\r\n");
+    assert!(serial.is_script_done());
+}
+
+#[derive(Default)]
+struct MockPin {
+    levels: Vec<bool, 4>,
+    broken: bool,
+}
+
+impl embedded_hal::digital::v2::OutputPin for MockPin {
+    type Error = ();
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        if self.broken {
+            return Err(());
+        }
+        self.levels.push(false).map_err(drop)
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        if self.broken {
+            return Err(());
+        }
+        self.levels.push(true).map_err(drop)
+    }
+}
+
+#[test]
+fn test_reset_kinds() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let clock = MockClock::new().with_step(10);
+    let mut module: Module<_, _, _, 64> =
+        Module::new(serial.clone(), serial.clone(), clock).unwrap();
+    serial.take_written();
+
+    // The hardware reset pulls the pin down and releases it without any command.
+    let mut pin = MockPin::default();
+    serial.receive(b"\x00\xfe boot noise\r\nready\r\n");
+    module.reset(ResetKind::Hardware(&mut pin)).unwrap();
+    assert_eq!(pin.levels, [false, true]);
+    serial.assert_written(b"ATE0\r\n");
+
+    // The pin errors are reported.
+    let mut pin = MockPin {
+        broken: true,
+        ..MockPin::default()
+    };
+    assert_eq!(
+        module.reset(ResetKind::Hardware(&mut pin)),
+        Err(Error::ResetPin)
+    );
+    serial.assert_written(b"");
+
+    // The factory reset uses its own command.
+    serial.expect(b"AT+RESTORE\r\n", b"OK\r\n\r\nready\r\n");
+    module.reset(ResetKind::Restore).unwrap();
+    serial.assert_written(b"AT+RESTORE\r\nATE0\r\n");
+    assert!(serial.is_script_done());
+}
+
+#[test]
+fn test_link_status() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    serial.expect(
+        b"AT+CIPSTATUS\r\n",
+        b"STATUS:3\r\n\
+        +CIPSTATUS:0,\"TCP\",\"192.168.1.10\",8080,54321,0\r\n\
+        +CIPSTATUS:1,\"UDP\",\"0.0.0.0\",123,123,0\r\n\
+        +CIPSTATUS:3,\"SSLv6\",\"fe80::1\",443,1025,0\r\n\
+        +CIPSTATUS:4,\"TCP\",\"192.168.4.2\",49152,80,1\r\n\r\nOK\r\n",
+    );
+    let links = session.status().unwrap();
+    serial.assert_written(b"AT+CIPSTATUS\r\n");
+
+    assert_eq!(links.len(), 4);
+    assert_eq!(
+        links[0],
+        LinkStatus {
+            link_id: 0,
+            protocol: LinkProtocol::Tcp,
+            remote: "192.168.1.10:8080".parse().unwrap(),
+            local_port: 54321,
+            role: LinkRole::Client,
+        }
+    );
+    assert_eq!(links[1].protocol, LinkProtocol::Udp);
+    assert_eq!(links[2].protocol, LinkProtocol::Ssl);
+    assert_eq!(links[2].remote, "[fe80::1]:443".parse().unwrap());
+    assert_eq!(links[3].role, LinkRole::Server);
+    assert_eq!(links[3].local_port, 80);
+
+    // No links are open.
+    serial.expect(b"AT+CIPSTATUS\r\n", b"STATUS:5\r\n\r\nOK\r\n");
+    assert!(session.status().unwrap().is_empty());
+
+    serial.expect(b"AT+CIPSTATUS\r\n", b"ERROR\r\n");
+    assert_eq!(session.status(), Err(Error::CommandFailed));
+}
+
+#[test]
+fn test_data_remote_addr() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    serial.receive(b"+IPD,0,2,\"192.168.1.7\",5000:hi");
+    let event = session.wait_for(|_| true).unwrap();
+    assert_eq!(
+        event.into_owned::<8>(),
+        OwnedNetworkEvent::DataAvailable {
+            link_id: 0,
+            remote: Some("192.168.1.7:5000".parse().unwrap()),
+            data: Vec::from_slice(b"hi").unwrap(),
+        }
+    );
+
+    // The address is not quoted by the NonOS firmware.
+    serial.receive(b"+IPD,1,3,10.0.0.2,123:abc");
+    let event = session.wait_for(|_| true).unwrap();
+    assert!(matches!(
+        event,
+        NetworkEvent::DataAvailable { link_id: 1, remote: Some(remote), ref data, .. }
+            if remote == "10.0.0.2:123".parse().unwrap() && data.as_ref() == b"abc"
+    ));
+    drop(event);
+
+    // The address is skipped in the pulled data.
+    session.set_passive_receive(true).unwrap();
+    serial.receive(b"+IPD,0,4,\"192.168.1.7\",5000\r\n");
+    let event = session.wait_for(|_| true).unwrap();
+    assert!(matches!(
+        event,
+        NetworkEvent::DataBuffered { link_id: 0, len: 4 }
+    ));
+    drop(event);
+
+    let mut buf = [0_u8; 8];
+    serial.expect(
+        b"AT+CIPRECVDATA=0,8\r\n",
+        b"+CIPRECVDATA:4,\"192.168.1.7\",5000,ping\r\nOK\r\n",
+    );
+    assert_eq!(session.pull_data(0, &mut buf), Ok(4));
+    assert_eq!(&buf[..4], b"ping");
+}
+
+#[test]
+fn test_dhcp() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    session.set_dhcp(WifiInterface::Station, false).unwrap();
+    session.set_dhcp(WifiInterface::SoftAp, true).unwrap();
+    session
+        .set_dhcp_lease_range(Some(DhcpLeaseRange {
+            start: Ipv4Addr::new(192, 168, 4, 10),
+            end: Ipv4Addr::new(192, 168, 4, 15),
+            lease_time: 120,
+        }))
+        .unwrap();
+    session.set_dhcp_lease_range(None).unwrap();
+    serial.assert_written(
+        b"AT+CWDHCP=0,1\r\n\
+        AT+CWDHCP=1,2\r\n\
+        AT+CWDHCPS=1,120,\"192.168.4.10\",\"192.168.4.15\"\r\n\
+        AT+CWDHCPS=0\r\n",
+    );
+
+    // The NonOS firmware swaps the parameters.
+    session.module.set_dialect(Dialect::NonOs);
+    session.set_dhcp(WifiInterface::Station, false).unwrap();
+    session.set_dhcp(WifiInterface::SoftAp, true).unwrap();
+    serial.assert_written(b"AT+CWDHCP_CUR=1,0\r\nAT+CWDHCP_CUR=0,1\r\n");
+
+    serial.expect(b"AT+CWDHCPS_CUR=0\r\n", b"ERROR\r\n");
+    assert_eq!(
+        session.set_dhcp_lease_range(None),
+        Err(Error::CommandFailed)
+    );
+}
+
+#[test]
+fn test_parse_mac() {
+    assert_eq!(
+        parse_mac(b"18:fe:34:a1:B2:c3"),
+        Some([0x18, 0xfe, 0x34, 0xa1, 0xb2, 0xc3])
+    );
+    assert_eq!(parse_mac(b"18:fe:34:a1:b2"), None);
+    assert_eq!(parse_mac(b"18:fe:34:a1:b2:c3:00"), None);
+    assert_eq!(parse_mac(b"18:fe:34:a1:b2:c"), None);
+
+    let raw = b"+CIPSTAMAC_CUR:\"18:fe:34:a1:b2:c3\"\r\n\r\nOK\r\n";
+    assert_eq!(
+        parse_mac_response(raw, "CIPSTAMAC"),
+        Some([0x18, 0xfe, 0x34, 0xa1, 0xb2, 0xc3])
+    );
+    assert_eq!(parse_mac_response(raw, "CIPAPMAC"), None);
+}
+
+#[test]
+fn test_mac_addresses() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    serial.expect(
+        b"AT+CIPSTAMAC?\r\n",
+        b"+CIPSTAMAC:\"18:fe:34:a1:b2:c3\"\r\n\r\nOK\r\n",
+    );
+    assert_eq!(session.sta_mac(), Ok([0x18, 0xfe, 0x34, 0xa1, 0xb2, 0xc3]));
+    serial.expect(b"AT+CIPAPMAC?\r\n", b"ERROR\r\n");
+    assert_eq!(session.ap_mac(), Err(Error::CommandFailed));
+
+    session.module.set_dialect(Dialect::NonOs);
+    serial.take_written();
+    session
+        .module
+        .set_ap_mac([0x1a, 0xfe, 0x34, 0x00, 0x00, 0x01])
+        .unwrap();
+    serial.assert_written(b"AT+CIPAPMAC_CUR=\"1a:fe:34:00:00:01\"\r\n");
+    // The multicast address is rejected before sending the command.
+    assert_eq!(
+        session
+            .module
+            .set_sta_mac([0x1b, 0xfe, 0x34, 0x00, 0x00, 0x01]),
+        Err(Error::InvalidArgument)
+    );
+    serial.assert_written(b"");
+    assert!(serial.is_script_done());
+}
+
+#[test]
+fn test_firmware_info() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    serial.expect(
+        b"AT+GMR\r\n",
+        b"AT version:1.7.4.0(May 11 2020 19:13:04)\r\n\
+        SDK version:3.0.4(9532ceb)\r\n\
+        compile time:May 27 2020 10:12:17\r\n\
+        Bin version(Wroom 02):1.7.4\r\n\r\nOK\r\n",
+    );
+    let info = session.firmware_info().unwrap();
+    assert_eq!(
+        info.at_version,
+        Some(AtVersion {
+            major: 1,
+            minor: 7,
+            patch: 4
+        })
+    );
+    assert_eq!(info.sdk_version.as_deref(), Some("3.0.4(9532ceb)"));
+    assert_eq!(info.compile_time.as_deref(), Some("May 27 2020 10:12:17"));
+
+    // The compile time of the ESP-AT firmware is preceded by the commit hash.
+    serial.expect(
+        b"AT+GMR\r\n",
+        b"AT version:2.2.0.0(c6fa6bf - ESP32 - Jul  2 2021 06:44:05)\r\n\
+        SDK version:v4.2.2-76-gefa6eca\r\n\
+        compile time(3a696ba):Jul  2 2021 11:54:43\r\n\r\nOK\r\n",
+    );
+    let info = session.firmware_info().unwrap();
+    assert_eq!(info.at_version.map(|version| version.major), Some(2));
+    assert_eq!(info.compile_time.as_deref(), Some("Jul  2 2021 11:54:43"));
+
+    // The old ESP-01 firmware reports the versions only.
+    serial.expect(
+        b"AT+GMR\r\n",
+        b"AT version:0.21.0.0\r\nSDK version:0.9.5\r\n\r\nOK\r\n",
+    );
+    let info = session.firmware_info().unwrap();
+    assert!(
+        info.at_version
+            < Some(AtVersion {
+                major: 1,
+                minor: 0,
+                patch: 0
+            })
+    );
+    assert_eq!(info.compile_time, None);
+
+    serial.expect(b"AT+GMR\r\n", b"ERROR\r\n");
+    assert_eq!(session.firmware_info(), Err(Error::CommandFailed));
+}
+
+#[test]
+fn test_set_tx_power() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    session.set_tx_power(15).unwrap();
+    serial.assert_written(b"AT+RFPOWER=60\r\n");
+
+    // The range is checked before sending the command.
+    assert_eq!(session.set_tx_power(9), Err(Error::InvalidArgument));
+    assert_eq!(session.set_tx_power(21), Err(Error::InvalidArgument));
+    assert_eq!(session.set_tx_power(u8::MAX), Err(Error::InvalidArgument));
+    serial.assert_written(b"");
+
+    // The range depends on the chip and the firmware.
+    session.module.set_chip(Chip::Esp32);
+    session.set_tx_power(21).unwrap();
+    session.module.set_dialect(Dialect::NonOs);
+    session.module.set_chip(Chip::Esp8266);
+    session.set_tx_power(0).unwrap();
+    serial.assert_written(b"AT+RFPOWER=84\r\nAT+RFPOWER=0\r\n");
+}
+
+#[test]
+fn test_current_ap() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    serial.expect(
+        b"AT+CWJAP?\r\n",
+        b"+CWJAP:\"my network\",\"aa:bb:cc:dd:ee:ff\",6,-56\r\n\r\nOK\r\n",
+    );
+    assert_eq!(
+        session.current_ap().unwrap(),
+        Some(ConnectedAp {
+            ssid: String::from("my network"),
+            bssid: [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff],
+            channel: 6,
+            rssi: -56,
+        })
+    );
+
+    serial.expect(b"AT+CWJAP?\r\n", b"No AP\r\n\r\nOK\r\n");
+    assert_eq!(session.current_ap(), Ok(None));
+}
+
+#[test]
+fn test_wifi_events() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+
+    serial.receive(b"WIFI DISCONNECT\r\nWIFI CONNECTED\r\nWIFI GOT IP\r\n");
+    for expected in [
+        OwnedNetworkEvent::WifiDisconnected,
+        OwnedNetworkEvent::WifiConnected,
+        OwnedNetworkEvent::WifiGotIp,
+    ] {
+        let event = session.wait_for(|_| true).unwrap();
+        assert_eq!(event.into_owned::<8>(), expected);
+    }
+
+    // The events received during the command are diverted to the session.
+    serial.expect(b"AT+CIPSERVER=1,80\r\n", b"WIFI DISCONNECT\r\n\r\nOK\r\n");
+    session.listen(80).unwrap();
+    let event = session.poll_network_event().unwrap();
+    assert!(matches!(event, NetworkEvent::WifiDisconnected));
+    assert_eq!(event.link_id(), None);
+}
+
+#[test]
+fn test_reconnect_policy() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let clock = MockClock::new();
+    let mut session = mock_session_with_clock(&serial, clock.clone());
+    serial.take_written();
+
+    let join_ap = OwnedJoinApConfig::try_from(JoinApConfig {
+        ssid: "ssid",
+        password: Some("password"),
+        options: None,
+    })
+    .unwrap();
+    session.set_reconnect_policy(Some(ReconnectPolicy::new(
+        join_ap,
+        Backoff::new(1_000, 1_000).with_max_attempts(2),
+    )));
+
+    serial.receive(b"WIFI DISCONNECT\r\n");
+    let event = session.wait_for(|_| true).unwrap();
+    assert!(matches!(event, NetworkEvent::WifiDisconnected));
+    drop(event);
+    assert!(session.is_reconnecting());
+
+    // The attempt is made by the explicit call only, the polling never blocks.
+    assert!(!session.service_reconnect());
+    clock.advance(1_000);
+    assert_eq!(
+        session.poll_network_event().map(drop),
+        Err(nb::Error::WouldBlock)
+    );
+    serial.assert_written(b"");
+
+    // The failed attempt schedules the next one.
+    serial.expect(b"AT+CWJAP=\"ssid\",\"password\"\r\n", b"ERROR\r\n");
+    assert!(!session.service_reconnect());
+    assert!(serial.is_script_done());
+    assert!(session.is_reconnecting());
+    assert!(!session.service_reconnect());
+
+    clock.advance(1_000);
+    serial.expect(
+        b"AT+CWJAP=\"ssid\",\"password\"\r\n",
+        b"WIFI CONNECTED\r\nWIFI GOT IP\r\n\r\nOK\r\n",
+    );
+    assert!(session.service_reconnect());
+    let event = session.poll_network_event().unwrap();
+    assert!(matches!(event, NetworkEvent::WifiConnected));
+    drop(event);
+    let event = session.poll_network_event().unwrap();
+    assert!(matches!(event, NetworkEvent::WifiGotIp));
+    drop(event);
+    assert!(!session.is_reconnecting());
+
+    // The attempts are exhausted.
+    session.set_reconnect_policy(Some(ReconnectPolicy::new(
+        OwnedJoinApConfig::try_from(JoinApConfig {
+            ssid: "ssid",
+            password: None,
+            options: None,
+        })
+        .unwrap(),
+        Backoff::new(1_000, 1_000).with_max_attempts(1),
+    )));
+    serial.receive(b"WIFI DISCONNECT\r\n");
+    session.wait_for(|_| true).unwrap();
+    clock.advance(1_000);
+    serial.expect(b"AT+CWJAP=\"ssid\",\"\"\r\n", b"ERROR\r\n");
+    assert!(!session.service_reconnect());
+    assert!(!session.is_reconnecting());
+    serial.take_written();
+    clock.advance(10_000);
+    assert!(!session.service_reconnect());
+    assert!(serial.take_written().is_empty());
+}
+
+#[test]
+fn test_smartconfig() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let module: Module<_, _, _, 256> =
+        Module::new(serial.clone(), serial.clone(), MockClock::new()).unwrap();
+    serial.take_written();
+
+    serial.expect(
+        b"AT+CWSTARTSMART=3\r\n",
+        b"OK\r\nsmartconfig type:ESPTOUCH\r\nSmart get wifi info\r\n\
+        ssid:home\r\npassword:pass\r\nWIFI CONNECTED\r\nWIFI GOT IP\r\n\
+        smartconfig connected wifi\r\n",
+    );
+    let mut store = MockStore::default();
+    let mut session = SmartConfig::new(SmartConfigProtocol::EspTouchAirKiss)
+        .timeout(Some(1_000_000))
+        .run(module, &mut store)
+        .unwrap();
+    let config = store.0.unwrap();
+    assert_eq!(config.ssid, "home");
+    assert_eq!(config.password.as_deref(), Some("pass"));
+    assert!(serial
+        .take_written()
+        .ends_with(b"AT+CWSTARTSMART=3\r\nAT+CWSTOPSMART\r\n"));
+    // The connection notifications are part of the SmartConfig output.
+    assert!(session.poll_network_event().is_err());
+
+    // The SmartConfig is stopped even if the credentials have not been received.
+    let module: Module<_, _, _, 256> = Module::new(
+        serial.clone(),
+        serial.clone(),
+        MockClock::new().with_step(100),
+    )
+    .unwrap();
+    serial.take_written();
+    let err = SmartConfig::new(SmartConfigProtocol::EspTouch)
+        .timeout(Some(1_000))
+        .run(module, &mut MockStore::default())
+        .unwrap_err();
+    assert_eq!(err, Error::Timeout);
+    assert!(serial
+        .take_written()
+        .ends_with(b"AT+CWSTARTSMART=1\r\nAT+CWSTOPSMART\r\n"));
+}
+
+#[test]
+fn test_join_ap_wps() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let module: Module<_, _, _, 256> =
+        Module::new(serial.clone(), serial.clone(), MockClock::new()).unwrap();
+    serial.take_written();
+
+    serial.expect(
+        b"AT+WPS=1\r\n",
+        b"OK\r\nwps success,connecting ap ...\r\nWIFI CONNECTED\r\nWIFI GOT IP\r\n",
+    );
+    let mut session = JoinApConfig::wps(module, 1_000_000).unwrap();
+    assert!(serial
+        .take_written()
+        .ends_with(b"AT+CIPMUX=1\r\nAT+CIPDINFO=1\r\nAT+WPS=1\r\n"));
+
+    // The notifications are left for the session.
+    let events = session
+        .events_owned::<8>()
+        .filter_map(|event| event.ok())
+        .collect::<Vec<_, 8>>();
+    assert!(events.contains(&OwnedNetworkEvent::WifiGotIp));
+
+    // The WPS is stopped after the failure.
+    let module: Module<_, _, _, 256> =
+        Module::new(serial.clone(), serial.clone(), MockClock::new()).unwrap();
+    serial.take_written();
+    serial.expect(b"AT+WPS=1\r\n", b"OK\r\nwps fail\r\n");
+    let err = JoinApConfig::wps(module, 1_000_000).unwrap_err();
+    assert_eq!(err, Error::JoinApError);
+    assert!(serial.take_written().ends_with(b"AT+WPS=1\r\nAT+WPS=0\r\n"));
+
+    let module: Module<_, _, _, 256> = Module::new(
+        serial.clone(),
+        serial.clone(),
+        MockClock::new().with_step(100),
+    )
+    .unwrap();
+    let err = JoinApConfig::wps(module, 1_000).unwrap_err();
+    assert_eq!(err, Error::Timeout);
+}
+
+#[test]
+fn test_hostname() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+    serial.take_written();
+
+    session.set_hostname("kitchen-sensor").unwrap();
+    serial.assert_written(b"AT+CWHOSTNAME=\"kitchen-sensor\"\r\n");
+    for hostname in ["", "kitchen sensor", "kitchen\"", &"a".repeat(33)] {
+        assert_eq!(
+            session.set_hostname(hostname),
+            Err(Error::InvalidConfig(ConfigError::InvalidHostname))
+        );
+    }
+    assert!(serial.take_written().is_empty());
+
+    serial.expect(
+        b"AT+CWHOSTNAME?\r\n",
+        b"+CWHOSTNAME:kitchen-sensor\r\n\r\nOK\r\n",
+    );
+    assert_eq!(session.hostname().unwrap(), "kitchen-sensor");
+}
+
+#[test]
+fn test_firmware_mdns() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+    serial.take_written();
+
+    session.enable_mdns("sensor", "_http", 80).unwrap();
+    serial.assert_written(b"AT+MDNS=1,\"sensor\",\"_http\",80\r\n");
+    assert_eq!(
+        session.enable_mdns("my sensor", "_http", 80),
+        Err(Error::InvalidConfig(ConfigError::InvalidHostname))
+    );
+
+    session.disable_mdns().unwrap();
+    serial.assert_written(b"AT+MDNS=0\r\n");
+}
+
+#[test]
+fn test_ping() {
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+    serial.take_written();
+
+    serial.expect(b"AT+PING=\"example.com\"\r\n", b"+25\r\n\r\nOK\r\n");
+    let time = session.ping("example.com").unwrap();
+    assert_eq!(time, PingTime(25));
+    assert_eq!(time.as_duration(), Duration::from_millis(25));
+
+    serial.expect(b"AT+PING=\"192.168.1.1\"\r\n", b"+PING:3\r\n\r\nOK\r\n");
+    assert_eq!(session.ping("192.168.1.1"), Ok(PingTime(3)));
+
+    serial.expect(b"AT+PING=\"10.0.0.1\"\r\n", b"+timeout\r\n\r\nERROR\r\n");
+    assert_eq!(session.ping("10.0.0.1"), Err(Error::CommandFailed));
+}
+
+#[cfg(feature = "embedded-nal")]
+#[test]
+fn test_nal_tcp_stack() {
+    use embedded_nal::{TcpClientStack, TcpFullStack};
+
+    use crate::NalStack;
+
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+    serial.take_written();
+    let mut stack = NalStack::<_, _, _, 256, 64>::new(&mut session);
+
+    // The refused connection is an error, the socket stays unconnected.
+    let mut client = stack.socket().unwrap();
+    let remote = core::net::SocketAddr::from(([192, 168, 1, 5], 8080));
+    serial.expect(
+        b"AT+CIPSTART=0,\"TCP\",\"192.168.1.5\",8080\r\n",
+        b"ERROR\r\n",
+    );
+    assert_eq!(
+        stack.connect(&mut client, remote),
+        Err(nb::Error::Other(Error::CommandFailed))
+    );
+    assert_eq!(client.link_id(), None);
+
+    // The client socket takes the lowest free link.
+    serial.expect(
+        b"AT+CIPSTART=0,\"TCP\",\"192.168.1.5\",8080\r\n",
+        b"0,CONNECT\r\n\r\nOK\r\n",
+    );
+    stack.connect(&mut client, remote).unwrap();
+    assert_eq!(client.link_id(), Some(0));
+
+    serial.expect(b"AT+CIPSEND=0,4\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"ping", b"\r\nRecv 4 bytes\r\n\r\nSEND OK\r\n");
+    assert_eq!(stack.send(&mut client, b"ping").unwrap(), 4);
+    serial.expect(b"AT+CIPSEND=0,4\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"ping", b"\r\nRecv 4 bytes\r\n\r\nSEND FAIL\r\n");
+    assert_eq!(
+        stack.send(&mut client, b"ping"),
+        Err(nb::Error::Other(Error::CommandFailed))
+    );
+    serial.receive(b"+IPD,0,4:pong");
+    let mut buf = [0_u8; 16];
+    let len = nb::block!(stack.receive(&mut client, &mut buf)).unwrap();
+    assert_eq!(&buf[..len], b"pong");
+
+    // The server connections are accepted on the links assigned by the module.
+    let mut server = stack.socket().unwrap();
+    stack.bind(&mut server, 80).unwrap();
+    stack.listen(&mut server).unwrap();
+    serial.receive(b"1,CONNECT\r\n");
+    serial.expect(
+        b"AT+CIPSTATUS\r\n",
+        b"STATUS:3\r\n+CIPSTATUS:1,\"TCP\",\"192.168.1.7\",51000,80,1\r\n\r\nOK\r\n",
+    );
+    let (mut accepted, remote) = nb::block!(stack.accept(&mut server)).unwrap();
+    assert_eq!(accepted.link_id(), Some(1));
+    assert_eq!(
+        remote,
+        core::net::SocketAddr::from(([192, 168, 1, 7], 51000))
+    );
+
+    // The closed connection reports the end of the stream.
+    serial.receive(b"1,CLOSED\r\n");
+    assert_eq!(
+        nb::block!(stack.receive(&mut accepted, &mut buf)),
+        Err(Error::ConnectionClosed)
+    );
+    stack.close(accepted).unwrap();
+    stack.close(client).unwrap();
+    stack.close(server).unwrap();
+    assert!(serial
+        .take_written()
+        .ends_with(b"AT+CIPCLOSE=0\r\nAT+CIPSERVER=0\r\n"));
+    assert!(serial.is_script_done());
+}
+
+#[test]
+#[cfg(feature = "embedded-nal")]
+fn test_nal_udp_stack() {
+    use embedded_nal::{UdpClientStack, UdpFullStack};
+
+    use crate::NalStack;
+
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session(&serial);
+    serial.take_written();
+    let mut stack = NalStack::<_, _, _, 256, 64>::new(&mut session);
+
+    // The bound socket reports the source addresses of the datagrams.
+    let mut socket = stack.socket().unwrap();
+    stack.bind(&mut socket, 5000).unwrap();
+    assert_eq!(socket.link_id(), Some(0));
+    serial.receive(b"+IPD,0,4,\"192.168.1.9\",5001:ping");
+    serial.receive(b"+IPD,0,5,\"192.168.1.8\",5002:hello");
+    let mut buf = [0_u8; 16];
+    let (len, remote) = nb::block!(stack.receive(&mut socket, &mut buf)).unwrap();
+    assert_eq!(&buf[..len], b"ping");
+    assert_eq!(
+        remote,
+        core::net::SocketAddr::from(([192, 168, 1, 9], 5001))
+    );
+    // The datagrams are not merged.
+    let (len, remote) = nb::block!(stack.receive(&mut socket, &mut buf)).unwrap();
+    assert_eq!(&buf[..len], b"hello");
+    assert_eq!(
+        remote,
+        core::net::SocketAddr::from(([192, 168, 1, 8], 5002))
+    );
+
+    // The unread datagram doesn't block the other links, the next datagrams of its
+    // socket are dropped.
+    let mut other = stack.socket().unwrap();
+    stack.bind(&mut other, 5003).unwrap();
+    assert_eq!(other.link_id(), Some(1));
+    serial.receive(b"+IPD,0,3,\"192.168.1.9\",5001:one");
+    serial.receive(b"+IPD,0,3,\"192.168.1.9\",5001:two");
+    serial.receive(b"+IPD,1,5,\"192.168.1.7\",5004:three");
+    let (len, _) = nb::block!(stack.receive(&mut other, &mut buf)).unwrap();
+    assert_eq!(&buf[..len], b"three");
+    let (len, _) = nb::block!(stack.receive(&mut socket, &mut buf)).unwrap();
+    assert_eq!(&buf[..len], b"one");
+    assert_eq!(
+        stack.receive(&mut socket, &mut buf),
+        Err(nb::Error::WouldBlock)
+    );
+    stack.close(other).unwrap();
+    serial.take_written();
+
+    serial.expect(b"AT+CIPSEND=0,4,\"192.168.1.9\",5001\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"pong", b"\r\nRecv 4 bytes\r\n\r\nSEND OK\r\n");
+    stack
+        .send_to(
+            &mut socket,
+            core::net::SocketAddr::from(([192, 168, 1, 9], 5001)),
+            b"pong",
+        )
+        .unwrap();
+
+    // The connected socket uses the next link, the rejected one is an error.
+    let mut client = stack.socket().unwrap();
+    let remote = core::net::SocketAddr::from(([192, 168, 1, 5], 53));
+    serial.expect(
+        b"AT+CIPSTART=1,\"UDP\",\"192.168.1.5\",53\r\n",
+        b"ERROR\r\n",
+    );
+    assert_eq!(
+        stack.connect(&mut client, remote),
+        Err(Error::CommandFailed)
+    );
+    assert_eq!(client.link_id(), None);
+    serial.expect(
+        b"AT+CIPSTART=1,\"UDP\",\"192.168.1.5\",53\r\n",
+        b"1,CONNECT\r\n\r\nOK\r\n",
+    );
+    stack.connect(&mut client, remote).unwrap();
+    assert_eq!(client.link_id(), Some(1));
+    assert_eq!(
+        stack.receive(&mut client, &mut buf),
+        Err(nb::Error::WouldBlock)
+    );
+
+    stack.close(client).unwrap();
+    stack.close(socket).unwrap();
+    assert!(serial
+        .take_written()
+        .ends_with(b"AT+CIPCLOSE=1\r\nAT+CIPCLOSE=0\r\n"));
+    assert!(serial.is_script_done());
+}
+
+#[test]
+#[cfg(feature = "async")]
+fn test_async_session() {
+    use core::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+    };
+
+    use crate::AsyncModule;
+
+    /// Polls the future in a loop, the mock serial port never wakes it.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        const VTABLE: RawWakerVTable = RawWakerVTable::new(
+            |_| RawWaker::new(core::ptr::null(), &VTABLE),
+            |_| {},
+            |_| {},
+            |_| {},
+        );
+        let waker = unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &VTABLE)) };
+        let mut cx = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        for _ in 0..1_000 {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+        panic!("The future is stalled");
+    }
+
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let clock = MockClock::new();
+    let mut session = block_on(async {
+        let mut module = AsyncModule::<_, _, 256>::new(serial.clone(), clock.clone()).await?;
+        module.set_timeout(Some(1_000_000));
+        JoinApConfig {
+            ssid: "ssid",
+            password: Some("password"),
+            options: None,
+        }
+        .join_async(module)
+        .await
+    })
+    .unwrap();
+    serial.assert_written(
+        b"ATE0\r\n\
+        AT+CWMODE=1\r\n\
+        AT+CIPMUX=1\r\n\
+        AT+CIPDINFO=1\r\n\
+        AT+CWJAP=\"ssid\",\"password\"\r\n",
+    );
+
+    // The unsolicited frames received during the command are returned by the next polls.
+    serial.expect(
+        b"AT+CIPSTART=0,\"TCP\",\"192.168.1.5\",8080\r\n",
+        b"0,CONNECT\r\n\r\nOK\r\n",
+    );
+    serial.expect(b"AT+CIPSEND=0,4\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"ping", b"\r\nRecv 4 bytes\r\n\r\nSEND OK\r\n+IPD,0,4:pong");
+    block_on(async {
+        session
+            .connect(0, crate::net::SocketAddr::from(([192, 168, 1, 5], 8080)))
+            .await?;
+        session.send(0, b"ping").await
+    })
+    .unwrap();
+    assert!(matches!(
+        block_on(session.next_event()),
+        Ok(NetworkEvent::Connected { link_id: 0 })
+    ));
+    match block_on(session.next_event()).unwrap() {
+        NetworkEvent::DataAvailable { link_id, data, .. } => {
+            assert_eq!(link_id, 0);
+            assert_eq!(data.as_ref(), b"pong");
+        }
+        other => panic!("Unexpected event: {:?}", other),
+    }
+
+    // The refused connection and the failed send are errors.
+    serial.expect(
+        b"AT+CIPSTART=1,\"TCP\",\"192.168.1.5\",8081\r\n",
+        b"ERROR\r\n",
+    );
+    assert_eq!(
+        block_on(session.connect(1, crate::net::SocketAddr::from(([192, 168, 1, 5], 8081)))),
+        Err(Error::CommandFailed)
+    );
+    serial.expect(b"AT+CIPSEND=0,4\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"ping", b"\r\nRecv 4 bytes\r\n\r\nSEND FAIL\r\n");
+    assert_eq!(
+        block_on(session.send(0, b"ping")),
+        Err(Error::CommandFailed)
+    );
+
+    // The silent module is reported by the timeout.
+    serial.expect(b"AT+CIPCLOSE=0\r\n", b"");
+    assert_eq!(block_on(session.close(0)).unwrap_err(), Error::Timeout);
+    assert!(serial.is_script_done());
+}
+
+#[test]
+#[cfg(feature = "embedded-io")]
+fn test_socket_handle() {
+    use embedded_io::{Read, Write};
+
+    let serial = MockSerial::new().with_default_reply(b"OK\r\n");
+    let mut session = mock_session_with_clock(&serial, MockClock::new().with_step(100));
+    session.set_timeout(Some(10_000));
+    serial.take_written();
+    let mut socket = session.socket_handle(0);
+
+    serial.expect(b"AT+CIPSEND=0,4\r\n", b"\r\nOK\r\n> ");
+    serial.expect(b"ping", b"\r\nRecv 4 bytes\r\n\r\nSEND OK\r\n");
+    socket.write_all(b"ping").unwrap();
+
+    // The data of the other links is discarded, the segments are read by parts.
+    serial.receive(b"+IPD,1,3:abc+IPD,0,6:hello!");
+    let mut buf = [0_u8; 4];
+    assert_eq!(socket.read(&mut buf).unwrap(), 4);
+    assert_eq!(&buf, b"hell");
+    assert_eq!(socket.read(&mut buf).unwrap(), 2);
+    assert_eq!(&buf[..2], b"o!");
+    assert_eq!(socket.read(&mut buf), Err(Error::Timeout));
+
+    // The closed link reports the end of the stream.
+    serial.receive(b"0,CLOSED\r\n");
+    assert_eq!(socket.read(&mut buf).unwrap(), 0);
+    assert!(socket.is_closed());
+    assert_eq!(socket.write(b"ping"), Err(Error::ConnectionClosed));
+    assert!(serial.is_script_done());
+}
@@ -0,0 +1,61 @@
+//! Post-mortem trace of the bytes exchanged with the module.
+
+use core::fmt;
+
+use heapless::HistoryBuffer;
+
+/// Number of the last bytes kept in the trace for each direction.
+pub const TRACE_BUFFER_LEN: usize = 128;
+
+/// Ring buffer with the last bytes sent or received over the serial port.
+pub(crate) type TraceRing = HistoryBuffer<u8, TRACE_BUFFER_LEN>;
+
+/// Last bytes that have been exchanged with the module.
+///
+/// It allows to dump what actually went over the wire when a command fails with
+/// the timeout or the module responds with something unexpected.
+///
+/// See [`Module::trace_buffer`](crate::Module::trace_buffer).
+#[derive(Clone, Copy)]
+pub struct TraceBuffer<'a> {
+    sent: &'a TraceRing,
+    received: &'a TraceRing,
+}
+
+impl<'a> TraceBuffer<'a> {
+    pub(crate) fn new(sent: &'a TraceRing, received: &'a TraceRing) -> Self {
+        Self { sent, received }
+    }
+
+    /// Returns the last bytes sent to the module from the oldest to the newest.
+    pub fn sent(&self) -> impl Iterator<Item = u8> + 'a {
+        self.sent.oldest_ordered().copied()
+    }
+
+    /// Returns the last bytes received from the module from the oldest to the newest.
+    pub fn received(&self) -> impl Iterator<Item = u8> + 'a {
+        self.received.oldest_ordered().copied()
+    }
+}
+
+/// Prints the bytes as an ASCII string with the escaped control characters.
+struct PrintTrace<'a>(&'a TraceRing);
+
+impl fmt::Debug for PrintTrace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("\"")?;
+        for byte in self.0.oldest_ordered() {
+            write!(f, "{}", core::ascii::escape_default(*byte))?;
+        }
+        f.write_str("\"")
+    }
+}
+
+impl fmt::Debug for TraceBuffer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TraceBuffer")
+            .field("sent", &PrintTrace(self.sent))
+            .field("received", &PrintTrace(self.received))
+            .finish()
+    }
+}
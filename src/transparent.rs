@@ -0,0 +1,121 @@
+//! Transparent transmission mode, in which the serial port data is passed through
+//! to the single connection as is.
+
+use embedded_hal::serial;
+use simple_clock::SimpleClock;
+
+use crate::{
+    module::{find_line, Condition},
+    reader_part::ReadData,
+    Error, Module, NetworkSession,
+};
+
+/// Time without any transmitted data that should surround the escape sequence.
+const ESCAPE_GUARD_US: u64 = 1_000_000;
+
+/// Maximum time to wait for the module to confirm the return to the normal mode
+/// if the operation timeout is not set or longer.
+const MODE_SWITCH_TIMEOUT_US: u64 = 3_000_000;
+
+/// Sequence that makes the module leave the transparent mode.
+const ESCAPE_SEQUENCE: &[u8] = b"+++";
+
+/// Prompt that marks the beginning of the transparent transmission, or the error if
+/// the transmission cannot be started.
+#[derive(Clone, Copy)]
+struct TransparentPrompt;
+
+impl TransparentPrompt {
+    const PROMPT: u8 = b'>';
+    const ERROR: &'static [u8] = b"ERROR\r\n";
+}
+
+impl<'a, const N: usize> Condition<'a, N> for TransparentPrompt {
+    type Output = Result<(), ()>;
+
+    fn is_performed(self, buf: &[u8]) -> bool {
+        buf.contains(&Self::PROMPT) || find_line(buf, Self::ERROR).is_some()
+    }
+
+    fn output(self, mut buf: ReadData<'a, N>) -> Self::Output {
+        // The response is removed from the buffer together with the prompt, so the prompt
+        // is not taken for the transmitted data.
+        match buf.iter().position(|&byte| byte == Self::PROMPT) {
+            Some(pos) => {
+                buf.cut(pos, 1);
+                Ok(())
+            }
+            None => Err(()),
+        }
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> Module<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Enters the transparent transmission mode.
+    ///
+    /// The module should be in the single connection mode (`AT+CIPMUX=0`) with
+    /// the established connection. The data is transmitted by the
+    /// [`write_raw`](Self::write_raw) and [`read_raw`](Self::read_raw) methods until
+    /// the [`exit_transparent`](Self::exit_transparent) call.
+    pub fn enter_transparent(&mut self) -> crate::Result<()> {
+        self.send_at_command("AT+CIPMODE=1")?
+            .map_err(|_| Error::CommandFailed)?;
+
+        self.write_command_fmt(format_args!("AT+CIPSEND"))?;
+        self.read_until(TransparentPrompt)?
+            .map_err(|_| Error::CommandFailed)
+    }
+
+    /// Leaves the transparent transmission mode by the `+++` escape sequence and resumes
+    /// the normal AT commands operation.
+    ///
+    /// The escape sequence is recognized only if it is surrounded by the one second pauses
+    /// without any transmitted data, so this method blocks for at least two seconds. The data
+    /// received before the mode switch is discarded. Returns the [`Error::Timeout`] error if
+    /// the module doesn't confirm the switch in time.
+    pub fn exit_transparent(&mut self) -> crate::Result<()> {
+        self.writer.flush()?;
+        self.delay(ESCAPE_GUARD_US);
+        // The sequence should not be followed by the line break.
+        self.writer.write_all(ESCAPE_SEQUENCE)?;
+        self.writer.flush()?;
+        self.delay(ESCAPE_GUARD_US);
+        self.reader.buf_mut().clear();
+
+        let timeout = self.timeout;
+        self.timeout = Some(timeout.map_or(MODE_SWITCH_TIMEOUT_US, |timeout| {
+            core::cmp::min(timeout, MODE_SWITCH_TIMEOUT_US)
+        }));
+        let switched = self
+            .send_at_command("AT+CIPMODE=0")
+            .and_then(|response| response.map(drop).map_err(|_| Error::CommandFailed));
+        self.timeout = timeout;
+        switched
+    }
+}
+
+impl<Rx, Tx, C, const N: usize> NetworkSession<Rx, Tx, C, N>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    /// Enters the transparent transmission mode.
+    ///
+    /// See [`Module::enter_transparent`].
+    pub fn enter_transparent(&mut self) -> crate::Result<()> {
+        self.module.enter_transparent()
+    }
+
+    /// Leaves the transparent transmission mode.
+    ///
+    /// See [`Module::exit_transparent`].
+    pub fn exit_transparent(&mut self) -> crate::Result<()> {
+        self.module.exit_transparent()
+    }
+}
@@ -0,0 +1,81 @@
+//! Joining the access point by the WPS push-button method.
+
+use embedded_hal::serial;
+use simple_clock::{Deadline, SimpleClock};
+
+use crate::{module::find_line, softap::init_station, Error, JoinApConfig, Module, NetworkSession};
+
+/// Notification that the module has joined the access point and obtained the address.
+const GOT_IP: &[u8] = b"WIFI GOT IP\r\n";
+/// Notification that the access point has not responded or rejected the module.
+const WPS_FAIL: &[u8] = b"wps fail\r\n";
+
+impl<'a> JoinApConfig<'a> {
+    /// Joins the access point by the WPS push-button method and establishes a new WiFi
+    /// session.
+    ///
+    /// The WPS button of the access point should be pressed before or soon after this call.
+    /// The method blocks until the module obtains the IP address, or returns the
+    /// [`Error::Timeout`] error after the given time in microseconds. Returns the
+    /// [`Error::JoinApError`] error if the access point has rejected the module.
+    ///
+    /// # Notes
+    ///
+    /// The module saves the received credentials to its flash and joins the access point
+    /// by itself after the reboot.
+    pub fn wps<Rx, Tx, C, const N: usize>(
+        mut module: Module<Rx, Tx, C, N>,
+        timeout_us: u64,
+    ) -> crate::Result<NetworkSession<Rx, Tx, C, N>>
+    where
+        Rx: serial::Read<u8> + 'static,
+        Tx: serial::Write<u8> + 'static,
+        C: SimpleClock,
+    {
+        // The WPS is available only in the station mode.
+        init_station(&mut module)?;
+        module
+            .send_at_command("AT+WPS=1")?
+            .map_err(|_| Error::CommandFailed)?;
+
+        if let Err(err) = wait_got_ip(&mut module, timeout_us) {
+            // Stop the WPS to let the module accept the other commands.
+            module.send_at_command("AT+WPS=0")?.ok();
+            return Err(err);
+        }
+        Ok(NetworkSession::new(module))
+    }
+}
+
+/// Reads bytes into the buffer until the module obtains the IP address, the notifications
+/// are left in the buffer for the network session.
+fn wait_got_ip<Rx, Tx, C, const N: usize>(
+    module: &mut Module<Rx, Tx, C, N>,
+    timeout_us: u64,
+) -> crate::Result<()>
+where
+    Rx: serial::Read<u8> + 'static,
+    Tx: serial::Write<u8> + 'static,
+    C: SimpleClock,
+{
+    let deadline = Deadline::new(&module.clock, timeout_us);
+    loop {
+        match module.reader.read_bytes() {
+            Ok(_) | Err(nb::Error::WouldBlock) => {}
+            Err(nb::Error::Other(err)) => return Err(err),
+        }
+
+        let buf = module.reader.buf();
+        if find_line(buf, GOT_IP).is_some() {
+            return Ok(());
+        }
+        if find_line(buf, WPS_FAIL).is_some() {
+            return Err(Error::JoinApError);
+        }
+        if buf.is_full() {
+            return Err(Error::BufferFull);
+        }
+
+        deadline.reached().map_err(|_| Error::Timeout)?;
+    }
+}
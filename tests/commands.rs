@@ -36,6 +36,7 @@ fn integration_test_softap() {
         password: "12345678",
         channel: 4,
         mode: WifiMode::Open,
+        address: None,
     }
     .start(module)
     .expect("unable to start network sesstion");
@@ -53,7 +54,8 @@ fn integration_test_joinap_ok() {
 
     let mut session = JoinApConfig {
         ssid: &necessary_env_var("ESP8266_WIFI_SERIAL_SSID"),
-        password: &necessary_env_var("ESP8266_WIFI_SERIAL_PASSWORD"),
+        password: Some(&necessary_env_var("ESP8266_WIFI_SERIAL_PASSWORD")),
+        options: None,
     }
     .join(module)
     .expect("unable to start network sesstion");
@@ -95,7 +97,8 @@ fn integration_test_joinap_fail() {
 
     let err = JoinApConfig {
         ssid: "some weird network",
-        password: "my password aaaa",
+        password: Some("my password aaaa"),
+        options: None,
     }
     .join(module)
     .expect_err("joining to the AP should fail");
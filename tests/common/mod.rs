@@ -7,7 +7,7 @@ use std::{
 };
 
 use embedded_hal::serial::{Read, Write};
-use esp8266_wifi_serial::{clock::SimpleClock, Module};
+use esp8266_wifi_serial::{clock::SimpleClock, Module, ResetKind};
 use once_cell::sync::Lazy;
 use serialport::SerialPort;
 
@@ -38,7 +38,7 @@ impl SimpleClock for ClockImpl {
 
 #[derive(Clone)]
 struct SerialPortWrapper {
-    guard: Rc<MutexGuard<'static, ()>>,
+    _guard: Rc<MutexGuard<'static, ()>>,
     inner: Rc<RefCell<Box<dyn SerialPort>>>,
 }
 
@@ -51,16 +51,16 @@ impl Debug for SerialPortWrapper {
 impl SerialPortWrapper {
     fn new(port: Box<dyn SerialPort>) -> Self {
         Self {
-            guard: Rc::new(ONCE_LOCK.lock().unwrap()),
+            _guard: Rc::new(ONCE_LOCK.lock().unwrap()),
             inner: Rc::new(RefCell::new(port)),
         }
     }
 
-    fn borrow(&self) -> Ref<Box<dyn SerialPort>> {
+    fn borrow(&self) -> Ref<'_, Box<dyn SerialPort>> {
         self.inner.borrow()
     }
 
-    fn borrow_mut(&self) -> RefMut<Box<dyn SerialPort>> {
+    fn borrow_mut(&self) -> RefMut<'_, Box<dyn SerialPort>> {
         self.inner.borrow_mut()
     }
 }
@@ -125,7 +125,7 @@ pub fn default_esp8266_serial_module() -> anyhow::Result<
 
     let mut module = Module::new(rx, tx, ClockImpl).map_err(from_debug)?;
     module.set_timeout(Some(RESET_TIMEOUT_US));
-    module.reset().map_err(from_debug)?;
+    module.reset(ResetKind::Soft).map_err(from_debug)?;
     module.set_timeout(Some(DEFAULT_TIMEOUT_US));
 
     Ok(module)
@@ -0,0 +1,225 @@
+use std::{hint::black_box, path::PathBuf};
+
+use esp8266_wifi_serial::{
+    net::Ipv4Addr, DhcpLeaseRange, JoinApConfig, SoftApConfig, WifiInterface, WifiMode,
+};
+
+use common::{default_esp8266_serial_module, from_debug, necessary_env_var};
+
+mod common;
+
+/// Panic messages of the driver that are not checked by the `ensure!` macro, they are
+/// capitalized at runtime to keep the messages themselves out of this test binary.
+///
+/// The messages of the `ensure!` checks are collected from the driver sources, see
+/// [`ensure_messages`].
+const PANIC_MESSAGES: [&str; 5] = [
+    "malformed command",
+    "the condition is not performed",
+    "buffer overflow",
+    "hTTP header overflow",
+    "the wakeup GPIO number is not supported by the chip",
+];
+
+/// Calls that panic regardless of the `no-panic` feature.
+const PANICKING_CALLS: [&str; 7] = [
+    ".unwrap()",
+    ".expect(",
+    "assert!(",
+    "panic!(",
+    "unreachable!(",
+    "unimplemented!(",
+    "todo!(",
+];
+
+/// Attribute of the items that are compiled only if the `no-panic` feature is disabled.
+const PANIC_GATE: &str = "#[cfg(not(feature = \"no-panic\"))]";
+
+/// Reads the driver sources, except the unit tests and their utilities.
+fn driver_sources() -> anyhow::Result<Vec<(PathBuf, String)>> {
+    let src_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let mut sources = Vec::new();
+    for entry in std::fs::read_dir(src_dir)? {
+        let path = entry?.path();
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("");
+        if file_name == "tests.rs" || file_name == "test_utils.rs" {
+            continue;
+        }
+
+        let source = std::fs::read_to_string(&path)?;
+        sources.push((path, source));
+    }
+    Ok(sources)
+}
+
+/// Collects the messages of the `ensure!` checks from the driver sources, they are read
+/// at runtime to keep the messages themselves out of this test binary.
+fn ensure_messages() -> anyhow::Result<Vec<String>> {
+    const ENSURE: &str = "ensure!(";
+
+    let mut messages = Vec::new();
+    for (_, source) in driver_sources()? {
+        for (pos, _) in source.match_indices(ENSURE) {
+            messages.extend(ensure_message(&source[pos + ENSURE.len()..]));
+        }
+    }
+    Ok(messages)
+}
+
+/// Returns the message literal of the `ensure!` arguments, i.e. the first string literal
+/// after the condition.
+fn ensure_message(args: &str) -> Option<String> {
+    let mut chars = args.chars();
+    let mut depth = 0;
+    let mut after_condition = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' if depth == 0 => return None,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => after_condition = true,
+            '"' => {
+                let mut literal = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => literal.extend(chars.next()),
+                        '"' => break,
+                        c => literal.push(c),
+                    }
+                }
+                if after_condition && depth == 0 {
+                    return Some(literal);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Uses the driver, so its code gets into the test binary.
+fn use_driver() -> anyhow::Result<()> {
+    let mut module = default_esp8266_serial_module()?;
+    module
+        .set_sta_mac([0x02, 0, 0, 0, 0, 0x01])
+        .map_err(from_debug)?;
+    module
+        .set_ap_mac([0x02, 0, 0, 0, 0, 0x02])
+        .map_err(from_debug)?;
+    let mut session = JoinApConfig {
+        ssid: &necessary_env_var("ESP8266_WIFI_SERIAL_SSID"),
+        password: None,
+        options: None,
+    }
+    .join(module)
+    .map_err(from_debug)?;
+
+    session.set_max_connections(1).map_err(from_debug)?;
+    session.listen(80).map_err(from_debug)?;
+    session
+        .send(0, b"ping".iter().copied())
+        .map_err(from_debug)?;
+    session.watch_link(0, true);
+    if !session.is_link_dead(0) {
+        session.defer_close(0).map_err(from_debug)?;
+    }
+    session.close(0).map_err(from_debug)?;
+
+    let module = default_esp8266_serial_module()?;
+    let mut session = SoftApConfig {
+        ssid: "ssid",
+        password: "password",
+        channel: 5,
+        mode: WifiMode::WpaWpa2Psk,
+        address: None,
+    }
+    .start(module)
+    .map_err(from_debug)?;
+    session
+        .set_dhcp(WifiInterface::SoftAp, true)
+        .map_err(from_debug)?;
+    session
+        .set_dhcp_lease_range(Some(DhcpLeaseRange {
+            start: Ipv4Addr::new(192, 168, 4, 2),
+            end: Ipv4Addr::new(192, 168, 4, 10),
+            lease_time: 60,
+        }))
+        .map_err(from_debug)?;
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(
+    not(feature = "no-panic"),
+    ignore = "feature \"no-panic\" is disabled."
+)]
+fn test_no_panic_messages() -> anyhow::Result<()> {
+    // The driver code is never executed.
+    if black_box(false) {
+        use_driver()?;
+    }
+
+    let messages = ensure_messages()?;
+    assert!(
+        !messages.is_empty(),
+        "The ensure! messages have not been found"
+    );
+
+    let binary = std::fs::read(std::env::current_exe()?)?;
+    let other_messages = PANIC_MESSAGES
+        .iter()
+        .map(|message| message[..1].to_uppercase() + &message[1..]);
+    for message in messages.into_iter().chain(other_messages) {
+        assert!(
+            !binary
+                .windows(message.len())
+                .any(|window| window == message.as_bytes()),
+            "The binary contains the panic message: {}",
+            message
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_no_panicking_calls() -> anyhow::Result<()> {
+    for (path, source) in driver_sources()? {
+        // The unit tests are placed at the end of the file.
+        let mut lines = source
+            .lines()
+            .enumerate()
+            .take_while(|(_, line)| line.trim() != "#[test]");
+        while let Some((number, line)) = lines.next() {
+            let code = line.trim_start();
+            if code == PANIC_GATE {
+                // Skip the gated item, it ends with the line of the same indentation.
+                let indent = line.len() - code.len();
+                for (_, item_line) in lines.by_ref() {
+                    let item_code = item_line.trim_start();
+                    if item_line.len() - item_code.len() == indent
+                        && (item_code.ends_with(';') || item_code == "}")
+                    {
+                        break;
+                    }
+                }
+                continue;
+            }
+            // Comments and compile time assertions never panic at runtime.
+            if code.starts_with("//") || code.contains("const ") {
+                continue;
+            }
+
+            assert!(
+                !PANICKING_CALLS.iter().any(|call| code.contains(call)),
+                "{}:{} may panic: {}",
+                path.display(),
+                number + 1,
+                code
+            );
+        }
+    }
+    Ok(())
+}